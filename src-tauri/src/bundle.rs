@@ -0,0 +1,267 @@
+//! Self-contained HTML exports.
+//!
+//! The preview loads local images one at a time through `read_file_base64`,
+//! so a page that keeps their relative URLs only works beside the document.
+//! Here the page is rendered by `render` from the Markdown, and every local
+//! image, in Markdown or in raw HTML, is inlined as a data URI or copied
+//! into an `assets` folder next to the page. The export can also keep the
+//! links as they are, for a page saved beside the document. Images are read with
+//! `read_image`, under the rules `read_file_base64` has: they must lie under
+//! the document's folder, be no larger than `MAX_IMAGE_SIZE` and be in a
+//! format the preview can show. Images that break a rule keep their URL and
+//! are listed in the report.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, PaddownError};
+use crate::{relink, render, scope, tasks};
+
+/// The folder beside an exported page that `ImageMode::Assets` copies to.
+pub const ASSETS_DIR: &str = "assets";
+
+/// Where a self-contained page keeps its images.
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageMode {
+    /// In the page, as data URIs.
+    Inline,
+    /// In an `assets` folder beside the page.
+    Assets,
+}
+
+impl ImageMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "inline" => Some(ImageMode::Inline),
+            "assets" => Some(ImageMode::Assets),
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct SkippedImage {
+    pub url: String,
+    pub error: PaddownError,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct BundleReport {
+    /// Images inlined or copied.
+    pub images: u64,
+    /// Local images left with their URL.
+    pub skipped: Vec<SkippedImage>,
+}
+
+/// The file a local image URL refers to, resolved against the document's
+/// folder as the preview does. `None` for URLs with a scheme, like `https:`
+/// or `data:`, and for anchors.
+pub(crate) fn image_path(dir: &Path, url: &str) -> Option<PathBuf> {
+    let (target, _) = relink::split_suffix(url);
+    let decoded = relink::percent_decode(target);
+    if decoded.is_empty() || (!relink::is_relative(url) && !Path::new(&decoded).is_absolute()) {
+        return None;
+    }
+    Some(dir.join(decoded))
+}
+
+/// `name` with the characters that mean something in a URL escaped.
+fn url_escape(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char);
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+struct Bundler<'a> {
+    /// Checks the assets folder against the scope, when exporting from the
+    /// window.
+    app: Option<&'a tauri::AppHandle>,
+    dir: &'a Path,
+    mode: ImageMode,
+    assets: PathBuf,
+    /// The new URL of every image path seen, `None` if it was skipped.
+    done: HashMap<PathBuf, Option<String>>,
+    report: BundleReport,
+}
+
+impl Bundler<'_> {
+    fn image(&mut self, url: &str) -> Option<String> {
+        let path = image_path(self.dir, url)?;
+        if let Some(done) = self.done.get(&path) {
+            return done.clone();
+        }
+        let src = match self.include(&path) {
+            Ok(src) => {
+                self.report.images += 1;
+                Some(src)
+            }
+            Err(error) => {
+                self.report.skipped.push(SkippedImage { url: url.to_string(), error });
+                None
+            }
+        };
+        self.done.insert(path, src.clone());
+        src
+    }
+
+    fn include(&mut self, path: &Path) -> error::Result<String> {
+        let (bytes, mime) = crate::read_image(path, self.dir)?;
+        match self.mode {
+            ImageMode::Inline => Ok(crate::data_uri(&bytes, mime)),
+            ImageMode::Assets => {
+                let name = self.copy(path, &bytes)?;
+                Ok(format!("{}/{}", ASSETS_DIR, url_escape(&name)))
+            }
+        }
+    }
+
+    /// Copies an image into the assets folder, and returns its name there.
+    /// Different images with the same name are told apart with a number;
+    /// a file already there is only replaced by the same contents.
+    fn copy(&mut self, path: &Path, bytes: &[u8]) -> error::Result<String> {
+        if let Some(app) = self.app {
+            scope::check_dir(app, &self.assets)?;
+        }
+        std::fs::create_dir_all(&self.assets).map_err(|e| PaddownError::io(&self.assets, e))?;
+        let stem = path.file_stem().map_or("image".into(), |s| s.to_string_lossy());
+        let extension = path.extension().map(|e| e.to_string_lossy());
+        let taken: Vec<&String> = self.done.values().flatten().collect();
+
+        for n in 1.. {
+            let name = match (&extension, n) {
+                (Some(ext), 1) => format!("{}.{}", stem, ext),
+                (Some(ext), _) => format!("{}-{}.{}", stem, n, ext),
+                (None, 1) => stem.to_string(),
+                (None, _) => format!("{}-{}", stem, n),
+            };
+            let url = format!("{}/{}", ASSETS_DIR, url_escape(&name));
+            if taken.iter().any(|taken| **taken == url) {
+                continue;
+            }
+            let target = self.assets.join(&name);
+            match std::fs::read(&target) {
+                Ok(existing) if existing != bytes => continue,
+                Ok(_) => return Ok(name),
+                Err(_) => {
+                    crate::write_file_atomic(&target, bytes)?;
+                    return Ok(name);
+                }
+            }
+        }
+        unreachable!("some name is always free")
+    }
+}
+
+/// A standalone page for `text`, to be written to `out`, with the images
+/// under `base_dir` (the document's folder) inlined or copied next to it.
+/// Without `base_dir` images are left as they are. With `app`, the assets
+/// folder has to be within the scope.
+pub fn bundle_page(
+    app: Option<&tauri::AppHandle>,
+    text: &str,
+    title: &str,
+    base_dir: Option<&Path>,
+    out: &Path,
+    mode: ImageMode,
+) -> (String, BundleReport) {
+    let Some(dir) = base_dir else {
+        return (render::render_page(text, title), BundleReport::default());
+    };
+    let assets = out.parent().unwrap_or(Path::new("")).join(ASSETS_DIR);
+    let mut bundler = Bundler {
+        app,
+        dir,
+        mode,
+        assets,
+        done: HashMap::new(),
+        report: BundleReport::default(),
+    };
+    let html = render::render_page_with(text, title, &mut |url| bundler.image(url));
+    (html, bundler.report)
+}
+
+/// Writes `markdown` as an HTML page to `path`, a file the user picked with
+/// `show_export_html_dialog`. Without `images` the page keeps the
+/// document's image URLs.
+#[tauri::command]
+pub async fn export_html(
+    app: tauri::AppHandle,
+    path: String,
+    markdown: String,
+    title: String,
+    base_dir: Option<String>,
+    images: Option<ImageMode>,
+) -> error::Result<BundleReport> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        let base_dir = base_dir.map(PathBuf::from);
+        if let Some(dir) = &base_dir {
+            scope::check_dir(&app, dir)?;
+        }
+        let dir = base_dir.as_deref();
+        let (html, report) = match images {
+            Some(mode) => bundle_page(Some(&app), &markdown, &title, dir, path, mode),
+            None => (render::render_page(&markdown, &title), BundleReport::default()),
+        };
+        crate::write_file_atomic(path, html.as_bytes())?;
+        Ok(report)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    #[test]
+    fn names_are_url_escaped() {
+        assert_eq!(url_escape("plain-name_1.png"), "plain-name_1.png");
+        assert_eq!(url_escape("a b#c?.png"), "a%20b%23c%3F.png");
+        assert_eq!(url_escape("caf\u{e9}.png"), "caf%C3%A9.png");
+        assert_eq!(url_escape("50%/x"), "50%25%2Fx");
+    }
+
+    #[test]
+    fn image_paths_are_local_only() {
+        let dir = Path::new("/docs");
+        assert_eq!(image_path(dir, "img/a%20b.png?v=1"), Some(dir.join("img/a b.png")));
+        assert_eq!(image_path(dir, "https://example.com/a.png"), None);
+        assert_eq!(image_path(dir, "data:image/png;base64,AAAA"), None);
+        assert_eq!(image_path(dir, "#top"), None);
+    }
+
+    #[test]
+    fn assets_are_copied_once_and_renamed_on_clashes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        std::fs::create_dir_all(docs.join("other")).unwrap();
+        std::fs::write(docs.join("my pic.png"), PNG).unwrap();
+        std::fs::write(docs.join("other/my pic.png"), [PNG, b"different"].concat()).unwrap();
+        let text = "![a](my%20pic.png) ![b](<my pic.png>) ![c](other/my%20pic.png) \
+                    <img src='missing.png'>";
+
+        let out = tmp.path().join("site/page.html");
+        let (html, report) =
+            bundle_page(None, text, "Page", Some(&docs), &out, ImageMode::Assets);
+        assert_eq!(report.images, 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].url, "missing.png");
+        assert_eq!(html.matches("src=\"assets/my%20pic.png\"").count(), 2);
+        assert!(html.contains("src=\"assets/my%20pic-2.png\""));
+        assert!(html.contains("src='missing.png'"));
+        let assets = tmp.path().join("site").join(ASSETS_DIR);
+        assert_eq!(std::fs::read(assets.join("my pic.png")).unwrap(), PNG);
+        assert!(assets.join("my pic-2.png").exists());
+    }
+}
@@ -0,0 +1,1165 @@
+//! The command line.
+//!
+//! `run` looks at the arguments before the app starts. `paddown export`
+//! renders Markdown files, or every Markdown file under a folder, and
+//! writes the results to disk without opening a window; `paddown export -`
+//! renders the text piped in, to stdout unless `-o` says otherwise. `--help` and
+//! `--version` print and exit. Anything else is parsed into an
+//! `OpenRequest` for the window, whether it comes from this process or is
+//! forwarded by a second instance. Text piped in with `-` is read here,
+//! before the single-instance handoff, which can only carry argv (see
+//! `forward_stdin`). `--edit-stdin` goes one step further and waits for
+//! the tab to close, then prints what it holds (see `edit_stdin`); given a
+//! file, it edits that file in place instead, as `$EDITOR` is expected to.
+//!
+//! Exit codes: 0 on success, 1 when some file failed (the rest are still
+//! written), 2 for a usage error. Release builds on Windows have no
+//! console attached, so messages only show up when the output is
+//! redirected.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+use crate::error::{self, PaddownError};
+use crate::{bundle, markdown, pdf, render, tasks, workspace};
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: paddown [options] [file[:line[:column]] | -]...
+       paddown export <file or folder | ->... [--to html|pdf|txt] [-o <path>]
+
+Options:
+  --line <n>        Put the cursor on line n of files that don't give a
+                    position of their own.
+  --folder <dir>    Pin a folder to the sidebar. Can be repeated.
+  --new             Open a new untitled tab as well.
+  --readonly        Open the files read-only.
+  -, --stdin        Open the text piped in as a new, unsaved tab.
+  --edit-stdin[=<file>] [<path>]
+                    Open the text piped in for editing and wait. When its
+                    tab is closed, the text is written to stdout, or to
+                    <file>. Given a <path>, that file is edited and written
+                    back instead of stdin, so EDITOR=\"paddown --edit-stdin\"
+                    works for git. Exits with 1 if the window goes away
+                    first.
+  -h, --help        Show this help.
+  -V, --version     Show the version.
+  --                Treat every later argument as a file.
+
+Run `paddown export --help` for the export options.
+";
+
+const EXPORT_USAGE: &str = "\
+Usage: paddown export <file or folder>... [--to html|pdf|txt] [-o <path>] [PDF options]
+       paddown export - [--to html|pdf|txt] [-o <file>] [PDF options]
+
+Renders Markdown files to another format without opening a window. Folders
+are searched for Markdown files the way the sidebar shows them. With -, the
+text piped in is rendered instead and written to stdout, or to -o; its
+images are looked up from the current folder.
+
+Options:
+  -t, --to <format>   html (the default), pdf or txt. Without --to the
+                      format is taken from the extension of -o, if any.
+  -o, --output <path> Where to write. For a single file this is the output
+                      file, unless it is an existing folder; otherwise it
+                      is a folder, and the layout of input folders is kept
+                      inside it. Without -o, each result is written next to
+                      its source with the new extension.
+  --images <mode>     Make HTML pages self-contained: inline puts local
+                      images into the page, assets copies them into an
+                      assets folder beside it.
+  -h, --help          Show this help.
+
+PDF options:
+  --page-size <size>  a4 (the default), letter, legal or a5.
+  --landscape         Turn the pages sideways.
+  --margin <mm>       The margin on every side, 20 by default.
+  --header            Print the title and date at the top of every page.
+  --no-page-numbers   Leave out the \"Page N of M\" footer.
+  --no-wrap           Cut long code lines off instead of wrapping them.
+
+Exit status: 0 on success, 1 if any file failed, 2 for a usage error.
+";
+
+/// Handles the arguments (without the program name) that don't open a
+/// window, and returns the exit code once done. `None` means the app
+/// should start and open `startup_request()`.
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.first().map(String::as_str) == Some("export") {
+        return Some(export(&args[1..]));
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+    match parse_open(args, &cwd) {
+        Ok(Launch::Open(request)) if request.edit_stdin => Some(edit_stdin(args, &request)),
+        Ok(Launch::Open(request)) if request.stdin => Some(forward_stdin(args)),
+        Ok(Launch::Open(_)) => None,
+        Ok(Launch::Help) => {
+            print!("{}", USAGE);
+            Some(EXIT_OK)
+        }
+        Ok(Launch::Version) => {
+            println!("paddown {}", env!("CARGO_PKG_VERSION"));
+            Some(EXIT_OK)
+        }
+        Err(message) => {
+            eprintln!("paddown: {}\n\n{}", message, USAGE);
+            Some(EXIT_USAGE)
+        }
+    }
+}
+
+// ─── Opening Files ───────────────────────────────────────────
+
+/// A file named on the command line, with where to put the cursor.
+/// Line and column are 1-based.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct FileTarget {
+    pub path: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+/// Text opened with `--edit-stdin`, to hand back with `finish_edit`.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct EditBuffer {
+    pub id: String,
+    pub text: String,
+}
+
+/// What a command line asks the window to open. Paths are resolved
+/// against the directory the command ran in and canonicalized; the ones
+/// that don't exist are left out and reported in `errors`.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct OpenRequest {
+    pub files: Vec<FileTarget>,
+    /// Folders to pin to the sidebar, including folders given as files.
+    pub folders: Vec<String>,
+    /// Open a fresh untitled tab.
+    pub new_tab: bool,
+    pub readonly: bool,
+    /// Text piped in on stdin, each to open as an untitled, unsaved tab.
+    pub untitled: Vec<String>,
+    pub edits: Vec<EditBuffer>,
+    pub errors: Vec<PaddownError>,
+    /// `-` or `--stdin` was given, so stdin still has to be read.
+    #[serde(skip)]
+    stdin: bool,
+    /// Files holding stdin that an earlier process read, from the internal
+    /// `--stdin-file` option.
+    #[serde(skip)]
+    spooled: Vec<PathBuf>,
+    /// `--edit-stdin` was given, with where to write the result if not to
+    /// stdout.
+    #[serde(skip)]
+    edit_stdin: bool,
+    #[serde(skip)]
+    edit_output: Option<PathBuf>,
+    /// Spool files of `--edit-stdin` processes, from the internal
+    /// `--edit-file` option.
+    #[serde(skip)]
+    edit_files: Vec<PathBuf>,
+}
+
+enum Launch {
+    Open(OpenRequest),
+    Help,
+    Version,
+}
+
+fn position(text: &str) -> Option<u32> {
+    text.parse().ok().filter(|n| *n > 0)
+}
+
+/// Splits `path:line[:column]`. A file whose name really ends that way
+/// wins over the position.
+fn split_position<'a>(arg: &'a str, cwd: &Path) -> (&'a str, Option<u32>, Option<u32>) {
+    if cwd.join(arg).exists() {
+        return (arg, None, None);
+    }
+    let Some((rest, last)) = arg.rsplit_once(':') else { return (arg, None, None) };
+    let Some(last) = position(last) else { return (arg, None, None) };
+    if cwd.join(rest).exists() {
+        return (rest, Some(last), None);
+    }
+    if let Some((path, line)) = rest.rsplit_once(':') {
+        if let Some(line) = position(line).filter(|_| !path.is_empty()) {
+            return (path, Some(line), Some(last));
+        }
+    }
+    if rest.is_empty() {
+        return (arg, None, None);
+    }
+    (rest, Some(last), None)
+}
+
+/// `path` resolved against `cwd`, in the form the rest of the app uses.
+/// `canonicalize` gives `\\?\C:\...` paths on Windows, while dialogs and
+/// the sidebar use plain ones.
+fn resolve(path: &str, cwd: &Path) -> error::Result<PathBuf> {
+    let joined = cwd.join(path);
+    let real = std::fs::canonicalize(&joined).map_err(|e| PaddownError::io(&joined, e))?;
+    Ok(crate::plain_path(real))
+}
+
+fn parse_open(args: &[String], cwd: &Path) -> Result<Launch, String> {
+    let mut request = OpenRequest::default();
+    let mut default_line = None;
+    let mut args = args.iter();
+    let mut options = true;
+
+    while let Some(arg) = args.next() {
+        if !options || !arg.starts_with('-') {
+            let (path, line, column) = split_position(arg, cwd);
+            match resolve(path, cwd) {
+                Ok(real) if real.is_dir() => {
+                    request.folders.push(real.to_string_lossy().into_owned());
+                }
+                Ok(real) => request.files.push(FileTarget {
+                    path: real.to_string_lossy().into_owned(),
+                    line,
+                    column,
+                }),
+                Err(err) => request.errors.push(err),
+            }
+            continue;
+        }
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone().or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match name {
+            "--" => options = false,
+            "-h" | "--help" => return Ok(Launch::Help),
+            "-V" | "--version" => return Ok(Launch::Version),
+            "--line" => {
+                let text = value()?;
+                let parsed = position(&text).ok_or_else(|| format!("bad line number '{}'", text))?;
+                default_line = Some(parsed);
+            }
+            "--folder" => match resolve(&value()?, cwd) {
+                Ok(real) if real.is_dir() => {
+                    request.folders.push(real.to_string_lossy().into_owned());
+                }
+                Ok(real) => request.errors.push(PaddownError::invalid_path(&real, "Not a folder")),
+                Err(err) => request.errors.push(err),
+            },
+            "--new" => request.new_tab = true,
+            "-" | "--stdin" => request.stdin = true,
+            "--stdin-file" => request.spooled.push(PathBuf::from(value()?)),
+            "--edit-stdin" => {
+                request.edit_stdin = true;
+                request.edit_output = inline.as_deref().map(|path| cwd.join(path));
+            }
+            "--edit-file" => request.edit_files.push(PathBuf::from(value()?)),
+            "--readonly" => request.readonly = true,
+            // Process serial numbers that macOS Finder used to append.
+            _ if arg.starts_with("-psn_") => {}
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    if request.stdin && request.edit_stdin {
+        return Err("- and --edit-stdin both read stdin".to_string());
+    }
+    let paths = request.files.len() + request.folders.len() + request.errors.len();
+    if request.edit_stdin && (paths > 1 || !request.folders.is_empty()) {
+        return Err("--edit-stdin edits stdin or a single file".to_string());
+    }
+    for file in &mut request.files {
+        file.line = file.line.or(default_line);
+    }
+    Ok(Launch::Open(request))
+}
+
+/// Reads the spool files of `request` into `untitled` and `edits`. Spool
+/// files of `-` are removed once read, so the text opens only once; those
+/// of `--edit-stdin` stay for `finish_edit` to write back to.
+fn load_spooled(request: &mut OpenRequest) {
+    for path in std::mem::take(&mut request.spooled) {
+        match crate::read_text_file(&path) {
+            Ok(file) => request.untitled.push(file.contents),
+            Err(err) => request.errors.push(err),
+        }
+        if is_spool(&path) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+    for path in &request.edit_files {
+        let Some(id) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        match crate::read_text_file(path) {
+            Ok(file) => request.edits.push(EditBuffer { id, text: file.contents }),
+            Err(err) => request.errors.push(err),
+        }
+    }
+}
+
+/// The request this instance was started with. Call it once, since
+/// reading it consumes the stdin spool file.
+pub fn startup_request() -> OpenRequest {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    match parse_open(&args, &cwd) {
+        Ok(Launch::Open(mut request)) => {
+            load_spooled(&mut request);
+            request
+        }
+        _ => OpenRequest::default(),
+    }
+}
+
+/// The startup request until the window takes it. It is handed out only
+/// once, so reloading the webview doesn't open its files, stdin tabs and
+/// edit sessions a second time.
+pub struct PendingRequest(Mutex<Option<OpenRequest>>);
+
+impl PendingRequest {
+    pub fn new(request: OpenRequest) -> Self {
+        PendingRequest(Mutex::new(Some(request)))
+    }
+
+    /// The request, or an empty one once it has been taken.
+    pub fn take(&self) -> OpenRequest {
+        self.0.lock().ok().and_then(|mut request| request.take()).unwrap_or_default()
+    }
+}
+
+/// The request of a second instance, from its argv (program name
+/// included) and working directory. That process has already handled
+/// `--help`, `--version` and bad arguments itself before handing off.
+pub fn forwarded_request(argv: &[String], cwd: &Path) -> OpenRequest {
+    match parse_open(argv.get(1..).unwrap_or_default(), cwd) {
+        Ok(Launch::Open(mut request)) => {
+            load_spooled(&mut request);
+            request
+        }
+        _ => OpenRequest::default(),
+    }
+}
+
+// ─── Standard Input ──────────────────────────────────────────
+
+const SPOOL_PREFIX: &str = "paddown-stdin-";
+
+/// How stdin is named in messages.
+const STDIN_NAME: &str = "<stdin>";
+
+fn read_stdin() -> error::Result<String> {
+    let name = Path::new(STDIN_NAME);
+    let mut bytes = Vec::new();
+    std::io::stdin().lock()
+        .take(crate::MAX_FILE_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| PaddownError::io(name, e))?;
+    if bytes.len() as u64 > crate::MAX_FILE_SIZE {
+        return Err(PaddownError::TooLarge {
+            size: bytes.len() as u64,
+            max: crate::MAX_FILE_SIZE,
+        });
+    }
+    String::from_utf8(bytes).map_err(|e| {
+        PaddownError::io(name, std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    })
+}
+
+/// Writes `text` to a new file in the temp folder that only this user can
+/// read.
+fn spool(text: &str) -> error::Result<PathBuf> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+    let name = format!("{}{}-{}.md", SPOOL_PREFIX, std::process::id(), nanos);
+    let path = std::env::temp_dir().join(name);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path).map_err(|e| PaddownError::io(&path, e))?;
+    file.write_all(text.as_bytes()).map_err(|e| PaddownError::io(&path, e))?;
+    Ok(path)
+}
+
+fn is_spool(path: &Path) -> bool {
+    path.parent() == Some(std::env::temp_dir().as_path())
+        && path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(SPOOL_PREFIX))
+}
+
+/// Runs Paddown again with `with` in place of the arguments `replaced`
+/// picks out (up to `--`), and nothing attached to stdin.
+fn respawn(
+    args: &[String],
+    replaced: impl Fn(&str) -> bool,
+    with: &[String],
+) -> std::io::Result<Child> {
+    let mut forwarded = Vec::new();
+    let mut options = true;
+    let mut done = false;
+    for arg in args {
+        if options && replaced(arg) {
+            if !done {
+                forwarded.extend_from_slice(with);
+                done = true;
+            }
+            continue;
+        }
+        options &= arg != "--";
+        forwarded.push(arg.clone());
+    }
+    Command::new(std::env::current_exe()?).args(&forwarded).stdin(Stdio::null()).spawn()
+}
+
+/// Reads stdin into a spool file, then runs Paddown again with
+/// `--stdin-file <spool>` in place of `-` and waits for it. The
+/// single-instance handoff sends only argv to a window that is already
+/// open, so the text has to travel as a path; the new process either
+/// forwards it or becomes the window itself. The text itself can't go in
+/// argv: Windows caps a command line at 32K characters and Linux a single
+/// argument at 128K, far below `MAX_FILE_SIZE`, and argv is visible to
+/// other users' `ps`, where the spool file is readable only by this one.
+fn forward_stdin(args: &[String]) -> i32 {
+    let spooled = match read_stdin().and_then(|text| spool(&text)) {
+        Ok(path) => path,
+        Err(err) => {
+            eprintln!("paddown: stdin: {}", err);
+            return EXIT_FAILED;
+        }
+    };
+    let with = ["--stdin-file".to_string(), spooled.to_string_lossy().into_owned()];
+    let status = respawn(args, |arg| arg == "-" || arg == "--stdin", &with)
+        .and_then(|mut child| child.wait());
+    match status {
+        Ok(status) => status.code().unwrap_or(EXIT_FAILED),
+        Err(err) => {
+            eprintln!("paddown: {}", err);
+            let _ = std::fs::remove_file(&spooled);
+            EXIT_FAILED
+        }
+    }
+}
+
+// ─── Editing Standard Input ──────────────────────────────────
+
+const EDIT_CANCELLED: &str = "cancelled";
+
+/// Created next to an edit's spool file once the edit is over. Holds
+/// `EDIT_CANCELLED` if the text never came back.
+fn done_marker(spooled: &Path) -> PathBuf {
+    let mut name = spooled.as_os_str().to_owned();
+    name.push(".done");
+    PathBuf::from(name)
+}
+
+/// The `--edit-stdin` sessions open in this window, by id.
+#[derive(Default)]
+pub struct EditSessions(Mutex<HashMap<String, PathBuf>>);
+
+/// Remembers the edits of `request`, so `finish_edit` only ever writes to
+/// spool files a command line asked for.
+pub fn register_edits(app: &tauri::AppHandle, request: &OpenRequest) {
+    let state = app.state::<EditSessions>();
+    let Ok(mut sessions) = state.0.lock() else { return };
+    for (edit, path) in request.edits.iter().zip(&request.edit_files) {
+        sessions.insert(edit.id.clone(), path.clone());
+    }
+}
+
+/// Ends every edit still open, for when the window goes away first.
+pub fn abandon_edits(app: &tauri::AppHandle) {
+    let state = app.state::<EditSessions>();
+    let Ok(mut sessions) = state.0.lock() else { return };
+    for (_, path) in sessions.drain() {
+        let _ = std::fs::write(done_marker(&path), EDIT_CANCELLED);
+    }
+}
+
+/// Hands the text of a closed `--edit-stdin` tab back to the process
+/// waiting for it.
+#[tauri::command]
+pub fn finish_edit(
+    sessions: tauri::State<'_, EditSessions>,
+    id: String,
+    contents: String,
+) -> error::Result<()> {
+    let path = sessions.0.lock()
+        .map_err(|_| PaddownError::internal("edit sessions lock poisoned"))?
+        .remove(&id)
+        .ok_or_else(|| PaddownError::NotFound { path: id.clone() })?;
+    std::fs::write(&path, contents).map_err(|e| PaddownError::io(&path, e))?;
+    let marker = done_marker(&path);
+    std::fs::write(&marker, "").map_err(|e| PaddownError::io(&marker, e))
+}
+
+/// Reads stdin, or the file `request` names, into a spool file, runs
+/// Paddown again with `--edit-file <spool>` in place of `--edit-stdin`
+/// (and of the file, whose path would open it as a tab of its own), and
+/// waits until the window marks the edit done. The new process may exit
+/// long before that, having handed the file to a window that was already
+/// open, so this watches for the marker rather than for the process. The
+/// text comes back with the line endings it went in with, to the output
+/// file, the edited file or stdout.
+fn edit_stdin(args: &[String], request: &OpenRequest) -> i32 {
+    if let Some(err) = request.errors.first() {
+        eprintln!("paddown: {}", err);
+        return EXIT_FAILED;
+    }
+    let file = request.files.first().map(|target| PathBuf::from(&target.path));
+    let source = file.as_deref().unwrap_or(Path::new(STDIN_NAME));
+    let read = match &file {
+        Some(path) => crate::read_text_file(path).map(|file| file.contents),
+        None => read_stdin(),
+    };
+    let (text, spooled) = match read.and_then(|text| Ok((spool(&text)?, text))) {
+        Ok((path, text)) => (text, path),
+        Err(err) => {
+            eprintln!("paddown: {}: {}", source.display(), err);
+            return EXIT_FAILED;
+        }
+    };
+    let output = request.edit_output.as_deref().or(file.as_deref());
+    let marker = done_marker(&spooled);
+    let cleanup = || {
+        let _ = std::fs::remove_file(&spooled);
+        let _ = std::fs::remove_file(&marker);
+    };
+
+    let with = ["--edit-file".to_string(), spooled.to_string_lossy().into_owned()];
+    let replaced = |arg: &str| arg.split('=').next() == Some("--edit-stdin");
+    // The file is the only other argument an editor is given.
+    let args: &[String] = if file.is_some() { &[] } else { args };
+    let mut child = match respawn(args, replaced, &with) {
+        Ok(child) => Some(child),
+        Err(err) => {
+            eprintln!("paddown: {}", err);
+            cleanup();
+            return EXIT_FAILED;
+        }
+    };
+    while !marker.exists() {
+        // A process that fails never got the edit to a window.
+        if let Some(Ok(Some(status))) = child.as_mut().map(|c| c.try_wait()) {
+            if !status.success() && !marker.exists() {
+                cleanup();
+                return status.code().unwrap_or(EXIT_FAILED);
+            }
+            child = None;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    let result = std::fs::read_to_string(&marker)
+        .map_err(|e| PaddownError::io(&marker, e))
+        .and_then(|state| {
+            if state == EDIT_CANCELLED {
+                return Err(PaddownError::Cancelled);
+            }
+            crate::read_text_file(&spooled)
+        })
+        .map(|file| {
+            if text.contains("\r\n") {
+                file.contents.replace("\r\n", "\n").replace('\n', "\r\n")
+            } else {
+                file.contents
+            }
+        })
+        .and_then(|edited| match output {
+            Some(path) => std::fs::write(path, edited).map_err(|e| PaddownError::io(path, e)),
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(edited.as_bytes())
+                    .and_then(|()| stdout.flush())
+                    .map_err(|e| PaddownError::io(Path::new("<stdout>"), e))
+            }
+        });
+    cleanup();
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            eprintln!("paddown: edit: {}", err);
+            EXIT_FAILED
+        }
+    }
+}
+
+// ─── Export ──────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    Html,
+    Pdf,
+    Txt,
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "html" | "htm" => Some(Format::Html),
+            "pdf" => Some(Format::Pdf),
+            "txt" | "text" => Some(Format::Txt),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Html => "html",
+            Format::Pdf => "pdf",
+            Format::Txt => "txt",
+        }
+    }
+}
+
+struct ExportArgs {
+    inputs: Vec<PathBuf>,
+    format: Format,
+    output: Option<PathBuf>,
+    /// Makes HTML pages self-contained.
+    images: Option<bundle::ImageMode>,
+    pdf: pdf::PdfOptions,
+}
+
+enum Parsed {
+    Export(ExportArgs),
+    Help,
+}
+
+fn parse_export(args: &[String]) -> Result<Parsed, String> {
+    let mut inputs = Vec::new();
+    let mut format = None;
+    let mut output: Option<PathBuf> = None;
+    let mut images = None;
+    let mut pdf = pdf::PdfOptions::default();
+    let mut args = args.iter();
+    let mut options = true;
+
+    while let Some(arg) = args.next() {
+        if !options || arg == "-" || !arg.starts_with('-') {
+            inputs.push(PathBuf::from(arg));
+            continue;
+        }
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone().or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match name {
+            "--" => options = false,
+            "-h" | "--help" => return Ok(Parsed::Help),
+            "-t" | "--to" => {
+                let name = value()?;
+                let parsed = Format::parse(&name)
+                    .ok_or_else(|| format!("unknown format '{}'", name))?;
+                format = Some(parsed);
+            }
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--images" => {
+                let name = value()?;
+                let mode = bundle::ImageMode::parse(&name)
+                    .ok_or_else(|| format!("--images takes inline or assets, not '{}'", name))?;
+                images = Some(mode);
+            }
+            "--page-size" => {
+                let name = value()?;
+                pdf.page_size = pdf::PageSize::parse(&name)
+                    .ok_or_else(|| format!("unknown page size '{}'", name))?;
+            }
+            "--landscape" => pdf.landscape = true,
+            "--margin" => {
+                let margin = value()?;
+                pdf.margin_mm = margin.parse().ok()
+                    .filter(|mm: &f32| (0.0..=50.0).contains(mm))
+                    .ok_or_else(|| {
+                        format!("--margin needs 0 to 50 millimetres, not '{}'", margin)
+                    })?;
+            }
+            "--header" => pdf.header = true,
+            "--no-page-numbers" => pdf.page_numbers = false,
+            "--no-wrap" => pdf.wrap_code = false,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err("nothing to export".to_string());
+    }
+    if inputs.len() > 1 && inputs.iter().any(|input| input == Path::new("-")) {
+        return Err("- can't be exported together with files".to_string());
+    }
+    let to_stdout = output.as_deref().is_none_or(|out| out == Path::new("-"));
+    if inputs[0] == Path::new("-") && to_stdout && images == Some(bundle::ImageMode::Assets) {
+        return Err("--images assets needs -o when writing to stdout".to_string());
+    }
+    let format = format
+        .or_else(|| {
+            let extension = output.as_deref()?.extension()?.to_str()?;
+            Format::parse(extension)
+        })
+        .unwrap_or(Format::Html);
+    Ok(Parsed::Export(ExportArgs { inputs, format, output, images, pdf }))
+}
+
+/// Pairs every Markdown file to export with the path to write it to.
+fn plan(args: &ExportArgs) -> Vec<(PathBuf, PathBuf)> {
+    let token = tasks::CancelToken::default();
+    let single_file = args.inputs.len() == 1 && !args.inputs[0].is_dir();
+    let mut jobs = Vec::new();
+
+    for input in &args.inputs {
+        if input.is_dir() {
+            for file in workspace::markdown_files(input, &token) {
+                let relative = file.strip_prefix(input).unwrap_or(&file).to_path_buf();
+                let target = match &args.output {
+                    Some(dir) => dir.join(&relative),
+                    None => file.clone(),
+                };
+                jobs.push((file, target.with_extension(args.format.extension())));
+            }
+            continue;
+        }
+        let target = match &args.output {
+            Some(out) if single_file && !out.is_dir() => out.clone(),
+            Some(dir) => {
+                let name = input.file_name().map(PathBuf::from).unwrap_or_default();
+                dir.join(name).with_extension(args.format.extension())
+            }
+            None => input.with_extension(args.format.extension()),
+        };
+        jobs.push((input.clone(), target));
+    }
+    jobs
+}
+
+fn title_for(path: &Path, doc: &markdown::Document) -> String {
+    doc.title().map(str::to_string).unwrap_or_else(|| {
+        if path == Path::new(STDIN_NAME) {
+            return "Untitled".to_string();
+        }
+        path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+    })
+}
+
+fn convert(
+    input: &Path,
+    contents: &str,
+    target: &Path,
+    args: &ExportArgs,
+) -> error::Result<Vec<u8>> {
+    let title = || title_for(input, &markdown::parse(contents));
+    // Images load relative to the document, as in the preview.
+    let dir = input.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match args.format {
+        Format::Html => match args.images {
+            Some(mode) => {
+                let (html, report) =
+                    bundle::bundle_page(None, contents, &title(), Some(dir), target, mode);
+                for skipped in report.skipped {
+                    eprintln!(
+                        "paddown export: {}: kept image {}: {}",
+                        input.display(),
+                        skipped.url,
+                        skipped.error
+                    );
+                }
+                Ok(html.into_bytes())
+            }
+            None => Ok(render::render_page(contents, &title()).into_bytes()),
+        },
+        Format::Txt => Ok(render::render_text(contents).into_bytes()),
+        Format::Pdf => {
+            let (bytes, report) = pdf::render_pdf(contents, &title(), Some(dir), &args.pdf);
+            if !report.unsupported.is_empty() {
+                let missing: String = report.unsupported.iter().collect();
+                eprintln!(
+                    "paddown export: {}: missing from the PDF fonts, printed as '?': {}",
+                    input.display(),
+                    missing
+                );
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+fn export_file(input: &Path, target: &Path, args: &ExportArgs) -> error::Result<()> {
+    let file = crate::read_text_file(input)?;
+    let bytes = convert(input, &file.contents, target, args)?;
+    if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| PaddownError::io(parent, e))?;
+    }
+    std::fs::write(target, bytes).map_err(|e| PaddownError::io(target, e))
+}
+
+fn export(args: &[String]) -> i32 {
+    let args = match parse_export(args) {
+        Ok(Parsed::Export(args)) => args,
+        Ok(Parsed::Help) => {
+            print!("{}", EXPORT_USAGE);
+            return EXIT_OK;
+        }
+        Err(message) => {
+            eprintln!("paddown export: {}\n\n{}", message, EXPORT_USAGE);
+            return EXIT_USAGE;
+        }
+    };
+    if args.inputs[0] == Path::new("-") {
+        return export_stdin(&args);
+    }
+
+    let mut code = EXIT_OK;
+    for input in &args.inputs {
+        if !input.exists() {
+            eprintln!("paddown export: {}: no such file or folder", input.display());
+            code = EXIT_FAILED;
+        }
+    }
+    let jobs = plan(&args);
+    if jobs.is_empty() && code == EXIT_OK {
+        eprintln!("paddown export: no Markdown files found");
+        return EXIT_FAILED;
+    }
+    for (input, target) in jobs {
+        if target == input {
+            eprintln!("paddown export: {}: would overwrite its source", input.display());
+            code = EXIT_FAILED;
+            continue;
+        }
+        match export_file(&input, &target, &args) {
+            Ok(()) => println!("{} -> {}", input.display(), target.display()),
+            Err(err) => {
+                eprintln!("paddown export: {}: {}", input.display(), err);
+                code = EXIT_FAILED;
+            }
+        }
+    }
+    code
+}
+
+/// Renders the text piped in to `-o`, or to stdout when that is missing or
+/// `-`.
+fn export_stdin(args: &ExportArgs) -> i32 {
+    let input = Path::new(STDIN_NAME);
+    let target = args.output.as_deref().filter(|out| *out != Path::new("-"));
+    let result = read_stdin().and_then(|text| match target {
+        Some(target) => {
+            let bytes = convert(input, &text, target, args)?;
+            std::fs::write(target, bytes).map_err(|e| PaddownError::io(target, e))
+        }
+        None => {
+            let bytes = convert(input, &text, Path::new("-"), args)?;
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&bytes)
+                .and_then(|()| stdout.flush())
+                .map_err(|e| PaddownError::io(Path::new("<stdout>"), e))
+        }
+    });
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(err) => {
+            eprintln!("paddown export: {}: {}", input.display(), err);
+            EXIT_FAILED
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn export_args(args: &[&str]) -> ExportArgs {
+        match parse_export(&strings(args)) {
+            Ok(Parsed::Export(parsed)) => parsed,
+            Ok(Parsed::Help) => panic!("help for {:?}", args),
+            Err(err) => panic!("{} for {:?}", err, args),
+        }
+    }
+
+    fn export_error(args: &[&str]) -> String {
+        match parse_export(&strings(args)) {
+            Err(err) => err,
+            Ok(_) => panic!("no error for {:?}", args),
+        }
+    }
+
+    // ─── Opening Files ───────────────────────────────────────
+
+    fn open_request(args: &[&str], cwd: &Path) -> OpenRequest {
+        match parse_open(&strings(args), cwd) {
+            Ok(Launch::Open(request)) => request,
+            Ok(_) => panic!("not an open request for {:?}", args),
+            Err(err) => panic!("{} for {:?}", err, args),
+        }
+    }
+
+    fn real(path: &Path) -> String {
+        std::fs::canonicalize(path).unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn positions_split_off() {
+        let cwd = Path::new("/nonexistent-paddown-dir");
+        assert_eq!(split_position("a.md", cwd), ("a.md", None, None));
+        assert_eq!(split_position("a.md:12", cwd), ("a.md", Some(12), None));
+        assert_eq!(split_position("a.md:12:5", cwd), ("a.md", Some(12), Some(5)));
+        assert_eq!(split_position("a:b.md:3", cwd), ("a:b.md", Some(3), None));
+        // Zero, junk and missing paths aren't positions.
+        assert_eq!(split_position("a.md:0", cwd), ("a.md:0", None, None));
+        assert_eq!(split_position("a.md:x", cwd), ("a.md:x", None, None));
+        assert_eq!(split_position("a.md:x:4", cwd), ("a.md:x", Some(4), None));
+        assert_eq!(split_position(":4", cwd), (":4", None, None));
+        assert_eq!(split_position(":3:4", cwd), (":3", Some(4), None));
+    }
+
+    #[test]
+    fn drive_letters_are_not_positions() {
+        let cwd = Path::new("/nonexistent-paddown-dir");
+        assert_eq!(split_position(r"C:\notes\a.md", cwd), (r"C:\notes\a.md", None, None));
+        assert_eq!(
+            split_position(r"C:\notes\a.md:7", cwd),
+            (r"C:\notes\a.md", Some(7), None)
+        );
+        assert_eq!(
+            split_position(r"C:\notes\a.md:7:2", cwd),
+            (r"C:\notes\a.md", Some(7), Some(2))
+        );
+    }
+
+    #[test]
+    fn files_named_like_positions_win() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("log:2"), "").unwrap();
+        assert_eq!(split_position("log:2", dir.path()), ("log:2", None, None));
+        assert_eq!(split_position("log:2:9", dir.path()), ("log:2", Some(9), None));
+        assert_eq!(split_position("log:3", dir.path()), ("log", Some(3), None));
+    }
+
+    #[test]
+    fn open_files_and_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "").unwrap();
+        std::fs::write(dir.path().join("-b.md"), "").unwrap();
+        std::fs::create_dir(dir.path().join("notes")).unwrap();
+
+        let request = open_request(
+            &["a.md:4:2", "notes", "--line=9", "--new", "--readonly", "--", "-b.md"],
+            dir.path(),
+        );
+        let files: Vec<_> =
+            request.files.iter().map(|f| (f.path.clone(), f.line, f.column)).collect();
+        assert_eq!(
+            files,
+            [
+                (real(&dir.path().join("a.md")), Some(4), Some(2)),
+                (real(&dir.path().join("-b.md")), Some(9), None),
+            ]
+        );
+        assert_eq!(request.folders, [real(&dir.path().join("notes"))]);
+        assert!(request.new_tab && request.readonly);
+        assert!(request.errors.is_empty());
+    }
+
+    #[test]
+    fn open_reports_missing_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.md"), "").unwrap();
+        let a = dir.path().join("a.md");
+
+        let request = open_request(
+            &["missing.md", "--folder", a.to_str().unwrap(), "--folder=gone"],
+            dir.path(),
+        );
+        assert!(request.files.is_empty() && request.folders.is_empty());
+        assert_eq!(request.errors.len(), 3);
+    }
+
+    #[test]
+    fn pending_request_is_taken_once() {
+        let request = OpenRequest { untitled: vec!["piped".to_string()], ..Default::default() };
+        let pending = PendingRequest::new(request);
+        assert_eq!(pending.take().untitled, ["piped"]);
+        assert!(pending.take().untitled.is_empty());
+    }
+
+    #[test]
+    fn open_flags() {
+        let cwd = Path::new("/");
+        assert!(matches!(parse_open(&strings(&["-h"]), cwd), Ok(Launch::Help)));
+        assert!(matches!(parse_open(&strings(&["--version"]), cwd), Ok(Launch::Version)));
+        assert!(open_request(&["-"], cwd).stdin);
+        assert!(open_request(&["-psn_0_12345"], cwd).errors.is_empty());
+
+        let edit = open_request(&["--edit-stdin=out.md"], Path::new("/work"));
+        assert!(edit.edit_stdin);
+        assert_eq!(edit.edit_output, Some(PathBuf::from("/work/out.md")));
+
+        let error = |args: &[&str]| parse_open(&strings(args), cwd).err().unwrap_or_default();
+        assert_eq!(error(&["--line", "0"]), "bad line number '0'");
+        assert_eq!(error(&["--line"]), "--line needs a value");
+        assert_eq!(error(&["--bogus"]), "unknown option '--bogus'");
+        assert_eq!(error(&["-", "--edit-stdin"]), "- and --edit-stdin both read stdin");
+    }
+
+    #[test]
+    fn edit_stdin_takes_one_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("COMMIT_EDITMSG"), "").unwrap();
+        std::fs::write(dir.path().join("b.md"), "").unwrap();
+
+        let edit = open_request(&["--edit-stdin", "COMMIT_EDITMSG"], dir.path());
+        assert!(edit.edit_stdin && edit.errors.is_empty());
+        let files: Vec<_> = edit.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(files, [real(&dir.path().join("COMMIT_EDITMSG"))]);
+        assert_eq!(open_request(&["--edit-stdin", "gone"], dir.path()).errors.len(), 1);
+
+        let error = |args: &[&str]| parse_open(&strings(args), dir.path()).err();
+        let message = Some("--edit-stdin edits stdin or a single file".to_string());
+        assert_eq!(error(&["--edit-stdin", "COMMIT_EDITMSG", "b.md"]), message);
+        assert_eq!(error(&["--edit-stdin", "gone", "b.md"]), message);
+        assert_eq!(error(&["--edit-stdin", "."]), message);
+    }
+
+    // ─── Export ──────────────────────────────────────────────
+
+    #[test]
+    fn export_options() {
+        let args = export_args(&[
+            "a.md", "--to=pdf", "-o", "out", "--page-size", "letter", "--landscape",
+            "--margin=12.5", "--header", "--no-page-numbers", "--no-wrap", "--", "--b.md",
+        ]);
+        assert_eq!(args.inputs, [PathBuf::from("a.md"), PathBuf::from("--b.md")]);
+        assert_eq!(args.format, Format::Pdf);
+        assert_eq!(args.output, Some(PathBuf::from("out")));
+        assert_eq!(args.pdf.page_size, pdf::PageSize::Letter);
+        assert!(args.pdf.landscape && args.pdf.header);
+        assert_eq!(args.pdf.margin_mm, 12.5);
+        assert!(!args.pdf.page_numbers && !args.pdf.wrap_code);
+        assert_eq!(args.images, None);
+
+        let args = export_args(&["b.md", "--images", "assets"]);
+        assert_eq!(args.images, Some(bundle::ImageMode::Assets));
+    }
+
+    #[test]
+    fn export_stdin_options() {
+        let args = export_args(&["-", "--images", "inline"]);
+        assert_eq!(args.inputs, [PathBuf::from("-")]);
+        assert_eq!((args.output, args.format), (None, Format::Html));
+        let args = export_args(&["-", "-o", "out.pdf", "--images", "assets"]);
+        assert_eq!((args.output, args.format), (Some(PathBuf::from("out.pdf")), Format::Pdf));
+        assert_eq!(export_args(&["-", "-o", "-", "-t", "txt"]).format, Format::Txt);
+
+        assert_eq!(export_error(&["-", "a.md"]), "- can't be exported together with files");
+        assert_eq!(
+            export_error(&["-", "--images", "assets", "-o", "-"]),
+            "--images assets needs -o when writing to stdout"
+        );
+        let untitled = title_for(Path::new(STDIN_NAME), &markdown::parse("no heading"));
+        assert_eq!(untitled, "Untitled");
+    }
+
+    #[test]
+    fn export_format_defaults() {
+        assert_eq!(export_args(&["a.md"]).format, Format::Html);
+        assert_eq!(export_args(&["a.md", "-o", "a.TXT"]).format, Format::Txt);
+        assert_eq!(export_args(&["a.md", "-o", "a.pdf", "-t", "htm"]).format, Format::Html);
+        assert!(matches!(parse_export(&strings(&["--help"])), Ok(Parsed::Help)));
+    }
+
+    #[test]
+    fn export_usage_errors() {
+        assert_eq!(export_error(&[]), "nothing to export");
+        assert_eq!(export_error(&["a.md", "-t"]), "-t needs a value");
+        assert_eq!(export_error(&["a.md", "--to=doc"]), "unknown format 'doc'");
+        assert_eq!(export_error(&["a.md", "--bogus"]), "unknown option '--bogus'");
+        assert!(export_error(&["a.md", "--margin", "60"]).contains("0 to 50"));
+        assert!(export_error(&["a.md", "--images", "copy"]).contains("'copy'"));
+        assert!(export_error(&["a.md", "--page-size", "a9"]).contains("'a9'"));
+    }
+
+    #[test]
+    fn plan_single_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("notes.md");
+        std::fs::write(&input, "# Notes").unwrap();
+        let input_arg = input.to_str().unwrap();
+
+        let next_to = plan(&export_args(&[input_arg, "-t", "pdf"]));
+        assert_eq!(next_to, [(input.clone(), dir.path().join("notes.pdf"))]);
+
+        let named = dir.path().join("custom.html");
+        let to_file = plan(&export_args(&[input_arg, "-o", named.to_str().unwrap()]));
+        assert_eq!(to_file, [(input.clone(), named)]);
+
+        // An existing folder as the output gets the file inside it.
+        let out = dir.path().join("out");
+        std::fs::create_dir(&out).unwrap();
+        let into_dir = plan(&export_args(&[input_arg, "-t", "txt", "-o", out.to_str().unwrap()]));
+        assert_eq!(into_dir, [(input.clone(), out.join("notes.txt"))]);
+
+        // With several inputs, the output is always a folder.
+        let other = dir.path().join("other.md");
+        let jobs = plan(&export_args(&[input_arg, other.to_str().unwrap(), "-o", "site"]));
+        assert_eq!(
+            jobs,
+            [
+                (input, PathBuf::from("site/notes.html")),
+                (other, PathBuf::from("site/other.html")),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_folders_keep_their_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir_all(docs.join("guide")).unwrap();
+        std::fs::write(docs.join("index.md"), "# Home").unwrap();
+        std::fs::write(docs.join("guide/start.markdown"), "# Start").unwrap();
+        std::fs::write(docs.join("guide/image.png"), "").unwrap();
+        let docs_arg = docs.to_str().unwrap();
+
+        let mut in_place = plan(&export_args(&[docs_arg]));
+        in_place.sort();
+        assert_eq!(
+            in_place,
+            [
+                (docs.join("guide/start.markdown"), docs.join("guide/start.html")),
+                (docs.join("index.md"), docs.join("index.html")),
+            ]
+        );
+
+        let out = dir.path().join("site");
+        let args = export_args(&[docs_arg, "-t", "pdf", "-o", out.to_str().unwrap()]);
+        let mut mirrored = plan(&args);
+        mirrored.sort();
+        assert_eq!(
+            mirrored,
+            [
+                (docs.join("guide/start.markdown"), out.join("guide/start.pdf")),
+                (docs.join("index.md"), out.join("index.pdf")),
+            ]
+        );
+    }
+}
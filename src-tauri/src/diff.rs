@@ -0,0 +1,274 @@
+//! Line diff with word-level highlights, for previewing what a reload,
+//! merge or history restore is about to change.
+//!
+//! Either side can be literal text (an editor buffer), a file on disk or a
+//! stored history version. Line endings are normalised to `\n` first, since
+//! the editor always works in LF while files on disk may not. Word ranges
+//! are UTF-16 offsets into each line's `text`, so they can be used directly
+//! as JS string indices.
+
+use similar::{ChangeTag, DiffTag, TextDiff};
+use std::path::Path;
+
+use crate::error;
+use crate::scope;
+use crate::tasks;
+
+/// Lines of unchanged context kept around each hunk by default.
+const DEFAULT_CONTEXT: usize = 3;
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSource {
+    Text { text: String },
+    File { path: String },
+    Version { path: String, version_id: String },
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum LineKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct DiffLine {
+    kind: LineKind,
+    /// 1-based line numbers; `None` on the side the line doesn't exist on.
+    old_line: Option<usize>,
+    new_line: Option<usize>,
+    /// The line without its terminating newline.
+    text: String,
+    /// `[start, end)` UTF-16 ranges within `text` that changed. Empty for
+    /// equal lines and for lines with no counterpart on the other side.
+    words: Vec<[usize; 2]>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct Hunk {
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+    lines: Vec<DiffLine>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct DiffResult {
+    identical: bool,
+    hunks: Vec<Hunk>,
+}
+
+fn load(app: &tauri::AppHandle, source: DiffSource) -> error::Result<String> {
+    let text = match source {
+        DiffSource::Text { text } => text,
+        DiffSource::File { path } => {
+            let path = Path::new(&path);
+            scope::check(app, path)?;
+            crate::read_text_file(path)?.contents
+        }
+        DiffSource::Version { path, version_id } => {
+            let path = Path::new(&path);
+            scope::check(app, path)?;
+            crate::history::read_version(app, path, &version_id)?
+        }
+    };
+    Ok(text.replace("\r\n", "\n"))
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+fn line_text(line: &str) -> String {
+    line.strip_suffix('\n').unwrap_or(line).to_string()
+}
+
+/// Word-level ranges for a deleted line and the inserted line that replaced
+/// it, as `(old ranges, new ranges)`.
+fn word_ranges(old: &str, new: &str) -> (Vec<[usize; 2]>, Vec<[usize; 2]>) {
+    let diff = TextDiff::from_words(old, new);
+    let mut old_ranges: Vec<[usize; 2]> = Vec::new();
+    let mut new_ranges: Vec<[usize; 2]> = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for change in diff.iter_all_changes() {
+        let len = utf16_len(change.value());
+        let (ranges, pos) = match change.tag() {
+            ChangeTag::Equal => {
+                old_pos += len;
+                new_pos += len;
+                continue;
+            }
+            ChangeTag::Delete => (&mut old_ranges, &mut old_pos),
+            ChangeTag::Insert => (&mut new_ranges, &mut new_pos),
+        };
+        // Adjacent changed words collapse into one range.
+        match ranges.last_mut() {
+            Some(last) if last[1] == *pos => last[1] += len,
+            _ => ranges.push([*pos, *pos + len]),
+        }
+        *pos += len;
+    }
+    (old_ranges, new_ranges)
+}
+
+fn push_lines(
+    lines: &mut Vec<DiffLine>,
+    kind: LineKind,
+    texts: &[&str],
+    old_start: Option<usize>,
+    new_start: Option<usize>,
+) {
+    for (i, text) in texts.iter().enumerate() {
+        lines.push(DiffLine {
+            kind,
+            old_line: old_start.map(|n| n + i + 1),
+            new_line: new_start.map(|n| n + i + 1),
+            text: line_text(text),
+            words: Vec::new(),
+        });
+    }
+}
+
+fn diff(old: &str, new: &str, context: usize) -> DiffResult {
+    let diff = TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut hunks = Vec::new();
+    for group in diff.grouped_ops(context) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else { continue };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+
+        let mut lines = Vec::new();
+        for op in &group {
+            let (old_r, new_r) = (op.old_range(), op.new_range());
+            let removed = &old_lines[old_r.clone()];
+            let added = &new_lines[new_r.clone()];
+            match op.tag() {
+                DiffTag::Equal => {
+                    push_lines(&mut lines, LineKind::Equal, removed, Some(old_r.start), Some(new_r.start));
+                }
+                DiffTag::Delete => {
+                    push_lines(&mut lines, LineKind::Delete, removed, Some(old_r.start), None);
+                }
+                DiffTag::Insert => {
+                    push_lines(&mut lines, LineKind::Insert, added, None, Some(new_r.start));
+                }
+                DiffTag::Replace => {
+                    let start = lines.len();
+                    push_lines(&mut lines, LineKind::Delete, removed, Some(old_r.start), None);
+                    let inserted = lines.len();
+                    push_lines(&mut lines, LineKind::Insert, added, None, Some(new_r.start));
+                    // Pair replaced lines up in order for word highlights;
+                    // any surplus on either side changed as a whole.
+                    for k in 0..removed.len().min(added.len()) {
+                        let (old_words, new_words) =
+                            word_ranges(&lines[start + k].text, &lines[inserted + k].text);
+                        lines[start + k].words = old_words;
+                        lines[inserted + k].words = new_words;
+                    }
+                }
+            }
+        }
+
+        hunks.push(Hunk {
+            old_start: old_range.start + 1,
+            old_lines: old_range.len(),
+            new_start: new_range.start + 1,
+            new_lines: new_range.len(),
+            lines,
+        });
+    }
+
+    DiffResult {
+        identical: hunks.is_empty(),
+        hunks,
+    }
+}
+
+#[tauri::command]
+pub async fn diff_sources(
+    app: tauri::AppHandle,
+    old: DiffSource,
+    new: DiffSource,
+    context: Option<usize>,
+) -> error::Result<DiffResult> {
+    tasks::blocking(move || {
+        let old = load(&app, old)?;
+        let new = load(&app, new)?;
+        Ok(diff(&old, &new, context.unwrap_or(DEFAULT_CONTEXT)))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(hunk: &Hunk) -> Vec<LineKind> {
+        hunk.lines.iter().map(|l| l.kind).collect()
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        let result = diff("a\nb\n", "a\nb\n", DEFAULT_CONTEXT);
+        assert!(result.identical);
+        assert!(result.hunks.is_empty());
+    }
+
+    #[test]
+    fn changed_line_becomes_a_hunk_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        let result = diff(old, new, 1);
+        assert!(!result.identical);
+        assert_eq!(result.hunks.len(), 1);
+
+        let hunk = &result.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines), (4, 3));
+        assert_eq!((hunk.new_start, hunk.new_lines), (4, 3));
+        use LineKind::*;
+        assert_eq!(kinds(hunk), [Equal, Delete, Insert, Equal]);
+        assert_eq!((hunk.lines[1].old_line, hunk.lines[1].new_line), (Some(5), None));
+        assert_eq!((hunk.lines[2].old_line, hunk.lines[2].new_line), (None, Some(5)));
+        assert_eq!(hunk.lines[2].text, "five");
+    }
+
+    #[test]
+    fn distant_changes_make_separate_hunks() {
+        let old = "a\n1\n2\n3\n4\n5\n6\n7\n8\nb\n";
+        let new = "A\n1\n2\n3\n4\n5\n6\n7\n8\nB\n";
+        assert_eq!(diff(old, new, 2).hunks.len(), 2);
+    }
+
+    #[test]
+    fn insertions_and_deletions_have_one_side() {
+        let result = diff("a\nb\n", "a\nc\nb\n", 0);
+        let hunk = &result.hunks[0];
+        assert_eq!(kinds(hunk), [LineKind::Insert]);
+        assert_eq!(hunk.lines[0].new_line, Some(2));
+        assert!(hunk.lines[0].words.is_empty());
+
+        let result = diff("a\nb\n", "b\n", 0);
+        assert_eq!(kinds(&result.hunks[0]), [LineKind::Delete]);
+    }
+
+    #[test]
+    fn replaced_lines_get_word_ranges() {
+        let result = diff("the quick fox\n", "the slow fox\n", 0);
+        let lines = &result.hunks[0].lines;
+        assert_eq!(lines[0].words, [[4, 9]]);
+        assert_eq!(lines[1].words, [[4, 8]]);
+    }
+
+    #[test]
+    fn word_ranges_count_utf16_units() {
+        let (old, new) = word_ranges("\u{1F600} caf\u{E9} old", "\u{1F600} caf\u{E9} new");
+        assert_eq!(old, [[8, 11]]);
+        assert_eq!(new, [[8, 11]]);
+    }
+}
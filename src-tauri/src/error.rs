@@ -0,0 +1,145 @@
+//! Error type shared by every backend command.
+//!
+//! Errors cross the IPC boundary as `{ "code": "...", "message": "...", ... }`
+//! so the frontend can branch on a stable `code` instead of parsing text.
+//! Variant-specific fields (`path`, `size`, `max`, `kind`) ride along next
+//! to the code.
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub enum PaddownError {
+    NotFound { path: String },
+    PermissionDenied { path: String },
+    /// Something is already at the path a command wanted to create.
+    AlreadyExists { path: String },
+    TooLarge { size: u64, max: u64 },
+    /// The file changed on disk since the caller last read it.
+    Conflict { path: String },
+    /// The path is malformed, is the wrong kind of entry, or falls outside
+    /// where the command is allowed to look.
+    InvalidPath { path: String, reason: String },
+    Unsupported { message: String },
+    /// A search pattern or glob that doesn't compile.
+    InvalidQuery { message: String },
+    Network { message: String },
+    Io { kind: String, path: String, message: String },
+    Cancelled,
+    Internal { message: String },
+}
+
+pub type Result<T> = std::result::Result<T, PaddownError>;
+
+impl PaddownError {
+    /// Maps an `io::Error` to the most specific variant, keeping the path
+    /// that was being operated on.
+    pub fn io(path: &Path, err: std::io::Error) -> Self {
+        let path = path.to_string_lossy().into_owned();
+        match err.kind() {
+            std::io::ErrorKind::NotFound => PaddownError::NotFound { path },
+            std::io::ErrorKind::PermissionDenied => PaddownError::PermissionDenied { path },
+            std::io::ErrorKind::AlreadyExists => PaddownError::AlreadyExists { path },
+            kind => PaddownError::Io {
+                kind: format!("{:?}", kind),
+                path,
+                message: err.to_string(),
+            },
+        }
+    }
+
+    pub fn invalid_path(path: &Path, reason: impl Into<String>) -> Self {
+        PaddownError::InvalidPath {
+            path: path.to_string_lossy().into_owned(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn internal(message: impl fmt::Display) -> Self {
+        PaddownError::Internal { message: message.to_string() }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            PaddownError::NotFound { .. } => "NotFound",
+            PaddownError::PermissionDenied { .. } => "PermissionDenied",
+            PaddownError::AlreadyExists { .. } => "AlreadyExists",
+            PaddownError::TooLarge { .. } => "TooLarge",
+            PaddownError::Conflict { .. } => "Conflict",
+            PaddownError::InvalidPath { .. } => "InvalidPath",
+            PaddownError::Unsupported { .. } => "Unsupported",
+            PaddownError::InvalidQuery { .. } => "InvalidQuery",
+            PaddownError::Network { .. } => "Network",
+            PaddownError::Io { .. } => "Io",
+            PaddownError::Cancelled => "Cancelled",
+            PaddownError::Internal { .. } => "Internal",
+        }
+    }
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+impl fmt::Display for PaddownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaddownError::NotFound { path } => write!(f, "Not found: {}", path),
+            PaddownError::PermissionDenied { path } => write!(f, "Permission denied: {}", path),
+            PaddownError::AlreadyExists { path } => write!(f, "Already exists: {}", path),
+            PaddownError::TooLarge { size, max } => write!(
+                f,
+                "File too large ({:.1} MB, max {:.0} MB)",
+                megabytes(*size),
+                megabytes(*max)
+            ),
+            PaddownError::Conflict { path } => {
+                write!(f, "{} was changed on disk since it was opened", path)
+            }
+            PaddownError::InvalidPath { path, reason } => write!(f, "{}: {}", reason, path),
+            PaddownError::Unsupported { message } => f.write_str(message),
+            PaddownError::InvalidQuery { message } => write!(f, "Invalid search: {}", message),
+            PaddownError::Network { message } => write!(f, "Network error: {}", message),
+            PaddownError::Io { path, message, .. } => write!(f, "{}: {}", path, message),
+            PaddownError::Cancelled => f.write_str("Cancelled"),
+            PaddownError::Internal { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for PaddownError {}
+
+impl Serialize for PaddownError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            PaddownError::NotFound { path }
+            | PaddownError::PermissionDenied { path }
+            | PaddownError::AlreadyExists { path }
+            | PaddownError::Conflict { path } => {
+                map.serialize_entry("path", path)?;
+            }
+            PaddownError::TooLarge { size, max } => {
+                map.serialize_entry("size", size)?;
+                map.serialize_entry("max", max)?;
+            }
+            PaddownError::InvalidPath { path, reason } => {
+                map.serialize_entry("path", path)?;
+                map.serialize_entry("reason", reason)?;
+            }
+            PaddownError::Io { kind, path, .. } => {
+                map.serialize_entry("kind", kind)?;
+                map.serialize_entry("path", path)?;
+            }
+            PaddownError::Unsupported { .. }
+            | PaddownError::InvalidQuery { .. }
+            | PaddownError::Network { .. }
+            | PaddownError::Cancelled
+            | PaddownError::Internal { .. } => {}
+        }
+        map.end()
+    }
+}
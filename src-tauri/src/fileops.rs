@@ -0,0 +1,491 @@
+//! File management for the sidebar: create, rename, move, duplicate and
+//! delete files and folders inside pinned folders.
+//!
+//! The tree isn't refreshed from here. Every pinned folder is watched, so
+//! the change comes back as an ordinary `fs-change` event. Commands return
+//! the path they produced so the frontend can follow the entry, e.g. to
+//! retarget an open tab after a rename.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, PaddownError};
+use crate::scope;
+use crate::tasks;
+use crate::trashcan;
+
+/// Longest file name most filesystems accept, in bytes.
+const MAX_NAME_LEN: usize = 255;
+
+/// Names Windows refuses regardless of extension. Rejected everywhere so a
+/// folder synced to a Windows machine keeps working there.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Upper bound on "name copy N" attempts when duplicating.
+const MAX_COPY_SUFFIX: u32 = 1000;
+
+fn validate_name(name: &str) -> error::Result<()> {
+    let invalid = |reason: &str| Err(PaddownError::invalid_path(Path::new(name), reason));
+
+    if name.trim().is_empty() {
+        return invalid("Name is empty");
+    }
+    if name == "." || name == ".." {
+        return invalid("Name is reserved");
+    }
+    if name.len() > MAX_NAME_LEN {
+        return invalid("Name is too long");
+    }
+    if name.chars().any(|c| c.is_control() || "/\\<>:\"|?*".contains(c)) {
+        return invalid("Name contains a character that isn't allowed in file names");
+    }
+    if name.ends_with('.') || name.ends_with(' ') || name.starts_with(' ') {
+        return invalid("Name can't start with a space or end with a space or dot");
+    }
+    let stem = name.split('.').next().unwrap_or(name);
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        return invalid("Name is reserved");
+    }
+    Ok(())
+}
+
+fn to_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn file_name(path: &Path) -> error::Result<&std::ffi::OsStr> {
+    path.file_name()
+        .ok_or_else(|| PaddownError::invalid_path(path, "Path has no file name"))
+}
+
+fn parent(path: &Path) -> error::Result<&Path> {
+    path.parent()
+        .ok_or_else(|| PaddownError::invalid_path(path, "Path has no parent directory"))
+}
+
+/// Fails with `AlreadyExists` if anything, even a dangling symlink, is at
+/// `path`.
+fn ensure_free(path: &Path) -> error::Result<()> {
+    if std::fs::symlink_metadata(path).is_ok() {
+        Err(PaddownError::AlreadyExists { path: to_string(path) })
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether `a` and `b` name the same entry, e.g. `Notes.md` and `notes.md`
+/// on a case-insensitive filesystem.
+fn same_entry(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Creates a symlink at `to` with the same target as the one at `from`.
+fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(from)?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&target, to)
+    }
+    #[cfg(windows)]
+    {
+        if from.is_dir() {
+            std::os::windows::fs::symlink_dir(&target, to)
+        } else {
+            std::os::windows::fs::symlink_file(&target, to)
+        }
+    }
+}
+
+/// Copies a file or a folder tree. Symlinks are copied as symlinks, never
+/// followed: a link may point outside the pinned folders, and the copy
+/// must not turn what it points at into a file inside them. That also
+/// keeps a link cycle from recursing forever. Anything that is neither a
+/// file, a folder nor a symlink (a FIFO, a socket) is refused.
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        copy_link(from, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else if metadata.is_file() {
+        std::fs::copy(from, to).map(|_| ())
+    } else {
+        Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Not a file or folder"))
+    }
+}
+
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(path)?.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Renames `from` to `to`, falling back to copy-and-delete when they are on
+/// different filesystems.
+fn rename_or_copy(from: &Path, to: &Path) -> error::Result<()> {
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to).map_err(|e| {
+                let _ = remove_entry(to);
+                PaddownError::io(to, e)
+            })?;
+            remove_entry(from).map_err(|e| PaddownError::io(from, e))
+        }
+        Err(e) => Err(PaddownError::io(from, e)),
+    }
+}
+
+/// `notes.md` → `notes copy.md`, `notes copy 2.md`, …; folders and files
+/// without an extension just get the suffix.
+fn copy_name(name: &str, is_dir: bool, n: u32) -> String {
+    let suffix = if n == 1 { " copy".to_string() } else { format!(" copy {}", n) };
+    match name.rfind('.') {
+        Some(dot) if !is_dir && dot > 0 => format!("{}{}{}", &name[..dot], suffix, &name[dot..]),
+        _ => format!("{}{}", name, suffix),
+    }
+}
+
+// The commands check names and scope, then leave the filesystem work to
+// the functions below.
+
+fn new_file(path: &Path) -> error::Result<()> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map(|_| ())
+        .map_err(|e| PaddownError::io(path, e))
+}
+
+fn new_folder(path: &Path) -> error::Result<()> {
+    std::fs::create_dir(path).map_err(|e| PaddownError::io(path, e))
+}
+
+fn rename_to(path: &Path, target: &Path) -> error::Result<()> {
+    if target == path {
+        return Ok(());
+    }
+    // A case-only rename finds the entry itself at the target on
+    // case-insensitive filesystems; that isn't a collision.
+    if !same_entry(path, target) {
+        ensure_free(target)?;
+    }
+    std::fs::rename(path, target).map_err(|e| PaddownError::io(path, e))
+}
+
+/// Moves `path` to `target`, unless that would put a folder inside itself.
+fn move_to(path: &Path, target: &Path) -> error::Result<()> {
+    if same_entry(path, target) {
+        return Ok(());
+    }
+    let dest_dir = parent(target)?;
+    if let (Ok(from), Ok(to)) = (std::fs::canonicalize(path), std::fs::canonicalize(dest_dir)) {
+        if to.starts_with(&from) {
+            return Err(PaddownError::invalid_path(dest_dir, "Can't move a folder into itself"));
+        }
+    }
+    ensure_free(target)?;
+    rename_or_copy(path, target)
+}
+
+/// Copies `path` to the first free "copy" name beside it.
+fn duplicate_at(path: &Path) -> error::Result<PathBuf> {
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| PaddownError::io(path, e))?;
+    let name = file_name(path)?.to_string_lossy().into_owned();
+    let dir = parent(path)?;
+
+    let target = (1..=MAX_COPY_SUFFIX)
+        .map(|n| dir.join(copy_name(&name, metadata.is_dir(), n)))
+        .find(|candidate| std::fs::symlink_metadata(candidate).is_err())
+        .ok_or_else(|| PaddownError::AlreadyExists {
+            path: to_string(&dir.join(copy_name(&name, metadata.is_dir(), MAX_COPY_SUFFIX))),
+        })?;
+
+    copy_recursive(path, &target).map_err(|e| {
+        let _ = remove_entry(&target);
+        PaddownError::io(&target, e)
+    })?;
+    Ok(target)
+}
+
+fn create_file_in(app: &tauri::AppHandle, dir: &Path, name: &str) -> error::Result<PathBuf> {
+    validate_name(name)?;
+    scope::check_pinned_dir(app, dir)?;
+    let path = dir.join(name);
+    scope::check_pinned_entry(app, &path)?;
+    new_file(&path)?;
+    Ok(path)
+}
+
+fn create_folder_in(app: &tauri::AppHandle, dir: &Path, name: &str) -> error::Result<PathBuf> {
+    validate_name(name)?;
+    scope::check_pinned_dir(app, dir)?;
+    let path = dir.join(name);
+    scope::check_pinned_entry(app, &path)?;
+    new_folder(&path)?;
+    Ok(path)
+}
+
+fn rename(app: &tauri::AppHandle, path: &Path, new_name: &str) -> error::Result<PathBuf> {
+    validate_name(new_name)?;
+    scope::check_pinned_entry(app, path)?;
+    let target = parent(path)?.join(new_name);
+    scope::check_pinned_entry(app, &target)?;
+    rename_to(path, &target)?;
+    Ok(target)
+}
+
+fn move_into(app: &tauri::AppHandle, path: &Path, dest_dir: &Path) -> error::Result<PathBuf> {
+    scope::check_pinned_entry(app, path)?;
+    scope::check_pinned_dir(app, dest_dir)?;
+    let target = dest_dir.join(file_name(path)?);
+    scope::check_pinned_entry(app, &target)?;
+    if same_entry(path, &target) {
+        return Ok(path.to_path_buf());
+    }
+    move_to(path, &target)?;
+    Ok(target)
+}
+
+fn duplicate(app: &tauri::AppHandle, path: &Path) -> error::Result<PathBuf> {
+    scope::check_pinned_entry(app, path)?;
+    duplicate_at(path)
+}
+
+/// Moves the entry to the system trash; `undo_delete` brings it back.
+fn delete(app: &tauri::AppHandle, path: &Path) -> error::Result<()> {
+    scope::check_pinned_entry(app, path)?;
+    trashcan::trash_entries(app, &[path])
+}
+
+#[tauri::command]
+pub async fn create_file(
+    app: tauri::AppHandle,
+    dir: String,
+    name: String,
+) -> error::Result<String> {
+    tasks::blocking(move || create_file_in(&app, Path::new(&dir), &name).map(|p| to_string(&p)))
+        .await?
+}
+
+#[tauri::command]
+pub async fn create_folder(
+    app: tauri::AppHandle,
+    dir: String,
+    name: String,
+) -> error::Result<String> {
+    tasks::blocking(move || create_folder_in(&app, Path::new(&dir), &name).map(|p| to_string(&p)))
+        .await?
+}
+
+#[tauri::command]
+pub async fn rename_entry(
+    app: tauri::AppHandle,
+    path: String,
+    new_name: String,
+) -> error::Result<String> {
+    tasks::blocking(move || rename(&app, Path::new(&path), &new_name).map(|p| to_string(&p)))
+        .await?
+}
+
+#[tauri::command]
+pub async fn move_entry(
+    app: tauri::AppHandle,
+    path: String,
+    dest_dir: String,
+) -> error::Result<String> {
+    tasks::blocking(move || {
+        move_into(&app, Path::new(&path), Path::new(&dest_dir)).map(|p| to_string(&p))
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn duplicate_entry(app: tauri::AppHandle, path: String) -> error::Result<String> {
+    tasks::blocking(move || duplicate(&app, Path::new(&path)).map(|p| to_string(&p))).await?
+}
+
+#[tauri::command]
+pub async fn delete_entry(app: tauri::AppHandle, path: String) -> error::Result<()> {
+    tasks::blocking(move || delete(&app, Path::new(&path))).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_invalid(name: &str) -> bool {
+        matches!(validate_name(name), Err(PaddownError::InvalidPath { .. }))
+    }
+
+    fn is_already_exists<T: std::fmt::Debug>(result: error::Result<T>) -> bool {
+        matches!(result, Err(PaddownError::AlreadyExists { .. }))
+    }
+
+    #[test]
+    fn names_are_validated() {
+        for name in ["notes.md", "Notes 2", ".hidden", "console.md", "a..b", "caf\u{e9}"] {
+            validate_name(name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+        }
+        let too_long = "a".repeat(MAX_NAME_LEN + 1);
+        for name in [
+            "", "   ", ".", "..", "a/b", "a\\b", "../up", "a:b", "a?", "tab\there", &too_long,
+            "CON", "nul.md", "Com1.txt", "lpt9", "aux.tar.gz", "trailing.", "trailing ", " leading",
+        ] {
+            assert!(is_invalid(name), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn copy_names_keep_the_extension() {
+        assert_eq!(copy_name("notes.md", false, 1), "notes copy.md");
+        assert_eq!(copy_name("notes.md", false, 3), "notes copy 3.md");
+        assert_eq!(copy_name("archive.tar.gz", false, 1), "archive.tar copy.gz");
+        assert_eq!(copy_name(".env", false, 1), ".env copy");
+        assert_eq!(copy_name("Makefile", false, 2), "Makefile copy 2");
+        assert_eq!(copy_name("v1.2", true, 1), "v1.2 copy");
+    }
+
+    #[test]
+    fn duplicates_take_the_first_free_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "text").unwrap();
+        std::fs::write(dir.path().join("notes copy.md"), "taken").unwrap();
+
+        let copy = duplicate_at(&path).unwrap();
+        assert_eq!(copy, dir.path().join("notes copy 2.md"));
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "text");
+    }
+
+    #[test]
+    fn duplicates_give_up_after_max_copy_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("n");
+        std::fs::write(&path, "").unwrap();
+        for n in 1..=MAX_COPY_SUFFIX {
+            std::fs::write(dir.path().join(copy_name("n", false, n)), "").unwrap();
+        }
+        assert!(is_already_exists(duplicate_at(&path)));
+    }
+
+    #[test]
+    fn folders_are_duplicated_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("docs");
+        std::fs::create_dir_all(folder.join("deep")).unwrap();
+        std::fs::write(folder.join("deep/a.md"), "a").unwrap();
+
+        let copy = duplicate_at(&folder).unwrap();
+        assert_eq!(copy, dir.path().join("docs copy"));
+        assert_eq!(std::fs::read_to_string(copy.join("deep/a.md")).unwrap(), "a");
+    }
+
+    #[test]
+    fn creating_over_an_entry_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        new_file(&path).unwrap();
+        assert!(is_already_exists(new_file(&path)));
+        assert!(is_already_exists(new_folder(&path)));
+    }
+
+    #[test]
+    fn renaming_onto_an_entry_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.md"), dir.path().join("b.md"));
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&b, "b").unwrap();
+        assert!(is_already_exists(rename_to(&a, &b)));
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "b");
+
+        let c = dir.path().join("c.md");
+        rename_to(&a, &c).unwrap();
+        assert_eq!(std::fs::read_to_string(&c).unwrap(), "a");
+        rename_to(&c, &c).unwrap();
+    }
+
+    #[test]
+    fn folders_cant_move_into_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("docs");
+        std::fs::create_dir_all(folder.join("inner")).unwrap();
+
+        let into_child = folder.join("inner/docs");
+        assert!(matches!(move_to(&folder, &into_child), Err(PaddownError::InvalidPath { .. })));
+        assert!(folder.join("inner").is_dir());
+
+        let elsewhere = dir.path().join("other");
+        std::fs::create_dir(&elsewhere).unwrap();
+        std::fs::write(elsewhere.join("docs"), "").unwrap();
+        assert!(is_already_exists(move_to(&folder, &elsewhere.join("docs"))));
+
+        std::fs::remove_file(elsewhere.join("docs")).unwrap();
+        move_to(&folder, &elsewhere.join("docs")).unwrap();
+        assert!(elsewhere.join("docs/inner").is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_copied_as_links() {
+        let outside = tempfile::tempdir().unwrap();
+        let secret = outside.path().join("id_rsa");
+        std::fs::write(&secret, "secret").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("key");
+        std::os::unix::fs::symlink(&secret, &link).unwrap();
+
+        let copy = duplicate_at(&link).unwrap();
+        assert!(std::fs::symlink_metadata(&copy).unwrap().is_symlink());
+        assert_eq!(std::fs::read_link(&copy).unwrap(), secret);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_inside_folders_are_kept() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret"), "secret").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("docs");
+        std::fs::create_dir(&folder).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), folder.join("file")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), folder.join("folder")).unwrap();
+        // A cycle back to the folder itself.
+        std::os::unix::fs::symlink("..", folder.join("loop")).unwrap();
+
+        let copy = dir.path().join("copy");
+        copy_recursive(&folder, &copy).unwrap();
+        for name in ["file", "folder", "loop"] {
+            let entry = copy.join(name);
+            assert!(std::fs::symlink_metadata(&entry).unwrap().is_symlink(), "{}", name);
+            let original = std::fs::read_link(folder.join(name)).unwrap();
+            assert_eq!(std::fs::read_link(&entry).unwrap(), original);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        assert!(duplicate_at(&socket).is_err());
+        assert!(!dir.path().join("socket copy").exists());
+    }
+}
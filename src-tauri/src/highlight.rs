@@ -0,0 +1,195 @@
+//! Syntax highlighting for rendered code blocks.
+//!
+//! A port of the preview's `highlighter.js`: the same rules in the same
+//! order, tagging whole matches with the `tok-*` classes that `tokens.css`
+//! colours, and never letting a later rule split an earlier one. The Rust
+//! `regex` crate has no lookahead, so rules that need one (a function name
+//! followed by `(`) consume it and tag only the `t` group instead.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::render::escape_html;
+
+struct Rule {
+    pattern: Regex,
+    class: &'static str,
+}
+
+const STRING: &str = r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#;
+const BLOCK_COMMENT: &str = r"/\*[\s\S]*?\*/";
+const NUMBER: &str = r"\b\d+(?:\.\d+)?(?:[eE][+\-]?\d+)?\b";
+
+const JS_KEYWORDS: &str = r"\b(?:function|return|const|let|var|if|else|for|while|do|switch|case|break|continue|class|new|this|import|export|from|of|in|typeof|instanceof|true|false|null|undefined|async|await|throw|try|catch|finally|default|void|delete|yield)\b";
+const PY_KEYWORDS: &str = r"\b(?:def|return|if|elif|else|for|while|class|import|from|as|in|not|and|or|is|True|False|None|pass|break|continue|with|yield|lambda|try|except|finally|raise|print|global|nonlocal|assert|del)\b";
+const BASH_KEYWORDS: &str = r"\b(?:if|then|else|elif|fi|for|do|done|while|until|case|esac|in|function|return|local|export|source|alias|unset|set|shift|exit|echo|printf|read|test|cd|ls|grep|sed|awk|cat|mkdir|rm|cp|mv|chmod|chown|sudo|apt|yum|pip|npm)\b";
+const SQL_KEYWORDS: &str = r"(?i)\b(?:SELECT|FROM|WHERE|AND|OR|NOT|IN|IS|NULL|AS|ON|JOIN|LEFT|RIGHT|INNER|OUTER|FULL|CROSS|INSERT|INTO|VALUES|UPDATE|SET|DELETE|CREATE|TABLE|ALTER|DROP|INDEX|VIEW|GRANT|REVOKE|UNION|ALL|DISTINCT|ORDER|BY|GROUP|HAVING|LIMIT|OFFSET|ASC|DESC|LIKE|BETWEEN|EXISTS|CASE|WHEN|THEN|ELSE|END|COUNT|SUM|AVG|MAX|MIN|PRIMARY|KEY|FOREIGN|REFERENCES|DEFAULT|CONSTRAINT|UNIQUE|CHECK|IF|BEGIN|COMMIT|ROLLBACK|TRUNCATE|WITH|RECURSIVE)\b";
+const PHP_KEYWORDS: &str = r"\b(?:function|return|if|else|elseif|for|foreach|while|do|switch|case|break|continue|class|new|this|public|private|protected|static|abstract|interface|extends|implements|namespace|use|require|include|require_once|include_once|echo|print|array|true|false|null|try|catch|finally|throw|const|var|match|fn|yield|enum)\b";
+
+const LANGUAGES: &[(&str, &[(&str, &str)])] = &[
+    ("javascript", &[
+        (r"//[^\n]*", "tok-comment"),
+        (BLOCK_COMMENT, "tok-comment"),
+        (STRING, "tok-string"),
+        (r"`(?:[^`\\]|\\.)*`", "tok-tmpl"),
+        (JS_KEYWORDS, "tok-keyword"),
+        (NUMBER, "tok-number"),
+        (r"[{}\[\]();,]", "tok-punct"),
+        (r"=>|[+\-*/%=\&|<>!\~^?:]+", "tok-operator"),
+        (r"\b(?P<t>[a-zA-Z_$][a-zA-Z0-9_$]*\s*)\(", "tok-fn"),
+    ]),
+    ("python", &[
+        (r"#[^\n]*", "tok-comment"),
+        (r#""""[\s\S]*?"""|'''[\s\S]*?'''"#, "tok-string"),
+        (STRING, "tok-string"),
+        (PY_KEYWORDS, "tok-keyword"),
+        (NUMBER, "tok-number"),
+        (r"[{}\[\]();,:]", "tok-punct"),
+        (r"[+\-*/%=\&|<>!\~^@]+", "tok-operator"),
+        (r"\b(?P<t>[a-zA-Z_][a-zA-Z0-9_]*\s*)\(", "tok-fn"),
+    ]),
+    ("html", &[
+        (r"<!--[\s\S]*?-->", "tok-comment"),
+        (r"</?[a-zA-Z][a-zA-Z0-9\-]*", "tok-keyword"),
+        (r"\s[a-zA-Z\-]+=", "tok-fn"),
+        (STRING, "tok-string"),
+        (r"\&[a-zA-Z]+;|\&#\d+;", "tok-number"),
+        (r"[<>/=]", "tok-punct"),
+    ]),
+    ("css", &[
+        (BLOCK_COMMENT, "tok-comment"),
+        (STRING, "tok-string"),
+        (r"@[a-zA-Z\-]+", "tok-keyword"),
+        (r"!important", "tok-keyword"),
+        (r"#[a-fA-F0-9]{3,8}\b", "tok-number"),
+        (r"\b\d+(?:\.\d+)?(?:px|em|rem|%|vh|vw|fr|s|ms|deg)?\b", "tok-number"),
+        (r"[{}();:,]", "tok-punct"),
+        (r"[.#][a-zA-Z_\-][a-zA-Z0-9_\-]*", "tok-fn"),
+        (r"\b(?P<t>[a-zA-Z\-]+\s*):", "tok-fn"),
+    ]),
+    ("bash", &[
+        (r"#[^\n]*", "tok-comment"),
+        (r#""(?:[^"\\]|\\.)*"|'[^']*'"#, "tok-string"),
+        (r"\$[a-zA-Z_][a-zA-Z0-9_]*|\$\{[^}]+\}", "tok-tmpl"),
+        (BASH_KEYWORDS, "tok-keyword"),
+        (r"\b\d+(?:\.\d+)?\b", "tok-number"),
+        (r"[|\&;<>(){}\[\]]", "tok-punct"),
+        (r"[=!<>+\-]+", "tok-operator"),
+    ]),
+    ("json", &[
+        (r#"(?P<t>"(?:[^"\\]|\\.)*"\s*):"#, "tok-fn"),
+        (r#""(?:[^"\\]|\\.)*""#, "tok-string"),
+        (r"\b(?:true|false|null)\b", "tok-keyword"),
+        (r"-?\d+(?:\.\d+)?(?:[eE][+\-]?\d+)?", "tok-number"),
+        (r"[{}\[\]:,]", "tok-punct"),
+    ]),
+    ("sql", &[
+        (r"--[^\n]*", "tok-comment"),
+        (BLOCK_COMMENT, "tok-comment"),
+        (r"'(?:[^'\\]|\\.)*'", "tok-string"),
+        (SQL_KEYWORDS, "tok-keyword"),
+        (r"\b\d+(?:\.\d+)?\b", "tok-number"),
+        (r"[();,.*]", "tok-punct"),
+        (r"[=<>!+\-]+", "tok-operator"),
+        (r"\b(?P<t>[a-zA-Z_][a-zA-Z0-9_]*\s*)\(", "tok-fn"),
+    ]),
+    ("php", &[
+        (r"//[^\n]*|#[^\n]*", "tok-comment"),
+        (BLOCK_COMMENT, "tok-comment"),
+        (STRING, "tok-string"),
+        (r"\$[a-zA-Z_][a-zA-Z0-9_]*", "tok-tmpl"),
+        (PHP_KEYWORDS, "tok-keyword"),
+        (NUMBER, "tok-number"),
+        (r"[{}\[\]();,]", "tok-punct"),
+        (r"=>|->|[+\-*/%=\&|<>!\~^.?:]+", "tok-operator"),
+        (r"\b(?P<t>[a-zA-Z_][a-zA-Z0-9_]*\s*)\(", "tok-fn"),
+    ]),
+];
+
+const ALIASES: &[(&str, &str)] = &[
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "javascript"),
+    ("tsx", "javascript"),
+    ("py", "python"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+    ("htm", "html"),
+    ("xml", "html"),
+    ("svg", "html"),
+    ("scss", "css"),
+    ("sass", "css"),
+    ("less", "css"),
+    ("mysql", "sql"),
+    ("pgsql", "sql"),
+    ("postgresql", "sql"),
+    ("sqlite", "sql"),
+    ("jsonc", "json"),
+];
+
+fn rules(lang: &str) -> Option<&'static [Rule]> {
+    static RULES: OnceLock<HashMap<&'static str, Vec<Rule>>> = OnceLock::new();
+    let rules = RULES.get_or_init(|| {
+        LANGUAGES.iter()
+            .map(|(name, rules)| {
+                let rules = rules.iter()
+                    .map(|(pattern, class)| Rule {
+                        pattern: Regex::new(pattern).expect("highlighter pattern"),
+                        class,
+                    })
+                    .collect();
+                (*name, rules)
+            })
+            .collect()
+    });
+    let lang = ALIASES.iter().find(|(alias, _)| *alias == lang).map_or(lang, |(_, name)| name);
+    rules.get(lang).map(Vec::as_slice)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    Free,
+    Taken,
+}
+
+/// The tokens of `code` in `lang`, in order, as byte ranges with their
+/// `tok-*` class. Unknown languages have none.
+pub fn tokens(code: &str, lang: &str) -> Vec<(Range<usize>, &'static str)> {
+    let Some(rules) = rules(lang) else { return Vec::new() };
+
+    let mut marks = vec![Mark::Free; code.len()];
+    let mut tokens = Vec::new();
+    for rule in rules {
+        for caps in rule.pattern.captures_iter(code) {
+            let Some(m) = caps.name("t").or_else(|| caps.get(0)) else { continue };
+            if m.is_empty() || marks[m.range()].iter().any(|mark| *mark != Mark::Free) {
+                continue;
+            }
+            marks[m.range()].fill(Mark::Taken);
+            tokens.push((m.range(), rule.class));
+        }
+    }
+    tokens.sort_by_key(|(range, _)| range.start);
+    tokens
+}
+
+/// `code` as escaped HTML, with tokens of `lang` wrapped in
+/// `<span class="tok-…">`. Unknown languages are only escaped.
+pub fn highlight(code: &str, lang: &str) -> String {
+    let mut html = String::with_capacity(code.len() * 2);
+    let mut plain = 0;
+    for (range, class) in tokens(code, lang) {
+        html.push_str(&escape_html(&code[plain..range.start]));
+        html.push_str(&format!(
+            "<span class=\"{}\">{}</span>",
+            class,
+            escape_html(&code[range.clone()])
+        ));
+        plain = range.end;
+    }
+    html.push_str(&escape_html(&code[plain..]));
+    html
+}
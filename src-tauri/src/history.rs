@@ -0,0 +1,456 @@
+//! Local version history for saved files.
+//!
+//! Every successful save records a snapshot under `<app_data_dir>/history`.
+//! Each file gets its own directory, named after a hash of its path, holding
+//! an `index.json` and one blob per distinct content hash, so saving the same
+//! text twice costs nothing. Retention runs on every record: first the
+//! per-file count and age limits, then a cap on the size of the whole store
+//! that drops the oldest versions across all files. The store's size is
+//! counted once and then kept up to date, so only a save that takes it over
+//! the cap reads every index again.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+use crate::error::{self, PaddownError};
+use crate::scope;
+use crate::tasks;
+
+/// Versions kept per file.
+const MAX_VERSIONS_PER_FILE: usize = 50;
+
+/// Versions older than this are dropped (30 days).
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Upper bound on the blobs in the whole store (200 MB).
+const MAX_TOTAL_SIZE: u64 = 200 * 1024 * 1024;
+
+const INDEX_FILE: &str = "index.json";
+
+/// Serialises index updates, since saves run concurrently on the blocking
+/// pool. Holds the size of the blobs in the store at a root, once counted.
+static STORE_LOCK: Mutex<Option<(PathBuf, u64)>> = Mutex::new(None);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Version {
+    id: String,
+    /// Milliseconds since the Unix epoch.
+    saved_at: u64,
+    size: u64,
+    hash: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct Index {
+    path: String,
+    /// Oldest first.
+    versions: Vec<Version>,
+}
+
+pub(crate) fn history_dir_path(app: &tauri::AppHandle) -> error::Result<PathBuf> {
+    let dir = app.path().app_data_dir().map_err(PaddownError::internal)?;
+    Ok(dir.join("history"))
+}
+
+/// The path history is keyed by. Saves write through symlinks, so the
+/// resolved target is what actually changed.
+fn history_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn file_dir(root: &Path, key: &Path) -> PathBuf {
+    let hash = crate::content_hash(key.to_string_lossy().as_bytes());
+    root.join(&hash[..32])
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn load_index(dir: &Path) -> error::Result<Index> {
+    let path = dir.join(INDEX_FILE);
+    match std::fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            PaddownError::internal(format!("Corrupt history index {}: {}", path.display(), e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Index::default()),
+        Err(e) => Err(PaddownError::io(&path, e)),
+    }
+}
+
+/// The size of the blobs `index` refers to. Versions of one file share
+/// blobs, so each counts once.
+fn blob_size(index: &Index) -> u64 {
+    let mut seen = std::collections::HashSet::new();
+    index.versions.iter().filter(|v| seen.insert(&v.hash)).map(|v| v.size).sum()
+}
+
+/// Writes `bytes` to `path` via a temp file, so readers never see a partial
+/// index or blob.
+fn write_atomic(path: &Path, bytes: &[u8]) -> error::Result<()> {
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| PaddownError::io(&tmp, e))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        PaddownError::io(path, e)
+    })
+}
+
+/// Saves the index and deletes blobs no version refers to any more. A file
+/// with no versions left loses its directory entirely.
+fn store_index(dir: &Path, index: &Index) -> error::Result<()> {
+    if index.versions.is_empty() {
+        return match std::fs::remove_dir_all(dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(PaddownError::io(dir, e)),
+            _ => Ok(()),
+        };
+    }
+    let json = serde_json::to_vec_pretty(index).map_err(PaddownError::internal)?;
+    write_atomic(&dir.join(INDEX_FILE), &json)?;
+
+    let entries = std::fs::read_dir(dir).map_err(|e| PaddownError::io(dir, e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name != INDEX_FILE && !index.versions.iter().any(|v| v.hash == name) {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Records a save of `path`. `previous` is what was on disk before the save,
+/// so the very first overwrite of a file can still be undone. Paths inside
+/// the app's own data directory (recovery files and the history itself) are
+/// never recorded.
+pub fn record(
+    app: &tauri::AppHandle,
+    path: &Path,
+    previous: Option<&[u8]>,
+    current: &[u8],
+) -> error::Result<()> {
+    let root = history_dir_path(app)?;
+    let key = history_key(path);
+    if let Some(data_dir) = root.parent() {
+        if key.starts_with(history_key(data_dir)) {
+            return Ok(());
+        }
+    }
+    record_in(&root, &key, previous, current, SystemTime::now())
+}
+
+/// `record` into the store at `root`, as of `now`. Both snapshots are
+/// stamped with `now`: ages count from when a version entered the history,
+/// so the contents a save replaces survive the age limit however long ago
+/// the file was last modified.
+fn record_in(
+    root: &Path,
+    key: &Path,
+    previous: Option<&[u8]>,
+    current: &[u8],
+    now: SystemTime,
+) -> error::Result<()> {
+    let mut store_size = STORE_LOCK.lock().map_err(PaddownError::internal)?;
+    let dir = file_dir(root, key);
+    std::fs::create_dir_all(&dir).map_err(|e| PaddownError::io(&dir, e))?;
+    let mut index = load_index(&dir)?;
+    let size_before = blob_size(&index);
+    index.path = key.to_string_lossy().into_owned();
+
+    let saved_at = millis(now);
+    for bytes in previous.into_iter().chain(std::iter::once(current)) {
+        let hash = crate::content_hash(bytes);
+        if index.versions.last().is_some_and(|v| v.hash == hash) {
+            continue;
+        }
+        let blob = dir.join(&hash);
+        if !blob.exists() {
+            write_atomic(&blob, bytes)?;
+        }
+        index.versions.push(Version {
+            id: format!("{}-{}", saved_at, &hash[..12]),
+            saved_at,
+            size: bytes.len() as u64,
+            hash,
+        });
+    }
+
+    // Per-file limits. The newest version always survives: it is the file
+    // as it is on disk right now.
+    let cutoff = saved_at.saturating_sub(MAX_AGE.as_millis() as u64);
+    let newest = index.versions.len().saturating_sub(1);
+    let mut position = 0;
+    index.versions.retain(|v| {
+        let keep = position == newest || v.saved_at >= cutoff;
+        position += 1;
+        keep
+    });
+    if index.versions.len() > MAX_VERSIONS_PER_FILE {
+        let excess = index.versions.len() - MAX_VERSIONS_PER_FILE;
+        index.versions.drain(..excess);
+    }
+    // Taken out first, so a failure below leaves the store to be counted
+    // afresh.
+    let known = match store_size.take() {
+        Some((counted, total)) if counted == root => {
+            Some((total + blob_size(&index)).saturating_sub(size_before))
+        }
+        _ => None,
+    };
+    store_index(&dir, &index)?;
+
+    let total = match known {
+        Some(total) if total <= MAX_TOTAL_SIZE => total,
+        _ => enforce_total_size(root, MAX_TOTAL_SIZE)?,
+    };
+    *store_size = Some((root.to_path_buf(), total));
+    Ok(())
+}
+
+/// Drops the oldest versions across every file until the store's blobs fit
+/// in `max`, and returns their size. Each file's newest version is left
+/// alone.
+fn enforce_total_size(root: &Path, max: u64) -> error::Result<u64> {
+    let mut files: Vec<(PathBuf, Index)> = Vec::new();
+    let entries = std::fs::read_dir(root).map_err(|e| PaddownError::io(root, e))?;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if dir.is_dir() {
+            if let Ok(index) = load_index(&dir) {
+                files.push((dir, index));
+            }
+        }
+    }
+
+    let mut total: u64 = files.iter().map(|(_, index)| blob_size(index)).sum();
+    let mut changed = vec![false; files.len()];
+
+    while total > max {
+        let oldest = files.iter()
+            .enumerate()
+            .filter(|(_, (_, index))| index.versions.len() > 1)
+            .min_by_key(|(_, (_, index))| index.versions[0].saved_at)
+            .map(|(i, _)| i);
+        let Some(i) = oldest else { break };
+        let index = &mut files[i].1;
+        let before = blob_size(index);
+        index.versions.remove(0);
+        total -= before - blob_size(index);
+        changed[i] = true;
+    }
+
+    for ((dir, index), changed) in files.iter().zip(changed) {
+        if changed {
+            store_index(dir, index)?;
+        }
+    }
+    Ok(total)
+}
+
+/// Locates the blob holding `version_id` of `path`.
+fn find_version(
+    app: &tauri::AppHandle,
+    path: &Path,
+    version_id: &str,
+) -> error::Result<PathBuf> {
+    let dir = file_dir(&history_dir_path(app)?, &history_key(path));
+    let index = load_index(&dir)?;
+    let version = index.versions.into_iter()
+        .find(|v| v.id == version_id)
+        .ok_or_else(|| PaddownError::NotFound {
+            path: format!("{} (version {})", path.display(), version_id),
+        })?;
+    Ok(dir.join(&version.hash))
+}
+
+pub(crate) fn read_version(app: &tauri::AppHandle, path: &Path, version_id: &str) -> error::Result<String> {
+    let blob = find_version(app, path, version_id)?;
+    let bytes = std::fs::read(&blob).map_err(|e| PaddownError::io(&blob, e))?;
+    String::from_utf8(bytes).map_err(|_| PaddownError::Unsupported {
+        message: "Stored version is not valid UTF-8".into(),
+    })
+}
+
+/// Lists the recorded versions of `path`, newest first.
+#[tauri::command]
+pub(crate) async fn list_file_versions(
+    app: tauri::AppHandle,
+    path: String,
+) -> error::Result<Vec<Version>> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        let root = history_dir_path(&app)?;
+        let mut index = load_index(&file_dir(&root, &history_key(path)))?;
+        index.versions.reverse();
+        Ok(index.versions)
+    })
+    .await?
+}
+
+#[tauri::command]
+pub(crate) async fn read_file_version(
+    app: tauri::AppHandle,
+    path: String,
+    version_id: String,
+) -> error::Result<String> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        read_version(&app, path, &version_id)
+    })
+    .await?
+}
+
+/// Writes a stored version back to `path` as a regular save, so the contents
+/// it replaces become a version of their own and the restore can be undone.
+#[tauri::command]
+pub(crate) async fn restore_file_version(
+    app: tauri::AppHandle,
+    path: String,
+    version_id: String,
+    expected: Option<crate::Fingerprint>,
+) -> error::Result<crate::Fingerprint> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        let contents = read_version(&app, path, &version_id)?;
+        crate::save_contents(&app, path, &contents, expected.as_ref())
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn versions(root: &Path, key: &Path) -> Vec<Version> {
+        load_index(&file_dir(root, key)).unwrap().versions
+    }
+
+    fn contents(root: &Path, key: &Path, version: &Version) -> String {
+        std::fs::read_to_string(file_dir(root, key).join(&version.hash)).unwrap()
+    }
+
+    #[test]
+    fn first_overwrite_of_an_old_file_keeps_its_contents() {
+        let store = tempfile::tempdir().unwrap();
+        let docs = tempfile::tempdir().unwrap();
+        let path = docs.path().join("note.md");
+        std::fs::write(&path, "written long ago").unwrap();
+        let long_ago = SystemTime::now() - 2 * MAX_AGE;
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(long_ago).unwrap();
+
+        let previous = std::fs::read(&path).unwrap();
+        std::fs::write(&path, "new").unwrap();
+        let now = SystemTime::now();
+        record_in(store.path(), &path, Some(&previous), b"new", now).unwrap();
+        // A later save must not prune it either.
+        record_in(store.path(), &path, None, b"newer", now + Duration::from_secs(60)).unwrap();
+
+        let kept = versions(store.path(), &path);
+        assert_eq!(kept.len(), 3);
+        assert_eq!(contents(store.path(), &path, &kept[0]), "written long ago");
+        assert_eq!(kept[0].saved_at, millis(now));
+    }
+
+    #[test]
+    fn versions_past_the_age_limit_are_dropped() {
+        let store = tempfile::tempdir().unwrap();
+        let key = Path::new("/notes/note.md");
+        let now = SystemTime::now();
+        record_in(store.path(), key, None, b"one", now - 40 * DAY).unwrap();
+        record_in(store.path(), key, None, b"two", now - 10 * DAY).unwrap();
+        record_in(store.path(), key, None, b"three", now).unwrap();
+
+        let kept = versions(store.path(), key);
+        let kept: Vec<String> = kept.iter().map(|v| contents(store.path(), key, v)).collect();
+        assert_eq!(kept, ["two", "three"]);
+    }
+
+    #[test]
+    fn the_newest_version_outlives_the_age_limit() {
+        let store = tempfile::tempdir().unwrap();
+        let key = Path::new("/notes/note.md");
+        let now = SystemTime::now();
+        record_in(store.path(), key, None, b"one", now - 40 * DAY).unwrap();
+        record_in(store.path(), key, None, b"one", now).unwrap();
+
+        assert_eq!(versions(store.path(), key).len(), 1);
+    }
+
+    #[test]
+    fn unchanged_saves_add_no_version() {
+        let store = tempfile::tempdir().unwrap();
+        let key = Path::new("/notes/note.md");
+        let now = SystemTime::now();
+        record_in(store.path(), key, Some(b"same"), b"same", now).unwrap();
+        record_in(store.path(), key, Some(b"same"), b"same", now).unwrap();
+
+        assert_eq!(versions(store.path(), key).len(), 1);
+    }
+
+    #[test]
+    fn the_store_cap_drops_the_oldest_versions_first() {
+        let store = tempfile::tempdir().unwrap();
+        let (a, b) = (Path::new("/notes/a.md"), Path::new("/notes/b.md"));
+        let now = SystemTime::now();
+        let text = |name: &str| name.repeat(100);
+        record_in(store.path(), a, None, text("a1").as_bytes(), now - 3 * DAY).unwrap();
+        record_in(store.path(), b, None, text("b1").as_bytes(), now - 2 * DAY).unwrap();
+        record_in(store.path(), a, None, text("a2").as_bytes(), now - DAY).unwrap();
+        record_in(store.path(), b, None, text("b2").as_bytes(), now).unwrap();
+        record_in(store.path(), a, None, text("a3").as_bytes(), now).unwrap();
+
+        let kept = |key: &Path| -> Vec<String> {
+            let versions = versions(store.path(), key);
+            versions.iter().map(|v| contents(store.path(), key, v)[..2].to_string()).collect()
+        };
+        assert_eq!(enforce_total_size(store.path(), 1000).unwrap(), 1000);
+        assert_eq!(enforce_total_size(store.path(), 700).unwrap(), 600);
+        assert_eq!(kept(a), ["a2", "a3"]);
+        assert_eq!(kept(b), ["b2"]);
+        // The blob of a dropped version goes with it.
+        let b1 = crate::content_hash(text("b1").as_bytes());
+        assert!(!file_dir(store.path(), b).join(b1).exists());
+
+        // Each file's newest version stays, even over the cap.
+        assert_eq!(enforce_total_size(store.path(), 0).unwrap(), 400);
+        assert_eq!(kept(a), ["a3"]);
+        assert_eq!(kept(b), ["b2"]);
+    }
+
+    #[test]
+    fn shared_blobs_count_once() {
+        let store = tempfile::tempdir().unwrap();
+        let key = Path::new("/notes/note.md");
+        let now = SystemTime::now();
+        for text in ["one", "two", "one"] {
+            record_in(store.path(), key, None, text.as_bytes(), now).unwrap();
+        }
+        let index = load_index(&file_dir(store.path(), key)).unwrap();
+        assert_eq!(index.versions.len(), 3);
+        assert_eq!(blob_size(&index), 6);
+        assert_eq!(enforce_total_size(store.path(), u64::MAX).unwrap(), 6);
+    }
+
+    #[test]
+    fn versions_per_file_are_capped() {
+        let store = tempfile::tempdir().unwrap();
+        let key = Path::new("/notes/note.md");
+        let now = SystemTime::now();
+        for n in 0..MAX_VERSIONS_PER_FILE + 5 {
+            let text = format!("version {}", n);
+            record_in(store.path(), key, None, text.as_bytes(), now).unwrap();
+        }
+
+        let kept = versions(store.path(), key);
+        assert_eq!(kept.len(), MAX_VERSIONS_PER_FILE);
+        assert_eq!(contents(store.path(), key, &kept[0]), "version 5");
+    }
+}
@@ -9,89 +9,380 @@ use tauri::Emitter;
 use notify::{Watcher, RecursiveMode, RecommendedWatcher};
 use base64::Engine;
 
+mod bundle;
+mod cli;
+mod diff;
+mod error;
+mod fileops;
+mod highlight;
+mod history;
+mod linkcheck;
+mod markdown;
+mod merge;
+mod pdf;
+mod relink;
+mod render;
+mod replace;
+mod scope;
+mod search;
+mod search_index;
+mod tasks;
+mod trashcan;
+mod wikilinks;
+mod workspace;
+
+use error::PaddownError;
+
 /// Maximum file size we'll read (50MB)
 const MAX_FILE_SIZE: u64 = 50 * 1024 * 1024;
 
 /// Maximum image file size we'll base64-encode (10 MB)
-const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+pub(crate) const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
 
 // ─── File I/O Commands ───────────────────────────────────────
 
-#[tauri::command]
-fn read_file(path: String) -> Result<String, String> {
-    let path = Path::new(&path);
+/// Identifies the exact on-disk state a buffer was loaded from. The
+/// frontend hands it back to `write_file`, which refuses to overwrite the
+/// file if it no longer matches.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct Fingerprint {
+    /// Nanoseconds since the Unix epoch, as a string: it doesn't fit in a
+    /// JS number.
+    mtime_ns: String,
+    size: u64,
+    /// Hex SHA-256 of the raw bytes.
+    hash: String,
+}
 
-    if !path.is_file() {
-        return Err(format!("Not a file: {}", path.display()));
+impl Fingerprint {
+    fn new(metadata: &std::fs::Metadata, bytes: &[u8]) -> Self {
+        let mtime_ns = metadata.modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        Fingerprint {
+            mtime_ns: mtime_ns.to_string(),
+            size: bytes.len() as u64,
+            hash: content_hash(bytes),
+        }
     }
+}
+
+fn content_hash(bytes: &[u8]) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(serde::Serialize)]
+struct FileContents {
+    contents: String,
+    fingerprint: Fingerprint,
+}
+
+#[tauri::command]
+async fn read_file(app: tauri::AppHandle, path: String) -> error::Result<FileContents> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        read_text_file(path)
+    })
+    .await?
+}
+
+fn read_text_file(path: &Path) -> error::Result<FileContents> {
+    let metadata = std::fs::metadata(path).map_err(|e| PaddownError::io(path, e))?;
 
-    let metadata = std::fs::metadata(path)
-        .map_err(|e| format!("Cannot read metadata for {}: {}", path.display(), e))?;
+    if !metadata.is_file() {
+        return Err(PaddownError::invalid_path(path, "Not a file"));
+    }
 
     if metadata.len() > MAX_FILE_SIZE {
-        return Err(format!(
-            "File too large ({:.1} MB, max {} MB)",
-            metadata.len() as f64 / (1024.0 * 1024.0),
-            MAX_FILE_SIZE / (1024 * 1024)
-        ));
+        return Err(PaddownError::TooLarge { size: metadata.len(), max: MAX_FILE_SIZE });
     }
 
-    std::fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    let bytes = std::fs::read(path).map_err(|e| PaddownError::io(path, e))?;
+    let fingerprint = Fingerprint::new(&metadata, &bytes);
+    let contents = String::from_utf8(bytes).map_err(|e| {
+        PaddownError::io(path, std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    })?;
+    Ok(FileContents { contents, fingerprint })
+}
+
+/// Fails with `Conflict` if the file at `path` no longer matches `expected`.
+///
+/// The size and hash decide; the mtime doesn't. Its granularity can be
+/// coarse enough to miss a quick same-size edit, so a matching mtime proves
+/// nothing, and a file touched or rewritten with the same bytes (by a sync
+/// tool, say) has nothing in it to lose. A file deleted since it was loaded
+/// is a conflict too, so the user decides whether saving brings it back.
+fn check_unchanged(path: &Path, expected: &Fingerprint) -> error::Result<()> {
+    let conflict = || PaddownError::Conflict { path: path.to_string_lossy().into_owned() };
+    let metadata = match std::fs::metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(conflict()),
+        Err(e) => return Err(PaddownError::io(path, e)),
+    };
+    // Cheap check first; only hash when the size still matches.
+    let unchanged = metadata.len() == expected.size && {
+        let bytes = std::fs::read(path).map_err(|e| PaddownError::io(path, e))?;
+        content_hash(&bytes) == expected.hash
+    };
+    if unchanged { Ok(()) } else { Err(conflict()) }
+}
+
+/// Replaces `expected` at `start..end` (byte offsets) in a file's text.
+pub(crate) struct Splice<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub expected: &'a str,
+    pub replacement: &'a str,
+}
+
+/// `contents` with `splices` applied. Fails with `Conflict` if one no
+/// longer covers the text it was computed from, or if two overlap.
+pub(crate) fn apply_splices(
+    path: &Path,
+    contents: &str,
+    mut splices: Vec<Splice>,
+) -> error::Result<String> {
+    splices.sort_by_key(|s| s.start);
+    let mut out = String::with_capacity(contents.len());
+    let mut pos = 0;
+    for splice in splices {
+        if splice.start < pos || contents.get(splice.start..splice.end) != Some(splice.expected) {
+            return Err(PaddownError::Conflict { path: path.to_string_lossy().into_owned() });
+        }
+        out.push_str(&contents[pos..splice.start]);
+        out.push_str(splice.replacement);
+        pos = splice.end;
+    }
+    out.push_str(&contents[pos..]);
+    Ok(out)
 }
 
 #[tauri::command]
-fn write_file(path: String, contents: String) -> Result<(), String> {
-    let path = Path::new(&path);
+async fn write_file(
+    app: tauri::AppHandle,
+    path: String,
+    contents: String,
+    expected: Option<Fingerprint>,
+) -> error::Result<Fingerprint> {
+    tasks::blocking(move || save_contents(&app, Path::new(&path), &contents, expected.as_ref()))
+        .await?
+}
+
+/// Saves `contents` to `path` and records the save in the version history.
+/// History is best-effort: a failure there never fails the save itself.
+fn save_contents(
+    app: &tauri::AppHandle,
+    path: &Path,
+    contents: &str,
+    expected: Option<&Fingerprint>,
+) -> error::Result<Fingerprint> {
+    scope::check(app, path)?;
+    if let Some(expected) = expected {
+        check_unchanged(path, expected)?;
+    }
+    // Keep what is about to be overwritten so the first save of a file can
+    // be undone too.
+    let previous = std::fs::metadata(path)
+        .ok()
+        .filter(|m| m.is_file() && m.len() <= MAX_FILE_SIZE)
+        .and_then(|_| std::fs::read(path).ok());
+
+    write_text_file(path, contents)?;
+    let metadata = std::fs::metadata(path).map_err(|e| PaddownError::io(path, e))?;
+
+    let _ = history::record(app, path, previous.as_deref(), contents.as_bytes());
+    Ok(Fingerprint::new(&metadata, contents.as_bytes()))
+}
+
+fn write_text_file(path: &Path, contents: &str) -> error::Result<()> {
+    stage_text_file(path, contents)?.commit()
+}
+
+/// New contents written to a sibling temp file, waiting to be renamed over
+/// the destination. Staging is the slow, failure-prone part; callers that
+/// update several files stage them all first so a failure leaves every
+/// file untouched.
+pub(crate) struct StagedFile {
+    tmp: PathBuf,
+    target: PathBuf,
+}
+
+impl StagedFile {
+    fn commit(self) -> error::Result<()> {
+        std::fs::rename(&self.tmp, &self.target).map_err(|e| {
+            let _ = std::fs::remove_file(&self.tmp);
+            PaddownError::io(&self.target, e)
+        })?;
+
+        // Persist the rename itself. Not every filesystem supports syncing a
+        // directory, and the data is already safe at this point, so failures
+        // here are ignored.
+        if let Some(parent) = self.target.parent() {
+            sync_dir(parent);
+        }
+        Ok(())
+    }
+
+    fn discard(self) {
+        let _ = std::fs::remove_file(&self.tmp);
+    }
+}
 
+fn stage_text_file(path: &Path, contents: &str) -> error::Result<StagedFile> {
     if contents.len() as u64 > MAX_FILE_SIZE {
-        return Err(format!(
-            "File too large ({:.1} MB, max {:.0} MB)",
-            contents.len() as f64 / (1024.0 * 1024.0),
-            MAX_FILE_SIZE as f64 / (1024.0 * 1024.0)
-        ));
+        return Err(PaddownError::TooLarge { size: contents.len() as u64, max: MAX_FILE_SIZE });
     }
+    stage_file(path, contents.as_bytes())
+}
+
+/// Writes `bytes` to `path` atomically, like a save, for generated files
+/// such as exports.
+pub(crate) fn write_file_atomic(path: &Path, bytes: &[u8]) -> error::Result<()> {
+    stage_file(path, bytes)?.commit()
+}
+
+fn stage_file(path: &Path, bytes: &[u8]) -> error::Result<StagedFile> {
+    // Saving through a symlink updates the file it points at; renaming over
+    // the link itself would replace it with a regular file.
+    let path = resolve_symlinks(path)?;
 
     let parent = path.parent().ok_or_else(|| {
-        format!("Path has no parent directory: {}", path.display())
+        PaddownError::invalid_path(&path, "Path has no parent directory")
     })?;
     if !parent.as_os_str().is_empty() && !parent.exists() {
-        return Err(format!("Directory does not exist: {}", parent.display()));
+        return Err(PaddownError::NotFound { path: parent.to_string_lossy().into_owned() });
     }
 
     // Atomic write: stage to a sibling temp file, then rename. A crash
     // mid-write leaves the original file intact.
     let file_name = path.file_name().ok_or_else(|| {
-        format!("Path has no file name: {}", path.display())
+        PaddownError::invalid_path(&path, "Path has no file name")
     })?;
     let mut tmp_name = file_name.to_os_string();
     tmp_name.push(".paddown-tmp");
     let tmp_path = parent.join(&tmp_name);
 
-    std::fs::write(&tmp_path, contents)
-        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
-
-    std::fs::rename(&tmp_path, path).map_err(|e| {
+    let original = std::fs::metadata(&path).ok();
+    write_temp_file(&tmp_path, bytes, original.as_ref()).map_err(|e| {
         let _ = std::fs::remove_file(&tmp_path);
-        format!("Failed to finalize write to {}: {}", path.display(), e)
-    })
+        PaddownError::io(&tmp_path, e)
+    })?;
+
+    Ok(StagedFile { tmp: tmp_path, target: path })
+}
+
+/// Follows a chain of symlinks to the path that will actually be written.
+/// Unlike `canonicalize`, this works for dangling links (saving creates the
+/// target) and leaves the rest of the path untouched.
+fn resolve_symlinks(path: &Path) -> error::Result<PathBuf> {
+    const MAX_HOPS: usize = 40;
+    let mut current = path.to_path_buf();
+    for _ in 0..MAX_HOPS {
+        match std::fs::symlink_metadata(&current) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let target = std::fs::read_link(&current)
+                    .map_err(|e| PaddownError::io(&current, e))?;
+                // A relative link target is relative to the link's directory;
+                // `join` keeps absolute targets as they are.
+                current = match current.parent() {
+                    Some(dir) => dir.join(target),
+                    None => target,
+                };
+            }
+            _ => return Ok(current),
+        }
+    }
+    Err(PaddownError::invalid_path(path, "Too many levels of symbolic links"))
+}
+
+/// `path` without the `\\?\` prefix `canonicalize` gives on Windows, in the
+/// form dialogs, the sidebar and the OS's own APIs use. Verbatim UNC paths
+/// are left alone.
+pub(crate) fn plain_path(path: PathBuf) -> PathBuf {
+    if let Some(plain) = path.to_str().and_then(|text| text.strip_prefix(r"\\?\")) {
+        if !plain.starts_with(r"UNC\") {
+            return PathBuf::from(plain);
+        }
+    }
+    path
+}
+
+/// Writes and fsyncs the temp file. On Unix it also carries over the mode
+/// bits and, where we're allowed to, the ownership of the file being
+/// replaced.
+fn write_temp_file(
+    tmp_path: &Path,
+    bytes: &[u8],
+    original: Option<&std::fs::Metadata>,
+) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(meta) = original {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        // Create with the final mode so a 0600 note is never briefly
+        // world-readable; the umask may still strip bits, hence the
+        // explicit set_permissions below.
+        options.mode(meta.permissions().mode() & 0o7777);
+    }
+    let mut file = options.open(tmp_path)?;
+    file.write_all(bytes)?;
+
+    #[cfg(unix)]
+    if let Some(meta) = original {
+        use std::os::unix::fs::MetadataExt;
+        // Changing the owner needs privileges we usually don't have;
+        // fall back to just the group, which any member may set.
+        if std::os::unix::fs::fchown(&file, Some(meta.uid()), Some(meta.gid())).is_err() {
+            let _ = std::os::unix::fs::fchown(&file, None, Some(meta.gid()));
+        }
+        file.set_permissions(meta.permissions())?;
+    }
+    #[cfg(not(unix))]
+    let _ = original;
+
+    file.sync_all()
+}
+
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    {
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        if let Ok(handle) = std::fs::File::open(dir) {
+            let _ = handle.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
 }
 
 // ─── Dialog Commands ─────────────────────────────────────────
 
 #[tauri::command]
-fn show_open_dialog() -> Result<Option<String>, String> {
+fn show_open_dialog(app: tauri::AppHandle) -> error::Result<Option<String>> {
     let result = rfd::FileDialog::new()
         .add_filter("Markdown", &["md", "markdown"])
         .add_filter("Text", &["txt"])
         .add_filter("All Files", &["*"])
         .pick_file();
 
-    Ok(result.map(|p| p.to_string_lossy().into_owned()))
+    Ok(result.map(|p| granted_path(&app, p)))
 }
 
 #[tauri::command]
-fn show_save_dialog(default_name: Option<String>) -> Result<Option<String>, String> {
+fn show_save_dialog(
+    app: tauri::AppHandle,
+    default_name: Option<String>,
+) -> error::Result<Option<String>> {
     let mut dialog = rfd::FileDialog::new()
         .add_filter("Markdown", &["md", "markdown"])
         .add_filter("Text", &["txt"])
@@ -103,11 +394,14 @@ fn show_save_dialog(default_name: Option<String>) -> Result<Option<String>, Stri
 
     let result = dialog.save_file();
 
-    Ok(result.map(|p| p.to_string_lossy().into_owned()))
+    Ok(result.map(|p| granted_path(&app, p)))
 }
 
 #[tauri::command]
-fn show_export_html_dialog(default_name: Option<String>) -> Result<Option<String>, String> {
+fn show_export_html_dialog(
+    app: tauri::AppHandle,
+    default_name: Option<String>,
+) -> error::Result<Option<String>> {
     let mut dialog = rfd::FileDialog::new()
         .add_filter("HTML", &["html", "htm"]);
 
@@ -116,15 +410,36 @@ fn show_export_html_dialog(default_name: Option<String>) -> Result<Option<String
     }
 
     let result = dialog.save_file();
-    Ok(result.map(|p| p.to_string_lossy().into_owned()))
+    Ok(result.map(|p| granted_path(&app, p)))
+}
+
+#[tauri::command]
+fn show_export_pdf_dialog(
+    app: tauri::AppHandle,
+    default_name: Option<String>,
+) -> error::Result<Option<String>> {
+    let mut dialog = rfd::FileDialog::new()
+        .add_filter("PDF", &["pdf"]);
+
+    if let Some(name) = default_name {
+        dialog = dialog.set_file_name(&name);
+    }
+
+    let result = dialog.save_file();
+    Ok(result.map(|p| granted_path(&app, p)))
+}
+
+/// Grants the webview access to a path the user just picked in a dialog.
+fn granted_path(app: &tauri::AppHandle, path: PathBuf) -> String {
+    scope::grant_file(app, &path);
+    path.to_string_lossy().into_owned()
 }
 
 // ─── Settings Commands ──────────────────────────────────────
 
 #[tauri::command]
-fn read_settings(app: tauri::AppHandle) -> Result<String, String> {
-    let dir = app.path().app_config_dir()
-        .map_err(|e| format!("Cannot resolve config dir: {}", e))?;
+fn read_settings(app: tauri::AppHandle) -> error::Result<String> {
+    let dir = app.path().app_config_dir().map_err(PaddownError::internal)?;
 
     let path = dir.join("settings.json");
 
@@ -132,23 +447,19 @@ fn read_settings(app: tauri::AppHandle) -> Result<String, String> {
         return Ok("{}".to_string());
     }
 
-    std::fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read settings: {}", e))
+    std::fs::read_to_string(&path).map_err(|e| PaddownError::io(&path, e))
 }
 
 #[tauri::command]
-fn write_settings(app: tauri::AppHandle, contents: String) -> Result<(), String> {
-    let dir = app.path().app_config_dir()
-        .map_err(|e| format!("Cannot resolve config dir: {}", e))?;
+fn write_settings(app: tauri::AppHandle, contents: String) -> error::Result<()> {
+    let dir = app.path().app_config_dir().map_err(PaddownError::internal)?;
 
     if !dir.exists() {
-        std::fs::create_dir_all(&dir)
-            .map_err(|e| format!("Cannot create config dir: {}", e))?;
+        std::fs::create_dir_all(&dir).map_err(|e| PaddownError::io(&dir, e))?;
     }
 
     let path = dir.join("settings.json");
-    std::fs::write(&path, contents)
-        .map_err(|e| format!("Failed to write settings: {}", e))
+    std::fs::write(&path, contents).map_err(|e| PaddownError::io(&path, e))
 }
 
 // ─── Portable Mode Detection ────────────────────────────────
@@ -157,21 +468,34 @@ fn write_settings(app: tauri::AppHandle, contents: String) -> Result<(), String>
 /// If it exists, the app should use it instead of the app config dir.
 #[tauri::command]
 fn get_portable_settings_path() -> Option<String> {
+    portable_settings_path().map(|p| p.to_string_lossy().into_owned())
+}
+
+fn portable_settings_path() -> Option<PathBuf> {
     let exe = std::env::current_exe().ok()?;
-    let dir = exe.parent()?;
-    let portable = dir.join("settings.json");
-    if portable.exists() {
-        Some(portable.to_string_lossy().into_owned())
-    } else {
-        None
-    }
+    let portable = exe.parent()?.join("settings.json");
+    portable.exists().then_some(portable)
 }
 
-// ─── CLI Args Command ───────────────────────────────────────
+// ─── Command Line ───────────────────────────────────────────
 
+/// What the command line this instance was started with asks to open.
+/// Empty after the first call.
 #[tauri::command]
-fn get_cli_args() -> Vec<String> {
-    std::env::args().skip(1).collect()
+fn get_open_request(pending: tauri::State<'_, cli::PendingRequest>) -> cli::OpenRequest {
+    pending.take()
+}
+
+/// Grants access to the files and folders of an open request and
+/// registers its `--edit-stdin` sessions.
+fn accept_open_request(app: &tauri::AppHandle, request: &cli::OpenRequest) {
+    for file in &request.files {
+        scope::grant_path(app, Path::new(&file.path));
+    }
+    for folder in &request.folders {
+        scope::pin_folder(app, Path::new(folder));
+    }
+    cli::register_edits(app, request);
 }
 
 // ─── Update Check Command ───────────────────────────────────
@@ -183,7 +507,25 @@ struct UpdateInfo {
 }
 
 #[tauri::command]
-fn check_for_updates(current_version: String) -> Result<Option<UpdateInfo>, String> {
+async fn check_for_updates(
+    app: tauri::AppHandle,
+    current_version: String,
+    task_id: Option<String>,
+) -> error::Result<Option<UpdateInfo>> {
+    let task = tasks::register(&app, task_id);
+    let token = task.token();
+
+    // ureq has no way to abort an in-flight request, so race it against
+    // the cancel token instead. A cancelled request finishes (or times
+    // out) on the blocking pool and its result is discarded.
+    let fetch = tasks::blocking(move || fetch_latest_update(&current_version));
+    tokio::select! {
+        res = fetch => res?,
+        _ = token.cancelled() => Err(PaddownError::Cancelled),
+    }
+}
+
+fn fetch_latest_update(current_version: &str) -> error::Result<Option<UpdateInfo>> {
     let agent = ureq::AgentBuilder::new()
         .timeout_connect(Duration::from_secs(5))
         .timeout_read(Duration::from_secs(10))
@@ -195,9 +537,9 @@ fn check_for_updates(current_version: String) -> Result<Option<UpdateInfo>, Stri
     .set("User-Agent", "Paddown-Update-Checker")
     .set("Accept", "application/vnd.github.v3+json")
     .call()
-    .map_err(|e| format!("Network error: {}", e))?
+    .map_err(|e| PaddownError::Network { message: e.to_string() })?
     .into_json()
-    .map_err(|e| format!("JSON parse error: {}", e))?;
+    .map_err(|e| PaddownError::Network { message: format!("Invalid response: {}", e) })?;
 
     let tag = resp["tag_name"].as_str().unwrap_or("");
     let latest = tag.trim_start_matches('v');
@@ -208,9 +550,9 @@ fn check_for_updates(current_version: String) -> Result<Option<UpdateInfo>, Stri
     }
 
     let latest_ver = semver::Version::parse(latest)
-        .map_err(|e| format!("Invalid latest version '{}': {}", latest, e))?;
+        .map_err(|e| PaddownError::internal(format!("Invalid latest version '{}': {}", latest, e)))?;
     let current_ver = semver::Version::parse(current)
-        .map_err(|e| format!("Invalid current version '{}': {}", current, e))?;
+        .map_err(|e| PaddownError::internal(format!("Invalid current version '{}': {}", current, e)))?;
 
     if latest_ver > current_ver {
         let url = resp["html_url"].as_str().unwrap_or("").to_string();
@@ -226,91 +568,119 @@ fn check_for_updates(current_version: String) -> Result<Option<UpdateInfo>, Stri
 // ─── Read Image as Base64 Data URI ──────────────────────────
 
 #[tauri::command]
-fn read_file_base64(path: String, base_dir: String) -> Result<String, String> {
-    let p = Path::new(&path).canonicalize()
-        .map_err(|e| format!("Cannot resolve path {}: {}", path, e))?;
+async fn read_file_base64(
+    app: tauri::AppHandle,
+    path: String,
+    base_dir: String,
+) -> error::Result<String> {
+    tasks::blocking(move || {
+        scope::check_dir(&app, Path::new(&base_dir))?;
+        read_image_data_uri(&path, &base_dir)
+    })
+    .await?
+}
+
+/// The MIME type of an image the preview can embed, by extension.
+pub(crate) fn image_mime(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("jpg" | "jpeg") => Some("image/jpeg"),
+        Some("png") => Some("image/png"),
+        Some("gif") => Some("image/gif"),
+        Some("svg") => Some("image/svg+xml"),
+        Some("webp") => Some("image/webp"),
+        Some("bmp") => Some("image/bmp"),
+        Some("ico") => Some("image/x-icon"),
+        _ => None,
+    }
+}
+
+/// Reads an image the way the preview embeds it: it must lie under
+/// `base_dir`, be no larger than `MAX_IMAGE_SIZE` and be in a format
+/// `image_mime` knows. Returns the bytes and their MIME type.
+pub(crate) fn read_image(path: &Path, base_dir: &Path) -> error::Result<(Vec<u8>, &'static str)> {
+    let p = path.canonicalize().map_err(|e| PaddownError::io(path, e))?;
 
-    let base = Path::new(&base_dir).canonicalize()
-        .map_err(|e| format!("Cannot resolve base dir {}: {}", base_dir, e))?;
+    let base = base_dir.canonicalize().map_err(|e| PaddownError::io(base_dir, e))?;
 
     if !p.starts_with(&base) {
-        return Err("Path escapes the document directory".to_string());
+        return Err(PaddownError::invalid_path(&p, "Path escapes the document directory"));
     }
 
-    let metadata = std::fs::metadata(&p)
-        .map_err(|e| format!("Cannot read metadata for {}: {}", p.display(), e))?;
+    let metadata = std::fs::metadata(&p).map_err(|e| PaddownError::io(&p, e))?;
 
     if !metadata.is_file() {
-        return Err(format!("Not a file: {}", p.display()));
+        return Err(PaddownError::invalid_path(&p, "Not a file"));
     }
 
     if metadata.len() > MAX_IMAGE_SIZE {
-        return Err(format!(
-            "Image too large ({:.1} MB, max {} MB)",
-            metadata.len() as f64 / (1024.0 * 1024.0),
-            MAX_IMAGE_SIZE / (1024 * 1024)
-        ));
-    }
-
-    let mime = match p.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
-        Some("jpg" | "jpeg") => "image/jpeg",
-        Some("png") => "image/png",
-        Some("gif") => "image/gif",
-        Some("svg") => "image/svg+xml",
-        Some("webp") => "image/webp",
-        Some("bmp") => "image/bmp",
-        Some("ico") => "image/x-icon",
-        _ => return Err(format!("Unsupported image format: {}", p.display())),
-    };
+        return Err(PaddownError::TooLarge { size: metadata.len(), max: MAX_IMAGE_SIZE });
+    }
+
+    let mime = image_mime(&p).ok_or_else(|| PaddownError::Unsupported {
+        message: format!("Unsupported image format: {}", p.display()),
+    })?;
+
+    let bytes = std::fs::read(&p).map_err(|e| PaddownError::io(&p, e))?;
+    Ok((bytes, mime))
+}
 
-    let bytes = std::fs::read(&p)
-        .map_err(|e| format!("Failed to read {}: {}", p.display(), e))?;
+fn read_image_data_uri(path: &str, base_dir: &str) -> error::Result<String> {
+    let (bytes, mime) = read_image(Path::new(path), Path::new(base_dir))?;
+    Ok(data_uri(&bytes, mime))
+}
 
+pub(crate) fn data_uri(bytes: &[u8], mime: &str) -> String {
     let mut result = format!("data:{};base64,", mime);
-    base64::engine::general_purpose::STANDARD.encode_string(&bytes, &mut result);
-    Ok(result)
+    base64::engine::general_purpose::STANDARD.encode_string(bytes, &mut result);
+    result
 }
 
 // ─── File Modification Time ─────────────────────────────────
 
 #[tauri::command]
-fn get_file_mtime(path: String) -> Result<u64, String> {
-    let metadata = std::fs::metadata(&path)
-        .map_err(|e| format!("Cannot read metadata for {}: {}", path, e))?;
-    let modified = metadata.modified()
-        .map_err(|e| format!("Cannot get mtime for {}: {}", path, e))?;
+async fn get_file_mtime(app: tauri::AppHandle, path: String) -> error::Result<u64> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        file_mtime_secs(path)
+    })
+    .await?
+}
+
+fn file_mtime_secs(path: &Path) -> error::Result<u64> {
+    let metadata = std::fs::metadata(path).map_err(|e| PaddownError::io(path, e))?;
+    let modified = metadata.modified().map_err(|e| PaddownError::io(path, e))?;
     let duration = modified.duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| format!("Time error: {}", e))?;
+        .map_err(PaddownError::internal)?;
     Ok(duration.as_secs())
 }
 
 // ─── Recovery Directory ─────────────────────────────────────
 
-fn recovery_dir_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let dir = app.path().app_data_dir()
-        .map_err(|e| format!("Cannot resolve app data dir: {}", e))?;
+fn recovery_dir_path(app: &tauri::AppHandle) -> error::Result<PathBuf> {
+    let dir = app.path().app_data_dir().map_err(PaddownError::internal)?;
     Ok(dir.join("recovery"))
 }
 
 #[tauri::command]
-fn get_recovery_dir(app: tauri::AppHandle) -> Result<String, String> {
+fn get_recovery_dir(app: tauri::AppHandle) -> error::Result<String> {
     let recovery_dir = recovery_dir_path(&app)?;
     if !recovery_dir.exists() {
         std::fs::create_dir_all(&recovery_dir)
-            .map_err(|e| format!("Cannot create recovery dir: {}", e))?;
+            .map_err(|e| PaddownError::io(&recovery_dir, e))?;
     }
     Ok(recovery_dir.to_string_lossy().into_owned())
 }
 
 #[tauri::command]
-fn list_recovery_files(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+fn list_recovery_files(app: tauri::AppHandle) -> error::Result<Vec<String>> {
     let recovery_dir = recovery_dir_path(&app)?;
     if !recovery_dir.exists() {
         return Ok(Vec::new());
     }
     let mut files = Vec::new();
     for entry in std::fs::read_dir(&recovery_dir)
-        .map_err(|e| format!("Cannot read recovery dir: {}", e))?
+        .map_err(|e| PaddownError::io(&recovery_dir, e))?
         .flatten()
     {
         let path = entry.path();
@@ -322,7 +692,7 @@ fn list_recovery_files(app: tauri::AppHandle) -> Result<Vec<String>, String> {
 }
 
 #[tauri::command]
-fn delete_recovery_file(app: tauri::AppHandle, filename: String) -> Result<(), String> {
+fn delete_recovery_file(app: tauri::AppHandle, filename: String) -> error::Result<()> {
     // Reject anything that isn't a single normal path component, blocking
     // both traversal (`..`, separators) and absolute paths. This still allows
     // legitimate names like `notes..bak.json`.
@@ -330,12 +700,11 @@ fn delete_recovery_file(app: tauri::AppHandle, filename: String) -> Result<(), S
     let mut components = as_path.components();
     let first = components.next();
     if components.next().is_some() || !matches!(first, Some(Component::Normal(_))) {
-        return Err("Invalid filename".to_string());
+        return Err(PaddownError::invalid_path(as_path, "Invalid filename"));
     }
     let path = recovery_dir_path(&app)?.join(&filename);
     if path.is_file() {
-        std::fs::remove_file(&path)
-            .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+        std::fs::remove_file(&path).map_err(|e| PaddownError::io(&path, e))?;
     }
     Ok(())
 }
@@ -343,9 +712,11 @@ fn delete_recovery_file(app: tauri::AppHandle, filename: String) -> Result<(), S
 // ─── Open URL in Default Browser ────────────────────────────
 
 #[tauri::command]
-fn open_url(url: String) -> Result<(), String> {
+fn open_url(url: String) -> error::Result<()> {
     if !url.starts_with("http://") && !url.starts_with("https://") {
-        return Err("Only http/https URLs are supported".to_string());
+        return Err(PaddownError::Unsupported {
+            message: "Only http/https URLs are supported".to_string(),
+        });
     }
 
     #[cfg(windows)]
@@ -354,21 +725,21 @@ fn open_url(url: String) -> Result<(), String> {
             .args(["url.dll,FileProtocolHandler", &url])
             .creation_flags(0x08000000) // CREATE_NO_WINDOW
             .spawn()
-            .map_err(|e| format!("Failed to open URL: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to open URL: {}", e)))?;
     }
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
             .arg(&url)
             .spawn()
-            .map_err(|e| format!("Failed to open URL: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to open URL: {}", e)))?;
     }
     #[cfg(target_os = "linux")]
     {
         std::process::Command::new("xdg-open")
             .arg(&url)
             .spawn()
-            .map_err(|e| format!("Failed to open URL: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to open URL: {}", e)))?;
     }
 
     Ok(())
@@ -384,8 +755,11 @@ fn get_app_version() -> String {
 // ─── Sidebar: Folder Dialog ─────────────────────────────────
 
 #[tauri::command]
-fn show_folder_dialog() -> Result<Option<String>, String> {
+fn show_folder_dialog(app: tauri::AppHandle) -> error::Result<Option<String>> {
     let result = rfd::FileDialog::new().pick_folder();
+    if let Some(path) = &result {
+        scope::pin_folder(&app, path);
+    }
     Ok(result.map(|p| p.to_string_lossy().into_owned()))
 }
 
@@ -400,21 +774,44 @@ struct DirEntry {
 }
 
 #[tauri::command]
-fn scan_directory(path: String, extensions: Vec<String>) -> Result<Vec<DirEntry>, String> {
-    let root = PathBuf::from(&path);
+async fn scan_directory(
+    app: tauri::AppHandle,
+    path: String,
+    extensions: Vec<String>,
+    task_id: Option<String>,
+) -> error::Result<Vec<DirEntry>> {
+    scope::check(&app, Path::new(&path))?;
+    let task = tasks::register(&app, task_id);
+    let token = task.token();
+    let progress = task.progress();
+    tasks::blocking(move || scan_tree(&path, &extensions, &token, &progress)).await?
+}
+
+fn scan_tree(
+    path: &str,
+    extensions: &[String],
+    token: &tasks::CancelToken,
+    progress: &tasks::Progress,
+) -> error::Result<Vec<DirEntry>> {
+    let root = PathBuf::from(path);
     if !root.is_dir() {
-        return Err(format!("Directory not found: {}", path));
+        return Err(PaddownError::NotFound { path: path.to_string() });
     }
 
-    let exts_lower: Vec<String> = extensions.iter().map(|e| e.to_lowercase()).collect();
-
-    const MAX_DEPTH: u32 = 20;
+    const MAX_DEPTH: u32 = workspace::MAX_DEPTH;
     const MAX_ENTRIES: usize = 10_000;
-
-    struct Counter { count: usize }
+    /// Emit a `task-progress` event every this many visited entries.
+    const PROGRESS_EVERY: u64 = 250;
+
+    struct Counter<'a> {
+        count: usize,
+        visited: u64,
+        token: &'a tasks::CancelToken,
+        progress: &'a tasks::Progress,
+    }
 
     fn build_tree(dir: &Path, exts: &[String], depth: u32, counter: &mut Counter) -> Vec<DirEntry> {
-        if depth > MAX_DEPTH || counter.count >= MAX_ENTRIES {
+        if depth > MAX_DEPTH || counter.count >= MAX_ENTRIES || counter.token.is_cancelled() {
             return Vec::new();
         }
 
@@ -427,28 +824,19 @@ fn scan_directory(path: String, extensions: Vec<String>) -> Result<Vec<DirEntry>
         };
 
         for entry in entries.flatten() {
-            if counter.count >= MAX_ENTRIES {
+            if counter.count >= MAX_ENTRIES || counter.token.is_cancelled() {
                 break;
             }
 
+            counter.visited += 1;
+            if counter.visited.is_multiple_of(PROGRESS_EVERY) {
+                counter.progress.report(counter.visited, None);
+            }
+
             let name = entry.file_name().to_string_lossy().into_owned();
 
-            // Skip hidden entries (Windows hidden attribute)
-            #[cfg(windows)]
-            {
-                use std::os::windows::fs::MetadataExt;
-                if let Ok(meta) = entry.metadata() {
-                    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
-                    if meta.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0 {
-                        continue;
-                    }
-                }
-            }
-            #[cfg(not(windows))]
-            {
-                if name.starts_with('.') {
-                    continue;
-                }
+            if workspace::is_hidden(&entry) {
+                continue;
             }
 
             let file_type = match entry.file_type() {
@@ -475,22 +863,14 @@ fn scan_directory(path: String, extensions: Vec<String>) -> Result<Vec<DirEntry>
                         children,
                     });
                 }
-            } else if file_type.is_file() {
-                let matches_ext = path.extension()
-                    .and_then(|e| e.to_str())
-                    .is_some_and(|e| {
-                        let lower = e.to_ascii_lowercase();
-                        exts.iter().any(|allowed| allowed == &lower)
-                    });
-                if matches_ext {
-                    counter.count += 1;
-                    files.push(DirEntry {
-                        name,
-                        path: path.to_string_lossy().into_owned(),
-                        is_dir: false,
-                        children: Vec::new(),
-                    });
-                }
+            } else if file_type.is_file() && workspace::has_extension(&path, exts) {
+                counter.count += 1;
+                files.push(DirEntry {
+                    name,
+                    path: path.to_string_lossy().into_owned(),
+                    is_dir: false,
+                    children: Vec::new(),
+                });
             }
         }
 
@@ -501,17 +881,23 @@ fn scan_directory(path: String, extensions: Vec<String>) -> Result<Vec<DirEntry>
         dirs
     }
 
-    let mut counter = Counter { count: 0 };
-    Ok(build_tree(&root, &exts_lower, 0, &mut counter))
+    let mut counter = Counter { count: 0, visited: 0, token, progress };
+    let tree = build_tree(&root, extensions, 0, &mut counter);
+    if token.is_cancelled() {
+        return Err(PaddownError::Cancelled);
+    }
+    Ok(tree)
 }
 
 // ─── Sidebar: Reveal in Explorer ────────────────────────────
 
 #[tauri::command]
-fn reveal_in_explorer(path: String) -> Result<(), String> {
+fn reveal_in_explorer(app: tauri::AppHandle, path: String) -> error::Result<()> {
     let p = Path::new(&path);
+    // Scope first, so callers can't probe whether paths outside it exist.
+    scope::check(&app, p)?;
     if !p.exists() {
-        return Err(format!("Path does not exist: {}", path));
+        return Err(PaddownError::NotFound { path });
     }
 
     #[cfg(windows)]
@@ -519,14 +905,14 @@ fn reveal_in_explorer(path: String) -> Result<(), String> {
         std::process::Command::new("explorer")
             .args(["/select,", &path])
             .spawn()
-            .map_err(|e| format!("Failed to reveal in explorer: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to reveal in explorer: {}", e)))?;
     }
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
             .args(["-R", &path])
             .spawn()
-            .map_err(|e| format!("Failed to reveal in Finder: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to reveal in Finder: {}", e)))?;
     }
     #[cfg(target_os = "linux")]
     {
@@ -536,7 +922,7 @@ fn reveal_in_explorer(path: String) -> Result<(), String> {
         std::process::Command::new("xdg-open")
             .arg(&parent)
             .spawn()
-            .map_err(|e| format!("Failed to reveal in file manager: {}", e))?;
+            .map_err(|e| PaddownError::internal(format!("Failed to reveal in file manager: {}", e)))?;
     }
 
     Ok(())
@@ -546,10 +932,24 @@ fn reveal_in_explorer(path: String) -> Result<(), String> {
 
 struct WatcherState(Mutex<HashMap<String, RecommendedWatcher>>);
 
+fn watch_error(err: notify::Error) -> PaddownError {
+    match err.kind {
+        notify::ErrorKind::Io(io) => {
+            let path = err.paths.first().cloned().unwrap_or_default();
+            PaddownError::io(&path, io)
+        }
+        notify::ErrorKind::PathNotFound => PaddownError::NotFound {
+            path: err.paths.first().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default(),
+        },
+        _ => PaddownError::internal(format!("Failed to watch: {}", err)),
+    }
+}
+
 #[tauri::command]
-fn start_watching(app: tauri::AppHandle, path: String) -> Result<(), String> {
+fn start_watching(app: tauri::AppHandle, path: String) -> error::Result<()> {
+    scope::check(&app, Path::new(&path))?;
     let state = app.state::<WatcherState>();
-    let mut watchers = state.0.lock().map_err(|e| format!("Lock error: {}", e))?;
+    let mut watchers = state.0.lock().map_err(PaddownError::internal)?;
 
     if watchers.contains_key(&path) {
         return Ok(());
@@ -560,24 +960,26 @@ fn start_watching(app: tauri::AppHandle, path: String) -> Result<(), String> {
 
     let mut watcher = RecommendedWatcher::new(
         move |res: Result<notify::Event, notify::Error>| {
-            if res.is_ok() {
+            if let Ok(event) = res {
+                search_index::changed(&app_handle, Path::new(&watch_path), &event.paths);
                 let _ = app_handle.emit_to("main", "fs-change", &watch_path);
             }
         },
         notify::Config::default(),
-    ).map_err(|e| format!("Failed to create watcher: {}", e))?;
+    ).map_err(watch_error)?;
 
     watcher.watch(Path::new(&path), RecursiveMode::Recursive)
-        .map_err(|e| format!("Failed to watch {}: {}", path, e))?;
+        .map_err(watch_error)?;
 
+    search_index::ensure(&app, Path::new(&path));
     watchers.insert(path, watcher);
     Ok(())
 }
 
 #[tauri::command]
-fn stop_watching(app: tauri::AppHandle, path: String) -> Result<(), String> {
+fn stop_watching(app: tauri::AppHandle, path: String) -> error::Result<()> {
     let state = app.state::<WatcherState>();
-    let mut watchers = state.0.lock().map_err(|e| format!("Lock error: {}", e))?;
+    let mut watchers = state.0.lock().map_err(PaddownError::internal)?;
     if let Some(mut watcher) = watchers.remove(&path) {
         let _ = watcher.unwatch(Path::new(&path));
     }
@@ -646,6 +1048,13 @@ fn migrate_legacy_bundle_id(app: &tauri::AppHandle) {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Subcommands like `paddown export` finish here, before any window or
+    // the single-instance handoff to a running copy.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     // Work around WebKitGTK EGL failures on Linux without DMA-BUF support
     #[cfg(target_os = "linux")]
     std::env::set_var("WEBKIT_DISABLE_DMABUF_RENDERER", "1");
@@ -655,29 +1064,56 @@ pub fn run() {
         // instance is launched (e.g. by double-clicking a .md file in
         // Explorer), this callback fires in the running instance with the
         // new process's argv; the new process then exits.
-        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
-            // Skip argv[0] (the exe path) so the frontend receives only
-            // the file arguments, matching get_cli_args's contract.
-            let args: Vec<String> = argv.into_iter().skip(1).collect();
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            // Paths are resolved against the directory the new process ran
+            // in, not this one's.
+            let request = cli::forwarded_request(&argv, Path::new(&cwd));
+            accept_open_request(app, &request);
             if let Some(window) = app.get_webview_window("main") {
                 let _ = window.unminimize();
                 let _ = window.show();
                 let _ = window.set_focus();
-                let _ = window.emit("second-instance", &args);
+                let _ = window.emit("second-instance", &request);
             }
         }))
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .setup(|app| {
             migrate_legacy_bundle_id(&app.handle());
+            app.manage(scope::ScopeState::load(app.handle()));
+            app.manage(search_index::IndexState::start(app.handle().clone()));
+            let request = cli::startup_request();
+            accept_open_request(app.handle(), &request);
+            app.manage(cli::PendingRequest::new(request));
             Ok(())
         })
+        // Files dropped on the window are read by the frontend afterwards,
+        // so the drop itself is what grants access to them.
+        .on_window_event(|window, event| {
+            match event {
+                tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) => {
+                    for path in paths {
+                        scope::grant_path(window.app_handle(), path);
+                    }
+                }
+                // Processes still waiting on `--edit-stdin` tabs would
+                // otherwise wait forever.
+                tauri::WindowEvent::Destroyed => cli::abandon_edits(window.app_handle()),
+                _ => {}
+            }
+        })
         .manage(WatcherState(Mutex::new(HashMap::new())))
+        .manage(tasks::TaskState::new())
+        .manage(cli::EditSessions::default())
+        .manage(trashcan::TrashState::new())
         .invoke_handler(tauri::generate_handler![
             read_file,
             write_file,
             show_open_dialog,
             show_save_dialog,
             show_export_html_dialog,
+            show_export_pdf_dialog,
+            pdf::export_pdf,
+            bundle::export_html,
             show_folder_dialog,
             scan_directory,
             reveal_in_explorer,
@@ -685,7 +1121,8 @@ pub fn run() {
             stop_watching,
             read_settings,
             write_settings,
-            get_cli_args,
+            get_open_request,
+            cli::finish_edit,
             get_portable_settings_path,
             open_url,
             check_for_updates,
@@ -695,7 +1132,213 @@ pub fn run() {
             get_recovery_dir,
             list_recovery_files,
             delete_recovery_file,
+            tasks::cancel_task,
+            scope::unpin_folder,
+            fileops::create_file,
+            fileops::create_folder,
+            fileops::rename_entry,
+            fileops::move_entry,
+            fileops::duplicate_entry,
+            fileops::delete_entry,
+            trashcan::undo_delete,
+            relink::preview_link_rewrites,
+            relink::apply_link_rewrites,
+            search::search_workspace,
+            search_index::query_index,
+            wikilinks::outgoing_links,
+            wikilinks::backlinks,
+            wikilinks::unresolved_links,
+            wikilinks::link_graph,
+            linkcheck::check_links,
+            markdown::markdown_outline,
+            markdown::markdown_structure,
+            render::render_markdown,
+            replace::preview_replace,
+            replace::apply_replace,
+            merge::merge_three_way,
+            diff::diff_sources,
+            history::list_file_versions,
+            history::read_file_version,
+            history::restore_file_version,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running Paddown");
+        .build(tauri::generate_context!())
+        .expect("error while building Paddown")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                search_index::flush(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbatim_prefixes_are_dropped() {
+        let plain = |text: &str| plain_path(PathBuf::from(text));
+        assert_eq!(plain(r"\\?\C:\Notes\a.md"), PathBuf::from(r"C:\Notes\a.md"));
+        assert_eq!(plain(r"C:\Notes\a.md"), PathBuf::from(r"C:\Notes\a.md"));
+        assert_eq!(plain(r"\\?\UNC\server\share"), PathBuf::from(r"\\?\UNC\server\share"));
+        assert_eq!(plain("/home/a.md"), PathBuf::from("/home/a.md"));
+    }
+
+    fn fingerprint(path: &Path) -> Fingerprint {
+        read_text_file(path).unwrap().fingerprint
+    }
+
+    fn is_conflict(result: error::Result<()>) -> bool {
+        matches!(result, Err(PaddownError::Conflict { .. }))
+    }
+
+    #[test]
+    fn unchanged_files_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        std::fs::write(&path, "hello").unwrap();
+        let expected = fingerprint(&path);
+        check_unchanged(&path, &expected).unwrap();
+    }
+
+    #[test]
+    fn same_size_edits_are_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        std::fs::write(&path, "hello").unwrap();
+        let expected = fingerprint(&path);
+        std::fs::write(&path, "jello").unwrap();
+        assert!(is_conflict(check_unchanged(&path, &expected)));
+        std::fs::write(&path, "hello!").unwrap();
+        assert!(is_conflict(check_unchanged(&path, &expected)));
+    }
+
+    #[test]
+    fn rewriting_the_same_bytes_is_not_a_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        std::fs::write(&path, "hello").unwrap();
+        let expected = fingerprint(&path);
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert_ne!(fingerprint(&path).mtime_ns, expected.mtime_ns);
+        check_unchanged(&path, &expected).unwrap();
+    }
+
+    #[test]
+    fn deleted_files_are_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.md");
+        std::fs::write(&path, "hello").unwrap();
+        let expected = fingerprint(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(is_conflict(check_unchanged(&path, &expected)));
+    }
+
+    fn leftover_temp_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().contains(".paddown-tmp"))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        for mode in [0o600, 0o755] {
+            let path = dir.path().join(format!("note-{:o}.md", mode));
+            std::fs::write(&path, "old").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+
+            write_text_file(&path, "new").unwrap();
+
+            let meta = std::fs::metadata(&path).unwrap();
+            assert_eq!(meta.permissions().mode() & 0o7777, mode);
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_through_a_symlink_updates_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.md");
+        let link = dir.path().join("link.md");
+        std::fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink("target.md", &link).unwrap();
+
+        write_text_file(&link, "new").unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_through_a_dangling_symlink_creates_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("link.md");
+        std::os::unix::fs::symlink("missing.md", &link).unwrap();
+
+        write_text_file(&link, "new").unwrap();
+
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(dir.path().join("missing.md")).unwrap(), "new");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.md");
+        std::os::unix::fs::symlink("b.md", &a).unwrap();
+        std::os::unix::fs::symlink("a.md", dir.path().join("b.md")).unwrap();
+
+        assert!(resolve_symlinks(&a).is_err());
+    }
+
+    #[test]
+    fn successful_save_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        write_text_file(&path, "first").unwrap();
+        write_text_file(&path, "second").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn failed_save_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // A non-empty folder can't be replaced by a file, so the rename fails.
+        let path = dir.path().join("folder.md");
+        std::fs::create_dir(&path).unwrap();
+        std::fs::write(path.join("inside.md"), "keep").unwrap();
+
+        assert!(write_text_file(&path, "new").is_err());
+        assert!(path.is_dir());
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn discarded_stage_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        std::fs::write(&path, "old").unwrap();
+
+        stage_text_file(&path, "new").unwrap().discard();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        assert!(leftover_temp_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn save_into_a_missing_folder_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("note.md");
+        assert!(matches!(write_text_file(&path, "new"), Err(PaddownError::NotFound { .. })));
+    }
 }
@@ -0,0 +1,312 @@
+//! Finding relative links and images that point at nothing.
+//!
+//! Links come from `markdown::parse`, so the checker sees exactly the links
+//! a move would rewrite, reference-style ones included. A link is broken
+//! when its file is missing, when it leads out of the pinned folder (or, for
+//! a document opened on its own, out of its folder), or when its `#anchor`
+//! names no heading in the target. Images follow the rules of
+//! `read_file_base64`, since that is what the preview and exports load them
+//! with: they must sit under the document's folder, be no larger than
+//! `MAX_IMAGE_SIZE` and be in a format it can embed.
+//!
+//! Anchors are compared against the GitHub-style slugs of the target's
+//! headings, ignoring case.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, PaddownError};
+use crate::{markdown, relink, scope, tasks, workspace};
+
+/// Emit a `task-progress` event every this many files checked.
+const PROGRESS_EVERY: u64 = 50;
+
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+enum Problem {
+    MissingFile,
+    MissingAnchor { anchor: String },
+    EscapesRoot,
+    ImageTooLarge { size: u64, max: u64 },
+    UnsupportedImage,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct BrokenLink {
+    /// The document containing the link.
+    path: String,
+    /// 1-based.
+    line: usize,
+    url: String,
+    image: bool,
+    #[serde(flatten)]
+    problem: Problem,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct LinkReport {
+    files_checked: u64,
+    broken: Vec<BrokenLink>,
+}
+
+/// The heading anchors of a Markdown document.
+fn heading_anchors(text: &str) -> HashSet<String> {
+    markdown::parse(text).headings.into_iter().map(|h| h.slug).collect()
+}
+
+// ─── Checking ────────────────────────────────────────────────
+
+struct Checker {
+    /// Links may not lead out of this folder.
+    root: PathBuf,
+    anchors: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl Checker {
+    fn has_anchor(&mut self, doc: &Path, contents: Option<&str>, anchor: &str) -> bool {
+        let anchors = self.anchors.entry(doc.to_path_buf()).or_insert_with(|| {
+            match contents {
+                Some(contents) => heading_anchors(contents),
+                None => crate::read_text_file(doc)
+                    .map(|f| heading_anchors(&f.contents))
+                    .unwrap_or_default(),
+            }
+        });
+        anchors.contains(&relink::percent_decode(anchor).to_lowercase())
+    }
+
+    fn check_link(
+        &mut self,
+        doc: &Path,
+        contents: &str,
+        url: &str,
+        image: bool,
+    ) -> Option<Problem> {
+        let missing_anchor = |anchor: &str| Problem::MissingAnchor { anchor: anchor.to_string() };
+        if !relink::is_relative(url) {
+            let anchor = url.strip_prefix('#').filter(|a| !a.is_empty())?;
+            if self.has_anchor(doc, Some(contents), anchor) {
+                return None;
+            }
+            return Some(missing_anchor(anchor));
+        }
+        let (target, suffix) = relink::split_suffix(url);
+        if target.is_empty() {
+            return None;
+        }
+        let dir = doc.parent()?;
+        let base = if image { dir } else { self.root.as_path() };
+
+        let joined = relink::lexical_normalize(&dir.join(relink::percent_decode(target)));
+        if !joined.starts_with(base) {
+            return Some(Problem::EscapesRoot);
+        }
+        let Ok(real) = std::fs::canonicalize(&joined) else {
+            return Some(Problem::MissingFile);
+        };
+        // A symlink inside the folder may still lead out of it.
+        if !real.starts_with(base) {
+            return Some(Problem::EscapesRoot);
+        }
+
+        if image {
+            let Ok(metadata) = std::fs::metadata(&real) else {
+                return Some(Problem::MissingFile);
+            };
+            if !metadata.is_file() {
+                return Some(Problem::MissingFile);
+            }
+            if metadata.len() > crate::MAX_IMAGE_SIZE {
+                return Some(Problem::ImageTooLarge {
+                    size: metadata.len(),
+                    max: crate::MAX_IMAGE_SIZE,
+                });
+            }
+            if crate::image_mime(&real).is_none() {
+                return Some(Problem::UnsupportedImage);
+            }
+            return None;
+        }
+
+        let anchor = suffix.strip_prefix('#').filter(|a| !a.is_empty())?;
+        if !workspace::is_markdown(&real) || self.has_anchor(&real, None, anchor) {
+            return None;
+        }
+        Some(missing_anchor(anchor))
+    }
+
+    fn check_file(&mut self, path: &Path, broken: &mut Vec<BrokenLink>) -> error::Result<()> {
+        let file = crate::read_text_file(path)?;
+        let contents = &file.contents;
+        for link in markdown::parse(contents).links {
+            let Some(problem) = self.check_link(path, contents, &link.url, link.image) else {
+                continue;
+            };
+            broken.push(BrokenLink {
+                path: path.to_string_lossy().into_owned(),
+                line: link.line,
+                url: link.url,
+                image: link.image,
+                problem,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn check(
+    app: &tauri::AppHandle,
+    path: &Path,
+    token: &tasks::CancelToken,
+    progress: &tasks::Progress,
+) -> error::Result<LinkReport> {
+    let folder = path.is_dir();
+    if folder {
+        scope::check_pinned_dir(app, path)?;
+    } else {
+        scope::check(app, path)?;
+    }
+    let path = std::fs::canonicalize(path).map_err(|e| PaddownError::io(path, e))?;
+    let root = match scope::pinned_root(app, &path)? {
+        Some(root) => root,
+        None => path.parent().unwrap_or(&path).to_path_buf(),
+    };
+    let mut checker = Checker { root, anchors: HashMap::new() };
+    let mut report = LinkReport::default();
+
+    if !folder {
+        checker.check_file(&path, &mut report.broken)?;
+        report.files_checked = 1;
+        return Ok(report);
+    }
+    let files = workspace::markdown_files(&path, token);
+    let total = files.len() as u64;
+    for (visited, file) in (1u64..).zip(files) {
+        if token.is_cancelled() {
+            return Err(PaddownError::Cancelled);
+        }
+        // Unreadable files are left out, as in search.
+        if checker.check_file(&file, &mut report.broken).is_ok() {
+            report.files_checked += 1;
+        }
+        if visited.is_multiple_of(PROGRESS_EVERY) {
+            progress.report(visited, Some(total));
+        }
+    }
+    if token.is_cancelled() {
+        return Err(PaddownError::Cancelled);
+    }
+    Ok(report)
+}
+
+/// Reports every broken relative link and image in a Markdown file, or in
+/// every Markdown file under a folder inside a pinned folder.
+#[tauri::command]
+pub async fn check_links(
+    app: tauri::AppHandle,
+    path: String,
+    task_id: Option<String>,
+) -> error::Result<LinkReport> {
+    let task = tasks::register(&app, task_id);
+    let token = task.token();
+    let progress = task.progress();
+    tasks::blocking(move || check(&app, Path::new(&path), &token, &progress)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The problems `check_file` finds in `doc`, as `(line, url, problem)`.
+    fn problems(root: &Path, doc: &Path) -> Vec<(usize, String, String)> {
+        let mut checker = Checker { root: root.to_path_buf(), anchors: HashMap::new() };
+        let mut broken = Vec::new();
+        checker.check_file(doc, &mut broken).unwrap();
+        broken.into_iter().map(|b| (b.line, b.url, format!("{:?}", b.problem))).collect()
+    }
+
+    /// A pinned folder with a `docs` subfolder to check documents in.
+    fn fixture() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let docs = root.join("docs");
+        std::fs::create_dir(&docs).unwrap();
+        std::fs::write(docs.join("other.md"), "# Other\n\n## Second Part\n").unwrap();
+        std::fs::write(docs.join("my notes.md"), "text").unwrap();
+        std::fs::write(docs.join("data.csv"), "a,b").unwrap();
+        std::fs::write(docs.join("pic.png"), "png").unwrap();
+        std::fs::write(root.join("top.md"), "# Top").unwrap();
+        std::fs::write(root.join("outside.png"), "png").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn valid_links_anchors_and_images_are_not_reported() {
+        let (_dir, root) = fixture();
+        let doc = root.join("docs/index.md");
+        let text = "# Intro\n\n\
+            [self](#intro) [case](#INTRO)\n\
+            [other](other.md) [part](other.md#second-part) [top](../top.md#top)\n\
+            [spaces](my%20notes.md) [csv](data.csv#anything) [dir](./)\n\
+            [web](https://example.com/x.md) [mail](mailto:a@b.c)\n\
+            ![pic](pic.png) ![ref][img]\n\n\
+            [img]: ./pic.png\n";
+        std::fs::write(&doc, text).unwrap();
+        assert_eq!(problems(&root, &doc), vec![]);
+    }
+
+    #[test]
+    fn every_problem_is_reported_on_its_line() {
+        let (_dir, root) = fixture();
+        let docs = root.join("docs");
+        let big = std::fs::File::create(docs.join("big.png")).unwrap();
+        big.set_len(crate::MAX_IMAGE_SIZE + 1).unwrap();
+        std::fs::write(docs.join("pic.tiff"), "tiff").unwrap();
+
+        let doc = docs.join("index.md");
+        let text = "# Intro\n\
+            [gone](gone.md)\n\
+            [anchor](#nowhere)\n\
+            [other](other.md#missing)\n\
+            [out](../../escape.md)\n\
+            ![up](../outside.png)\n\
+            ![big](big.png)\n\
+            ![tiff](pic.tiff)\n\
+            ![gone](gone.png)\n";
+        std::fs::write(&doc, text).unwrap();
+
+        let missing = |anchor: &str| format!("MissingAnchor {{ anchor: {anchor:?} }}");
+        let too_large = format!(
+            "ImageTooLarge {{ size: {}, max: {} }}",
+            crate::MAX_IMAGE_SIZE + 1,
+            crate::MAX_IMAGE_SIZE,
+        );
+        let expected = [
+            (2, "gone.md", "MissingFile".to_string()),
+            (3, "#nowhere", missing("nowhere")),
+            (4, "other.md#missing", missing("missing")),
+            (5, "../../escape.md", "EscapesRoot".to_string()),
+            (6, "../outside.png", "EscapesRoot".to_string()),
+            (7, "big.png", too_large),
+            (8, "pic.tiff", "UnsupportedImage".to_string()),
+            (9, "gone.png", "MissingFile".to_string()),
+        ];
+        let expected: Vec<_> =
+            expected.into_iter().map(|(line, url, p)| (line, url.to_string(), p)).collect();
+        assert_eq!(problems(&root, &doc), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_folder_escape_it() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.md"), "# Secret").unwrap();
+        let (_dir, root) = fixture();
+        std::os::unix::fs::symlink(outside.path(), root.join("docs/link")).unwrap();
+
+        let doc = root.join("docs/index.md");
+        std::fs::write(&doc, "[secret](link/secret.md)\n").unwrap();
+        let expected = vec![(1, "link/secret.md".to_string(), "EscapesRoot".to_string())];
+        assert_eq!(problems(&root, &doc), expected);
+    }
+}
@@ -0,0 +1,643 @@
+//! The backend's Markdown parser: document structure from pulldown-cmark.
+//!
+//! One parse yields everything the workspace features need: the heading
+//! outline with GitHub-compatible anchors, links and images, reference
+//! definitions, code blocks, task items and `[[wiki-links]]`. Moving files
+//! (`relink`), checking links (`linkcheck`), the link graph (`wikilinks`)
+//! and the search index all read documents through `parse`, so they agree
+//! on what is a link and what is code.
+//!
+//! The options follow the preview's GFM mode (tables, task lists,
+//! strikethrough), plus footnotes and front matter so neither is mistaken
+//! for a link definition or a heading.
+
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::error;
+use crate::{scope, tasks};
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    /// The anchor GitHub gives the heading, unique within the document.
+    pub slug: String,
+    /// 1-based.
+    pub line: usize,
+}
+
+/// Where a link destination is written in the source.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Dest {
+    /// `[start, end)` UTF-8 byte offsets.
+    pub start: usize,
+    pub end: usize,
+    /// Written as `<dest>`, which allows spaces without encoding.
+    pub angle: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Link {
+    pub url: String,
+    pub title: String,
+    pub text: String,
+    pub line: usize,
+    pub image: bool,
+    /// The label of a reference-style link (`[text][label]`).
+    pub reference: Option<String>,
+    /// The destination in the source: the link's own for inline links, the
+    /// definition's for reference-style ones, none for autolinks.
+    #[serde(skip)]
+    pub(crate) dest: Option<Dest>,
+}
+
+/// A reference definition (`[label]: url`), used or not.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct Definition {
+    pub label: String,
+    pub url: String,
+    pub line: usize,
+    #[serde(skip)]
+    pub(crate) dest: Option<Dest>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct CodeBlock {
+    /// The first word of a fence's info string.
+    pub language: Option<String>,
+    /// `false` for indented code.
+    pub fenced: bool,
+    /// 1-based, inclusive; fences included.
+    pub line: usize,
+    pub end_line: usize,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TaskItem {
+    pub checked: bool,
+    /// The item's own text, without nested lists.
+    pub text: String,
+    pub line: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct WikiLink {
+    /// The note as written, without the heading or alias.
+    pub target: String,
+    pub heading: Option<String>,
+    pub alias: Option<String>,
+    /// 1-based line the link is on.
+    pub line: usize,
+    /// Written as `![[...]]`, to embed rather than link.
+    pub embed: bool,
+}
+
+#[derive(serde::Serialize, Clone, Debug, Default)]
+pub struct Document {
+    pub headings: Vec<Heading>,
+    pub links: Vec<Link>,
+    pub definitions: Vec<Definition>,
+    pub code_blocks: Vec<CodeBlock>,
+    pub tasks: Vec<TaskItem>,
+    pub wiki_links: Vec<WikiLink>,
+}
+
+impl Document {
+    /// Every destination written in the source, each once, in order.
+    pub(crate) fn destinations(&self) -> Vec<Dest> {
+        let links = self.links.iter().filter_map(|l| l.dest);
+        let definitions = self.definitions.iter().filter_map(|d| d.dest);
+        let unique: BTreeMap<usize, Dest> = links.chain(definitions)
+            .map(|d| (d.start, d))
+            .collect();
+        unique.into_values().collect()
+    }
+
+    /// The text of the first level-1 heading.
+    pub fn title(&self) -> Option<&str> {
+        self.headings.iter()
+            .find(|h| h.level == 1 && !h.text.is_empty())
+            .map(|h| h.text.as_str())
+    }
+}
+
+pub(crate) fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_WIKILINKS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+}
+
+// ─── Anchors ─────────────────────────────────────────────────
+
+/// GitHub's anchor for a heading: lowercased, punctuation other than `-`
+/// and `_` dropped, spaces turned into `-`.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hands out unique slugs, appending `-1`, `-2`… to repeats as GitHub does.
+#[derive(Default)]
+pub(crate) struct Slugger(HashMap<String, usize>);
+
+impl Slugger {
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slug(text);
+        let mut anchor = base.clone();
+        if let Some(&count) = self.0.get(&base) {
+            let mut n = count;
+            loop {
+                n += 1;
+                anchor = format!("{}-{}", base, n);
+                if !self.0.contains_key(&anchor) {
+                    break;
+                }
+            }
+            self.0.insert(base, n);
+        }
+        self.0.insert(anchor.clone(), 0);
+        anchor
+    }
+}
+
+// ─── Locating destinations ───────────────────────────────────
+
+/// Marks the bytes of `text` that sit inside inline code spans.
+fn code_span_mask(text: &str) -> Vec<bool> {
+    let bytes = text.as_bytes();
+    let mut mask = vec![false; bytes.len()];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let open = i;
+        while i < bytes.len() && bytes[i] == b'`' {
+            i += 1;
+        }
+        let run = i - open;
+        // The span closes at the next run of exactly the same length; an
+        // unmatched run is literal backticks.
+        let mut j = i;
+        let mut close = None;
+        while j < bytes.len() {
+            if bytes[j] != b'`' {
+                j += 1;
+                continue;
+            }
+            let start = j;
+            while j < bytes.len() && bytes[j] == b'`' {
+                j += 1;
+            }
+            if j - start == run {
+                close = Some(j);
+                break;
+            }
+        }
+        if let Some(end) = close {
+            mask[open..end].iter_mut().for_each(|m| *m = true);
+            i = end;
+        }
+    }
+    mask
+}
+
+/// Parses a link destination starting at `from` in `text`. `in_parens`
+/// applies the inline-link rule that an unbalanced `)` ends it.
+fn parse_dest(text: &str, from: usize, in_parens: bool) -> Option<Dest> {
+    let bytes = text.as_bytes();
+    let mut i = from;
+    while i < bytes.len() && (bytes[i] == b' ' || bytes[i] == b'\t') {
+        i += 1;
+    }
+    if i >= bytes.len() {
+        return None;
+    }
+    if bytes[i] == b'<' {
+        let close = text[i + 1..].find(['>', '<'])?;
+        if bytes[i + 1 + close] != b'>' {
+            return None;
+        }
+        return Some(Dest { start: i + 1, end: i + 1 + close, angle: true });
+    }
+
+    let start = i;
+    let mut depth = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b' ' | b'\t' | b'\n' | b'\r' => break,
+            b'(' if in_parens => depth += 1,
+            b')' if in_parens => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let end = i.min(bytes.len());
+    (end > start).then_some(Dest { start, end, angle: false })
+}
+
+/// The destination of the inline link or image spanning `range`: after the
+/// `](` that closes its text.
+fn inline_dest(source: &str, range: &Range<usize>) -> Option<Dest> {
+    let text = &source[range.clone()];
+    let bytes = text.as_bytes();
+    let mask = code_span_mask(text);
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            _ if mask[i] => {}
+            b'\\' => i += 1,
+            b'[' => depth += 1,
+            b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if !text.get(i..)?.starts_with("](") {
+        return None;
+    }
+    let dest = parse_dest(text, i + 2, true)?;
+    Some(Dest { start: dest.start + range.start, end: dest.end + range.start, ..dest })
+}
+
+/// The destination of the reference definition spanning `range`.
+fn definition_dest(source: &str, range: &Range<usize>) -> Option<Dest> {
+    let text = &source[range.clone()];
+    let close = text.find("]:")?;
+    let dest = parse_dest(text, close + 2, false)?;
+    Some(Dest { start: dest.start + range.start, end: dest.end + range.start, ..dest })
+}
+
+// ─── Parsing ─────────────────────────────────────────────────
+
+/// 1-based line numbers for byte offsets.
+struct Lines(Vec<usize>);
+
+impl Lines {
+    fn new(text: &str) -> Self {
+        let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1));
+        Lines(starts.collect())
+    }
+
+    fn of(&self, offset: usize) -> usize {
+        self.0.partition_point(|&start| start <= offset)
+    }
+}
+
+/// A link or wiki-link whose text is still being collected.
+struct OpenLink {
+    start: usize,
+    link: Link,
+    wiki: Option<(bool, bool)>,
+}
+
+/// A list item, which becomes a task item if it has a checkbox.
+struct OpenItem {
+    task: Option<TaskItem>,
+    /// Still in the item's own text, before any nested block.
+    collecting: bool,
+}
+
+fn wiki_link(link: Link, has_pothole: bool, embed: bool) -> WikiLink {
+    // Inside a table the alias pipe is escaped as `\|`.
+    let url = link.url.strip_suffix('\\').unwrap_or(&link.url);
+    let (target, heading) = match url.split_once('#') {
+        Some((target, heading)) => (target, Some(heading)),
+        None => (url, None),
+    };
+    let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    WikiLink {
+        target: target.trim().to_string(),
+        heading: heading.and_then(non_empty),
+        alias: if has_pothole { non_empty(&link.text) } else { None },
+        line: link.line,
+        embed,
+    }
+}
+
+/// Parses `text` into its structure.
+pub fn parse(text: &str) -> Document {
+    let lines = Lines::new(text);
+    let mut parser = Parser::new_ext(text, options()).into_offset_iter();
+    let mut doc = Document::default();
+    let mut slugger = Slugger::default();
+
+    let mut heading: Option<(u8, usize, String)> = None;
+    let mut links: Vec<OpenLink> = Vec::new();
+    // By start offset: an image inside a link closes before the link.
+    let mut found: Vec<(usize, Link)> = Vec::new();
+    let mut items: Vec<OpenItem> = Vec::new();
+    let mut code: Option<CodeBlock> = None;
+
+    let mut events = Vec::new();
+    for (event, range) in parser.by_ref() {
+        events.push((event, range));
+    }
+    let definitions = parser.reference_definitions();
+
+    for (event, range) in events {
+        let mut add_text = |s: &str| {
+            if let Some((_, _, text)) = heading.as_mut() {
+                text.push_str(s);
+            }
+            for open in links.iter_mut() {
+                open.link.text.push_str(s);
+            }
+            if let Some(OpenItem { task: Some(task), collecting: true }) = items.last_mut() {
+                task.text.push_str(s);
+            }
+        };
+        match event {
+            Event::Text(s) | Event::Code(s) if code.is_none() => add_text(&s),
+            Event::SoftBreak | Event::HardBreak => add_text(" "),
+            Event::TaskListMarker(checked) => {
+                if let Some(item) = items.last_mut() {
+                    let line = lines.of(range.start);
+                    item.task = Some(TaskItem { checked, text: String::new(), line });
+                }
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                heading = Some((level as u8, lines.of(range.start), String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, line, text)) = heading.take() {
+                    let text = text.trim().to_string();
+                    let slug = slugger.slug(&text);
+                    doc.headings.push(Heading { level, text, slug, line });
+                }
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title, id })
+            | Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+                let image = source_is_image(text, &range);
+                let reference = matches!(
+                    link_type,
+                    LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+                );
+                let dest = match link_type {
+                    LinkType::Inline => inline_dest(text, &range),
+                    _ if reference => {
+                        definitions.get(&id).and_then(|d| definition_dest(text, &d.span))
+                    }
+                    _ => None,
+                };
+                let wiki = match link_type {
+                    LinkType::WikiLink { has_pothole } => Some((has_pothole, image)),
+                    _ => None,
+                };
+                links.push(OpenLink {
+                    start: range.start,
+                    link: Link {
+                        url: dest_url.to_string(),
+                        title: title.to_string(),
+                        text: String::new(),
+                        line: lines.of(range.start),
+                        image,
+                        reference: reference.then(|| id.to_string()),
+                        dest,
+                    },
+                    wiki,
+                });
+            }
+            Event::End(TagEnd::Link) | Event::End(TagEnd::Image) => {
+                if let Some(open) = links.pop() {
+                    match open.wiki {
+                        Some((has_pothole, embed)) => {
+                            doc.wiki_links.push(wiki_link(open.link, has_pothole, embed));
+                        }
+                        None => found.push((open.start, open.link)),
+                    }
+                }
+            }
+            Event::Start(Tag::Item) => items.push(OpenItem { task: None, collecting: true }),
+            Event::Start(Tag::List(_)) => {
+                if let Some(item) = items.last_mut() {
+                    item.collecting = false;
+                }
+            }
+            Event::End(TagEnd::Item) => {
+                if let Some(OpenItem { task: Some(mut task), .. }) = items.pop() {
+                    task.text = task.text.trim().to_string();
+                    doc.tasks.push(task);
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                if let Some(item) = items.last_mut() {
+                    item.collecting = false;
+                }
+                code = Some(code_block(kind, &lines, &range));
+            }
+            Event::End(TagEnd::CodeBlock) => doc.code_blocks.extend(code.take()),
+            _ => {}
+        }
+    }
+
+    for (label, def) in definitions.iter() {
+        doc.definitions.push(Definition {
+            label: label.to_string(),
+            url: def.dest.to_string(),
+            line: lines.of(def.span.start),
+            dest: definition_dest(text, &def.span),
+        });
+    }
+    doc.definitions.sort_by_key(|d| d.line);
+    // Nested items finish before their parents.
+    doc.tasks.sort_by_key(|t| t.line);
+    found.sort_by_key(|(start, _)| *start);
+    doc.links = found.into_iter().map(|(_, link)| link).collect();
+    doc
+}
+
+/// Whether the link element at `range` is written as an image (`![`),
+/// including `![[embeds]]`.
+fn source_is_image(source: &str, range: &Range<usize>) -> bool {
+    source[range.clone()].starts_with('!')
+}
+
+fn code_block(kind: CodeBlockKind, lines: &Lines, range: &Range<usize>) -> CodeBlock {
+    let (language, fenced) = match kind {
+        CodeBlockKind::Fenced(info) => {
+            let language = info.split_whitespace().next().map(str::to_string);
+            (language, true)
+        }
+        CodeBlockKind::Indented => (None, false),
+    };
+    let end = range.end.saturating_sub(1).max(range.start);
+    CodeBlock { language, fenced, line: lines.of(range.start), end_line: lines.of(end) }
+}
+
+// ─── Commands ────────────────────────────────────────────────
+
+fn parse_source(
+    app: &tauri::AppHandle,
+    path: &Path,
+    contents: Option<String>,
+) -> error::Result<Document> {
+    let contents = match contents {
+        Some(contents) => contents,
+        None => {
+            scope::check(app, path)?;
+            crate::read_text_file(path)?.contents
+        }
+    };
+    Ok(parse(&contents))
+}
+
+/// The headings of a document, for an outline. `contents`, if given, is
+/// parsed instead of what's on disk, e.g. for unsaved edits.
+#[tauri::command]
+pub async fn markdown_outline(
+    app: tauri::AppHandle,
+    path: String,
+    contents: Option<String>,
+) -> error::Result<Vec<Heading>> {
+    tasks::blocking(move || parse_source(&app, Path::new(&path), contents).map(|d| d.headings))
+        .await?
+}
+
+/// Everything `parse` finds in a document: headings, links and images,
+/// reference definitions, code blocks, task items and wiki-links.
+#[tauri::command]
+pub async fn markdown_structure(
+    app: tauri::AppHandle,
+    path: String,
+    contents: Option<String>,
+) -> error::Result<Document> {
+    tasks::blocking(move || parse_source(&app, Path::new(&path), contents)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dest_text(source: &str, dest: Option<Dest>) -> Option<(&str, bool)> {
+        dest.map(|d| (&source[d.start..d.end], d.angle))
+    }
+
+    #[test]
+    fn slugs_follow_github() {
+        assert_eq!(slug("Hello, World!"), "hello-world");
+        assert_eq!(slug("  Foo_Bar - baz "), "foo_bar---baz");
+        assert_eq!(slug("Über `code` café"), "über-code-café");
+        assert_eq!(slug("1.2 Setup"), "12-setup");
+    }
+
+    #[test]
+    fn repeated_headings_get_numbered_slugs() {
+        let doc = parse("# A\n\n# A\n\n# A-1\n\n# A\n\n# !!\n\n# ??\n");
+        let slugs: Vec<_> = doc.headings.iter().map(|h| h.slug.as_str()).collect();
+        assert_eq!(slugs, ["a", "a-1", "a-1-1", "a-2", "", "-1"]);
+    }
+
+    #[test]
+    fn headings_have_1_based_lines() {
+        let doc = parse("---\ntitle: x\n---\n# Title\n\ntext\n\n## Sub *part*\nSetext\n---\n");
+        let headings: Vec<_> =
+            doc.headings.iter().map(|h| (h.level, h.text.as_str(), h.line)).collect();
+        assert_eq!(headings, [(1, "Title", 4), (2, "Sub part", 8), (2, "Setext", 9)]);
+        assert_eq!(doc.title(), Some("Title"));
+    }
+
+    #[test]
+    fn inline_destinations_are_located() {
+        let source = "[one](one.md \"Title\") [`](x`](two.md)\n\
+            ![pic](<my pic.png>) [nested (parens)](a_(b).md)\n";
+        let doc = parse(source);
+        let links: Vec<_> = doc.links.iter()
+            .map(|l| (l.url.as_str(), l.line, l.image, dest_text(source, l.dest)))
+            .collect();
+        assert_eq!(links, [
+            ("one.md", 1, false, Some(("one.md", false))),
+            ("two.md", 1, false, Some(("two.md", false))),
+            ("my pic.png", 2, true, Some(("my pic.png", true))),
+            ("a_(b).md", 2, false, Some(("a_(b).md", false))),
+        ]);
+        assert_eq!(doc.links[0].title, "Title");
+        assert_eq!(doc.links[1].text, "](x");
+    }
+
+    #[test]
+    fn reference_links_point_at_their_definition() {
+        let source = "[full][ref] [Ref][] [ref]\n\n\
+            <https://example.com> <mail@example.com>\n\n\
+            [ref]: ref.md\n[spaced]: <my ref.md> \"Title\"\n";
+        let doc = parse(source);
+        let links: Vec<_> = doc.links.iter()
+            .map(|l| (l.url.as_str(), l.reference.as_deref(), dest_text(source, l.dest)))
+            .collect();
+        let ref_dest = Some(("ref.md", false));
+        assert_eq!(links, [
+            ("ref.md", Some("ref"), ref_dest),
+            ("ref.md", Some("Ref"), ref_dest),
+            ("ref.md", Some("ref"), ref_dest),
+            ("https://example.com", None, None),
+            ("mail@example.com", None, None),
+        ]);
+
+        let definitions: Vec<_> = doc.definitions.iter()
+            .map(|d| (d.url.as_str(), d.line, dest_text(source, d.dest)))
+            .collect();
+        assert_eq!(definitions, [
+            ("ref.md", 5, ref_dest),
+            ("my ref.md", 6, Some(("my ref.md", true))),
+        ]);
+        // Three links share one written destination.
+        let written: Vec<_> =
+            doc.destinations().into_iter().map(|d| &source[d.start..d.end]).collect();
+        assert_eq!(written, ["ref.md", "my ref.md"]);
+    }
+
+    #[test]
+    fn code_blocks_keep_their_language_and_hide_links() {
+        let source = "```rust title=x\n[a](a.md)\n```\n\n    indented [b](b.md)\n\n\
+            ~~~\nplain\n~~~\n\n`[c](c.md)`\n";
+        let doc = parse(source);
+        let blocks: Vec<_> = doc.code_blocks.iter()
+            .map(|c| (c.language.as_deref(), c.fenced, c.line, c.end_line))
+            .collect();
+        assert_eq!(blocks, [
+            (Some("rust"), true, 1, 3),
+            (None, false, 5, 5),
+            (None, true, 7, 9),
+        ]);
+        assert!(doc.links.is_empty());
+    }
+
+    #[test]
+    fn task_items_are_collected_without_nested_lists() {
+        let doc = parse("- [ ] one\n- [x] two\n  - [ ] nested\n- plain\n\n1. [X] *three*\n");
+        let tasks: Vec<_> =
+            doc.tasks.iter().map(|t| (t.checked, t.text.as_str(), t.line)).collect();
+        assert_eq!(tasks, [
+            (false, "one", 1),
+            (true, "two", 2),
+            (false, "nested", 3),
+            (true, "three", 6),
+        ]);
+    }
+}
@@ -0,0 +1,232 @@
+//! Line-based three-way merge for reconciling an external edit with unsaved
+//! changes in a tab.
+//!
+//! `base` is the text the tab was loaded from, `disk` is what is on disk now
+//! and `buffer` is what the user has in the editor. Regions only one side
+//! touched are taken from that side; regions both sides changed differently
+//! become conflict chunks.
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+use crate::error;
+use crate::tasks;
+
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MergeChunk {
+    Resolved {
+        text: String,
+    },
+    Conflict {
+        /// 1-based line in the merged `text` where the conflict markers start.
+        line: usize,
+        base: String,
+        disk: String,
+        buffer: String,
+    },
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MergeResult {
+    Clean {
+        text: String,
+    },
+    /// `text` is the merge with git-style conflict markers around each
+    /// conflict; `chunks` is the same document split into resolved runs and
+    /// conflicts, for a frontend that wants to offer per-hunk choices.
+    Conflict {
+        text: String,
+        chunks: Vec<MergeChunk>,
+        conflicts: usize,
+    },
+}
+
+const MARKER_BUFFER: &str = "<<<<<<< Your changes\n";
+const MARKER_SPLIT: &str = "=======\n";
+const MARKER_DISK: &str = ">>>>>>> On disk\n";
+
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// For every base line, the index of the line it is matched to on the other
+/// side (if it survived unchanged).
+fn matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut map = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal { old_index, new_index, len } = op {
+            for k in 0..len {
+                map[old_index + k] = Some(new_index + k);
+            }
+        }
+    }
+    map
+}
+
+fn merge(base: &str, disk: &str, buffer: &str) -> MergeResult {
+    let base_lines = split_lines(base);
+    let disk_lines = split_lines(disk);
+    let buffer_lines = split_lines(buffer);
+    let to_disk = matches(&base_lines, &disk_lines);
+    let to_buffer = matches(&base_lines, &buffer_lines);
+
+    let mut chunks: Vec<MergeChunk> = Vec::new();
+    let mut text = String::new();
+    let mut conflicts = 0;
+
+    let push_resolved = |chunks: &mut Vec<MergeChunk>, text: &mut String, lines: &[&str]| {
+        if lines.is_empty() {
+            return;
+        }
+        let joined = lines.concat();
+        text.push_str(&joined);
+        match chunks.last_mut() {
+            Some(MergeChunk::Resolved { text: prev }) => prev.push_str(&joined),
+            _ => chunks.push(MergeChunk::Resolved { text: joined }),
+        }
+    };
+
+    let (mut b, mut d, mut u) = (0, 0, 0);
+    while b < base_lines.len() || d < disk_lines.len() || u < buffer_lines.len() {
+        // Stable line: unchanged on both sides at the current position.
+        if b < base_lines.len() && to_disk[b] == Some(d) && to_buffer[b] == Some(u) {
+            push_resolved(&mut chunks, &mut text, &base_lines[b..b + 1]);
+            b += 1;
+            d += 1;
+            u += 1;
+            continue;
+        }
+
+        // Find the next base line both sides still share; everything up to
+        // it is one unstable region.
+        let (mut nb, mut nd, mut nu) = (base_lines.len(), disk_lines.len(), buffer_lines.len());
+        for k in b..base_lines.len() {
+            if let (Some(kd), Some(ku)) = (to_disk[k], to_buffer[k]) {
+                (nb, nd, nu) = (k, kd, ku);
+                break;
+            }
+        }
+
+        let base_part = &base_lines[b..nb];
+        let disk_part = &disk_lines[d..nd];
+        let buffer_part = &buffer_lines[u..nu];
+
+        if buffer_part == base_part || disk_part == buffer_part {
+            push_resolved(&mut chunks, &mut text, disk_part);
+        } else if disk_part == base_part {
+            push_resolved(&mut chunks, &mut text, buffer_part);
+        } else {
+            conflicts += 1;
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            let line = text.matches('\n').count() + 1;
+            let buffer_text = with_trailing_newline(buffer_part.concat());
+            let disk_text = with_trailing_newline(disk_part.concat());
+            text.push_str(MARKER_BUFFER);
+            text.push_str(&buffer_text);
+            text.push_str(MARKER_SPLIT);
+            text.push_str(&disk_text);
+            text.push_str(MARKER_DISK);
+            chunks.push(MergeChunk::Conflict {
+                line,
+                base: base_part.concat(),
+                disk: disk_part.concat(),
+                buffer: buffer_part.concat(),
+            });
+        }
+
+        (b, d, u) = (nb, nd, nu);
+    }
+
+    if conflicts == 0 {
+        MergeResult::Clean { text }
+    } else {
+        MergeResult::Conflict { text, chunks, conflicts }
+    }
+}
+
+fn with_trailing_newline(mut s: String) -> String {
+    if !s.is_empty() && !s.ends_with('\n') {
+        s.push('\n');
+    }
+    s
+}
+
+#[tauri::command]
+pub async fn merge_three_way(
+    base: String,
+    disk: String,
+    buffer: String,
+) -> error::Result<MergeResult> {
+    tasks::blocking(move || merge(&base, &disk, &buffer)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(result: MergeResult) -> String {
+        match result {
+            MergeResult::Clean { text } => text,
+            MergeResult::Conflict { text, .. } => panic!("unexpected conflict:\n{}", text),
+        }
+    }
+
+    #[test]
+    fn takes_changes_from_either_side() {
+        let base = "one\ntwo\nthree\nfour\n";
+        let disk = "one\nTWO\nthree\nfour\n";
+        let buffer = "one\ntwo\nthree\nFOUR\n";
+        assert_eq!(clean(merge(base, disk, buffer)), "one\nTWO\nthree\nFOUR\n");
+    }
+
+    #[test]
+    fn identical_changes_merge_cleanly() {
+        let base = "a\nb\nc\n";
+        let both = "a\nB\nc\n";
+        assert_eq!(clean(merge(base, both, both)), both);
+    }
+
+    #[test]
+    fn insertions_and_deletions_merge() {
+        let base = "a\nb\nc\n";
+        let disk = "a\nc\n";
+        let buffer = "a\nb\nc\nd\n";
+        assert_eq!(clean(merge(base, disk, buffer)), "a\nc\nd\n");
+    }
+
+    #[test]
+    fn overlapping_changes_conflict() {
+        let base = "a\nb\nc\n";
+        let disk = "a\nfrom disk\nc\n";
+        let buffer = "a\nfrom buffer\nc\n";
+        let MergeResult::Conflict { text, chunks, conflicts } = merge(base, disk, buffer) else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            text,
+            "a\n<<<<<<< Your changes\nfrom buffer\n=======\nfrom disk\n>>>>>>> On disk\nc\n"
+        );
+        assert_eq!(chunks.len(), 3);
+        match &chunks[1] {
+            MergeChunk::Conflict { line, base, disk, buffer } => {
+                assert_eq!(*line, 2);
+                assert_eq!(base, "b\n");
+                assert_eq!(disk, "from disk\n");
+                assert_eq!(buffer, "from buffer\n");
+            }
+            other => panic!("expected a conflict chunk, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conflict_markers_start_on_their_own_line() {
+        let MergeResult::Conflict { text, .. } = merge("a\nb", "a\nx", "a\ny") else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(text, "a\n<<<<<<< Your changes\ny\n=======\nx\n>>>>>>> On disk\n");
+    }
+}
@@ -0,0 +1,1966 @@
+//! Markdown to PDF without a webview.
+//!
+//! Lays a document out from the same pulldown-cmark parse as `render`, with
+//! the sizes, spacing and colours `markdown.css` gives the preview in its
+//! light theme: headings, paragraphs, lists and task lists, blockquotes,
+//! highlighted code blocks, tables, rules, footnotes and local PNG and JPEG
+//! images. Raw HTML is left out, as in a print of the preview without
+//! scripts. The result is a PDF 1.4 file that uses the standard Helvetica
+//! and Courier fonts, so characters outside Windows-1252 print as `?`;
+//! the `PdfReport` lists the ones that did, for the export to warn about.
+//!
+//! Pages get an optional header with the title and date, and an optional
+//! "Page N of M" footer.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
+
+use crate::error;
+use crate::{bundle, highlight, markdown, relink, scope, tasks};
+
+// ─── Options ─────────────────────────────────────────────────
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+    Legal,
+    A5,
+}
+
+impl PageSize {
+    /// Width and height in points, portrait.
+    fn points(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+            PageSize::Legal => (612.0, 1008.0),
+            PageSize::A5 => (419.53, 595.28),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "a4" => Some(PageSize::A4),
+            "letter" => Some(PageSize::Letter),
+            "legal" => Some(PageSize::Legal),
+            "a5" => Some(PageSize::A5),
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    pub landscape: bool,
+    /// On every side, in millimetres.
+    pub margin_mm: f32,
+    /// The title and date at the top of every page.
+    pub header: bool,
+    /// "Page N of M" at the bottom of every page.
+    pub page_numbers: bool,
+    /// Wraps long code lines instead of cutting them off at the margin.
+    pub wrap_code: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page_size: PageSize::A4,
+            landscape: false,
+            margin_mm: 20.0,
+            header: false,
+            page_numbers: true,
+            wrap_code: true,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct PdfReport {
+    /// Characters the fonts don't have, printed as `?`.
+    pub unsupported: Vec<char>,
+}
+
+// ─── Metrics ─────────────────────────────────────────────────
+
+// markdown.css sizes, at 1px = 0.75pt.
+const BODY_SIZE: f32 = 12.0;
+const BODY_LINE: f32 = 1.7;
+const HEADING_LINE: f32 = 1.3;
+const BLOCK_GAP: f32 = 9.0;
+const NESTED_GAP: f32 = 4.5;
+const HEADING_AFTER: f32 = 6.0;
+const ITEM_GAP: f32 = 3.0;
+const LIST_INDENT: f32 = 30.0;
+const TASK_INDENT: f32 = 22.5;
+const CHECKBOX: f32 = 10.5;
+const QUOTE_BAR: f32 = 2.25;
+const QUOTE_INDENT: f32 = 14.25;
+const QUOTE_PAD: f32 = 1.2;
+const CODE_SIZE: f32 = 10.5;
+const CODE_LINE: f32 = 1.5;
+const CODE_PAD: f32 = 9.0;
+const CODE_PAD_TOP: f32 = 7.2;
+const CODE_RADIUS: f32 = 6.0;
+const LABEL_SIZE: f32 = 8.64;
+const LABEL_PAD_TOP: f32 = 5.4;
+const INLINE_CODE_SCALE: f32 = 0.9;
+const INLINE_CODE_PAD: f32 = 3.0;
+const TABLE_SIZE: f32 = 10.5;
+const TABLE_INSET: f32 = 6.0;
+const CELL_PAD_Y: f32 = 6.0;
+const CELL_PAD_RIGHT: f32 = 12.0;
+const TABLE_AFTER: f32 = 18.0;
+const FOOTNOTE_SIZE: f32 = 10.5;
+const RULE_MARGIN: f32 = 9.0;
+const RULE_INSET: f32 = 4.5;
+const HAIRLINE: f32 = 0.375;
+const HEADER_SIZE: f32 = 8.0;
+/// Largest image decoded, in pixels; bigger ones print their alt text.
+const MAX_PIXELS: u64 = 25_000_000;
+
+type Rgb = (u8, u8, u8);
+
+const TEXT: Rgb = (20, 24, 31);
+const SECONDARY: Rgb = (90, 98, 117);
+const BORDER_200: Rgb = (214, 219, 226);
+const BORDER_300: Rgb = (176, 184, 196);
+const DANGER: Rgb = (207, 58, 72);
+const WHITE: Rgb = (255, 255, 255);
+const CODE_BG: Rgb = (246, 248, 250);
+const CODE_BORDER: Rgb = (226, 230, 234);
+const INLINE_CODE_BG: Rgb = (245, 245, 245);
+const LINK_LINE: Rgb = (161, 163, 165);
+const TABLE_HEAD_BORDER: Rgb = (208, 212, 220);
+const TABLE_CELL_BORDER: Rgb = (231, 234, 237);
+
+/// The `tokens.css` colour of a `highlight` class.
+fn token_color(class: &str) -> Rgb {
+    match class {
+        "tok-keyword" => (129, 0, 194),
+        "tok-number" => (0, 128, 128),
+        "tok-comment" => (110, 118, 135),
+        "tok-string" => (3, 106, 58),
+        "tok-fn" | "tok-tmpl" => (0, 92, 197),
+        "tok-operator" | "tok-punct" => (43, 48, 59),
+        _ => TEXT,
+    }
+}
+
+// ─── Fonts ───────────────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Font {
+    Regular,
+    Bold,
+    Italic,
+    BoldItalic,
+    Mono,
+    MonoBold,
+}
+
+impl Font {
+    const ALL: [Font; 6] =
+        [Font::Regular, Font::Bold, Font::Italic, Font::BoldItalic, Font::Mono, Font::MonoBold];
+
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Italic => "F3",
+            Font::BoldItalic => "F4",
+            Font::Mono => "F5",
+            Font::MonoBold => "F6",
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Italic => "Helvetica-Oblique",
+            Font::BoldItalic => "Helvetica-BoldOblique",
+            Font::Mono => "Courier",
+            Font::MonoBold => "Courier-Bold",
+        }
+    }
+
+    fn new(bold: bool, italic: bool, mono: bool) -> Font {
+        match (mono, bold, italic) {
+            (true, false, _) => Font::Mono,
+            (true, true, _) => Font::MonoBold,
+            (false, false, false) => Font::Regular,
+            (false, true, false) => Font::Bold,
+            (false, false, true) => Font::Italic,
+            (false, true, true) => Font::BoldItalic,
+        }
+    }
+
+    fn is_bold(self) -> bool {
+        matches!(self, Font::Bold | Font::BoldItalic | Font::MonoBold)
+    }
+
+    /// The advance of a WinAnsi byte, in thousandths of the font size.
+    fn width(self, byte: u8) -> u16 {
+        let bold = match self {
+            Font::Mono | Font::MonoBold => return 600,
+            Font::Regular | Font::Italic => false,
+            Font::Bold | Font::BoldItalic => true,
+        };
+        let table = if bold { &HELVETICA_BOLD } else { &HELVETICA };
+        match byte {
+            32..=126 => table[usize::from(byte - 32)],
+            // The rest are close enough for laying out Latin-1 text.
+            130 | 145 | 146 => if bold { 278 } else { 222 },
+            132 | 147 | 148 => if bold { 500 } else { 333 },
+            133 | 137 | 140 | 151 | 153 => 1000,
+            136 | 139 | 152 | 155 => 333,
+            138 | 142 | 159 => 667,
+            149 => 350,
+            156 => 944,
+            160 => 278,
+            192..=222 => 722,
+            223 => 611,
+            _ => 556,
+        }
+    }
+}
+
+/// Helvetica advances for ASCII 32 to 126.
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // space to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // 0 to ?
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // @ to O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // P to _
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // ` to o
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // p to ~
+];
+
+/// Helvetica-Bold advances for ASCII 32 to 126.
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, // space to /
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, // 0 to ?
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778, // @ to O
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, // P to _
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, // ` to o
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // p to ~
+];
+
+/// The WinAnsiEncoding byte for `c`, or `?` if it has none.
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+        '\u{20ac}' => 128,
+        '\u{201a}' => 130,
+        '\u{192}' => 131,
+        '\u{201e}' => 132,
+        '\u{2026}' => 133,
+        '\u{2020}' => 134,
+        '\u{2021}' => 135,
+        '\u{2c6}' => 136,
+        '\u{2030}' => 137,
+        '\u{160}' => 138,
+        '\u{2039}' => 139,
+        '\u{152}' => 140,
+        '\u{17d}' => 142,
+        '\u{2018}' => 145,
+        '\u{2019}' => 146,
+        '\u{201c}' => 147,
+        '\u{201d}' => 148,
+        '\u{2022}' => 149,
+        '\u{2013}' => 150,
+        '\u{2014}' => 151,
+        '\u{2dc}' => 152,
+        '\u{2122}' => 153,
+        '\u{161}' => 154,
+        '\u{203a}' => 155,
+        '\u{153}' => 156,
+        '\u{17e}' => 158,
+        '\u{178}' => 159,
+        _ => b'?',
+    }
+}
+
+fn text_width(text: &str, font: Font, size: f32) -> f32 {
+    let units: u32 = text.chars().map(|c| u32::from(font.width(win_ansi(c)))).sum();
+    units as f32 * size / 1000.0
+}
+
+/// `text` as a PDF literal string in WinAnsiEncoding.
+fn pdf_string(text: &str) -> String {
+    literal(text.chars().map(win_ansi))
+}
+
+fn literal(bytes: impl Iterator<Item = u8>) -> String {
+    let mut out = String::from("(");
+    for byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            32..=126 => out.push(byte as char),
+            _ => out.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    out.push(')');
+    out
+}
+
+/// `text` as a UTF-16 PDF string, for metadata outside WinAnsi.
+fn utf16_string(text: &str) -> String {
+    let mut out = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        out.push_str(&format!("{:04X}", unit));
+    }
+    out.push('>');
+    out
+}
+
+/// A number for a content stream.
+fn num(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+fn rgb(color: Rgb) -> String {
+    let channel = |c: u8| num(f32::from(c) / 255.0);
+    format!("{} {} {}", channel(color.0), channel(color.1), channel(color.2))
+}
+
+// ─── Inline Layout ───────────────────────────────────────────
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Style {
+    font: Font,
+    size: f32,
+    color: Rgb,
+    /// Inline code, drawn on a background.
+    code: bool,
+    strike: bool,
+    /// Index into `Layout::links`.
+    link: Option<usize>,
+    /// Raise above the baseline, for footnote references.
+    rise: f32,
+}
+
+enum Piece {
+    Word(String, Style),
+    Space(Style),
+    /// Fixed, unbreakable space, like the padding of inline code.
+    Kern(f32, Style),
+    Break,
+}
+
+impl Piece {
+    fn width(&self) -> f32 {
+        match self {
+            Piece::Word(text, style) => text_width(text, style.font, style.size),
+            Piece::Space(style) => text_width(" ", style.font, style.size),
+            Piece::Kern(width, _) => *width,
+            Piece::Break => 0.0,
+        }
+    }
+}
+
+/// Text placed on a line; `x` is from the line's start.
+struct Placed {
+    x: f32,
+    width: f32,
+    text: String,
+    style: Style,
+}
+
+struct Line {
+    items: Vec<Placed>,
+    width: f32,
+    /// The largest font size on the line.
+    size: f32,
+}
+
+impl Line {
+    fn new(size: f32) -> Line {
+        Line { items: Vec::new(), width: 0.0, size }
+    }
+
+    fn place(&mut self, text: String, width: f32, style: Style) {
+        self.items.push(Placed { x: self.width, width, text, style });
+        self.width += width;
+        self.size = self.size.max(style.size);
+    }
+}
+
+/// Splits `pieces` at spaces into lines no wider than `width`. Words too
+/// long for a line of their own are broken anywhere, as `overflow-wrap:
+/// anywhere` does.
+fn break_lines(pieces: &[Piece], width: f32, size: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::new(size);
+    let mut space: Option<Style> = None;
+    let mut i = 0;
+    while i < pieces.len() {
+        match &pieces[i] {
+            Piece::Break => {
+                lines.push(std::mem::replace(&mut line, Line::new(size)));
+                space = None;
+                i += 1;
+            }
+            Piece::Space(style) => {
+                space = Some(*style);
+                i += 1;
+            }
+            Piece::Word(..) | Piece::Kern(..) => {
+                let end = pieces[i..]
+                    .iter()
+                    .position(|p| matches!(p, Piece::Space(_) | Piece::Break))
+                    .map_or(pieces.len(), |n| i + n);
+                let group = &pieces[i..end];
+                let group_width: f32 = group.iter().map(Piece::width).sum();
+                let space_width = space.map_or(0.0, |s| text_width(" ", s.font, s.size));
+                if !line.items.is_empty() && line.width + space_width + group_width > width {
+                    lines.push(std::mem::replace(&mut line, Line::new(size)));
+                } else if let Some(style) = space.filter(|_| !line.items.is_empty()) {
+                    line.place(" ".to_string(), space_width, style);
+                }
+                space = None;
+
+                for piece in group {
+                    match piece {
+                        Piece::Word(text, style) if group_width > width => {
+                            for c in text.chars() {
+                                let c = c.to_string();
+                                let w = text_width(&c, style.font, style.size);
+                                if !line.items.is_empty() && line.width + w > width {
+                                    lines.push(std::mem::replace(&mut line, Line::new(size)));
+                                }
+                                line.place(c, w, *style);
+                            }
+                        }
+                        Piece::Word(text, style) => line.place(text.clone(), piece.width(), *style),
+                        Piece::Kern(w, style) => line.place(String::new(), *w, *style),
+                        _ => {}
+                    }
+                }
+                i = end;
+            }
+        }
+    }
+    if !line.items.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The runs of `items` that share a key, as start and end offsets.
+fn spans<K: PartialEq>(items: &[Placed], key: impl Fn(&Placed) -> Option<K>) -> Vec<(f32, f32, K)> {
+    let mut spans: Vec<(f32, f32, K)> = Vec::new();
+    for item in items {
+        let Some(k) = key(item) else { continue };
+        match spans.last_mut() {
+            Some((_, end, last)) if *last == k && (*end - item.x).abs() < 0.01 => {
+                *end = item.x + item.width;
+            }
+            _ => spans.push((item.x, item.x + item.width, k)),
+        }
+    }
+    spans
+}
+
+// ─── Images ──────────────────────────────────────────────────
+
+struct Picture {
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+    filter: &'static str,
+    data: Vec<u8>,
+    /// 8-bit alpha, uncompressed.
+    alpha: Option<Vec<u8>>,
+}
+
+impl Picture {
+    fn decode(bytes: Vec<u8>, mime: &str) -> Option<Picture> {
+        match mime {
+            "image/jpeg" => Picture::jpeg(bytes),
+            "image/png" => Picture::png(&bytes),
+            _ => None,
+        }
+    }
+
+    /// Passes a baseline or progressive JPEG through as it is.
+    fn jpeg(bytes: Vec<u8>) -> Option<Picture> {
+        if !bytes.starts_with(&[0xFF, 0xD8]) {
+            return None;
+        }
+        let mut i = 2;
+        while i + 9 < bytes.len() {
+            if bytes[i] != 0xFF {
+                return None;
+            }
+            let marker = bytes[i + 1];
+            if marker == 0xFF {
+                i += 1;
+                continue;
+            }
+            if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+                i += 2;
+                continue;
+            }
+            let length = usize::from(u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]));
+            let frame = (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker);
+            if frame {
+                let height = u16::from_be_bytes([bytes[i + 5], bytes[i + 6]]);
+                let width = u16::from_be_bytes([bytes[i + 7], bytes[i + 8]]);
+                let color_space = match bytes[i + 9] {
+                    1 => "DeviceGray",
+                    3 => "DeviceRGB",
+                    _ => return None,
+                };
+                if width == 0 || height == 0 {
+                    return None;
+                }
+                return Some(Picture {
+                    width: u32::from(width),
+                    height: u32::from(height),
+                    color_space,
+                    filter: "DCTDecode",
+                    data: bytes,
+                    alpha: None,
+                });
+            }
+            i += 2 + length;
+        }
+        None
+    }
+
+    fn png(bytes: &[u8]) -> Option<Picture> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().ok()?;
+        let info = reader.info();
+        if u64::from(info.width) * u64::from(info.height) > MAX_PIXELS {
+            return None;
+        }
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buf).ok()?;
+        let pixels = &buf[..frame.buffer_size()];
+
+        let split = |channels: usize| -> (Vec<u8>, Vec<u8>) {
+            let mut color = Vec::with_capacity(pixels.len());
+            let mut alpha = Vec::with_capacity(pixels.len() / channels);
+            for pixel in pixels.chunks_exact(channels) {
+                color.extend_from_slice(&pixel[..channels - 1]);
+                alpha.push(pixel[channels - 1]);
+            }
+            (color, alpha)
+        };
+        let (color_space, color, alpha) = match frame.color_type {
+            png::ColorType::Grayscale => ("DeviceGray", pixels.to_vec(), None),
+            png::ColorType::Rgb => ("DeviceRGB", pixels.to_vec(), None),
+            png::ColorType::GrayscaleAlpha => {
+                let (color, alpha) = split(2);
+                ("DeviceGray", color, Some(alpha))
+            }
+            png::ColorType::Rgba => {
+                let (color, alpha) = split(4);
+                ("DeviceRGB", color, Some(alpha))
+            }
+            png::ColorType::Indexed => return None,
+        };
+        // Fully opaque images need no mask.
+        let alpha = alpha.filter(|a| a.iter().any(|&value| value != 255));
+        Some(Picture {
+            width: frame.width,
+            height: frame.height,
+            color_space,
+            filter: "FlateDecode",
+            data: deflate(&color),
+            alpha,
+        })
+    }
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    // Writing to a Vec can't fail.
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+// ─── Block Layout ────────────────────────────────────────────
+
+#[derive(Default)]
+struct Page {
+    content: String,
+    /// Link areas in PDF coordinates, with their index into `links`.
+    links: Vec<([f32; 4], usize)>,
+}
+
+enum Mark {
+    /// Text ending at `right`, like a bullet or an item's number.
+    Text { text: String, right: f32, style: Style },
+    Checkbox { checked: bool, x: f32 },
+}
+
+enum Kind {
+    Quote { bar: f32, top: f32 },
+    List { next: Option<u64> },
+    Item,
+    Footnote,
+}
+
+struct Container {
+    kind: Kind,
+    indent: f32,
+    /// Drawn beside the first line laid out inside the container.
+    mark: Option<Mark>,
+}
+
+struct Inline {
+    pieces: Vec<Piece>,
+    base: Style,
+    line_height: f32,
+    /// Space kept free below the first line, so a heading isn't left alone
+    /// at the bottom of a page.
+    keep: f32,
+}
+
+struct Table {
+    aligns: Vec<Alignment>,
+    rows: Vec<Vec<Vec<Piece>>>,
+    /// Whether the first row is the head.
+    head: bool,
+    in_head: bool,
+}
+
+struct Layout<'a> {
+    options: &'a PdfOptions,
+    base_dir: Option<&'a Path>,
+    page_width: f32,
+    page_height: f32,
+    margin: f32,
+    pages: Vec<Page>,
+    /// From the top of the current page's content area.
+    y: f32,
+    /// Space owed before the next block, dropped at the top of a page.
+    pending: f32,
+    containers: Vec<Container>,
+    inline: Option<Inline>,
+    strong: usize,
+    emphasis: usize,
+    strike: usize,
+    link: Option<usize>,
+    code: Option<(String, String)>,
+    image: Option<(String, String)>,
+    table: Option<Table>,
+    links: Vec<String>,
+    footnotes: HashMap<String, usize>,
+    pictures: Vec<Picture>,
+    loaded: HashMap<PathBuf, Option<usize>>,
+    /// Characters drawn as `?` for want of a glyph.
+    unsupported: BTreeSet<char>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(options: &'a PdfOptions, base_dir: Option<&'a Path>) -> Layout<'a> {
+        let (width, height) = options.page_size.points();
+        let (page_width, page_height) =
+            if options.landscape { (height, width) } else { (width, height) };
+        Layout {
+            options,
+            base_dir,
+            page_width,
+            page_height,
+            margin: options.margin_mm.clamp(0.0, 50.0) * 72.0 / 25.4,
+            pages: vec![Page::default()],
+            y: 0.0,
+            pending: 0.0,
+            containers: Vec::new(),
+            inline: None,
+            strong: 0,
+            emphasis: 0,
+            strike: 0,
+            link: None,
+            code: None,
+            image: None,
+            table: None,
+            links: Vec::new(),
+            footnotes: HashMap::new(),
+            pictures: Vec::new(),
+            loaded: HashMap::new(),
+            unsupported: BTreeSet::new(),
+        }
+    }
+
+    fn content_width(&self) -> f32 {
+        self.page_width - 2.0 * self.margin
+    }
+
+    fn content_height(&self) -> f32 {
+        self.page_height - 2.0 * self.margin
+    }
+
+    fn left(&self) -> f32 {
+        self.containers.iter().map(|c| c.indent).sum()
+    }
+
+    fn gap(&self) -> f32 {
+        if self.containers.is_empty() { BLOCK_GAP } else { NESTED_GAP }
+    }
+
+    fn body_style(&self) -> Style {
+        let footnote = self.containers.iter().any(|c| matches!(c.kind, Kind::Footnote));
+        let quote = self.containers.iter().any(|c| matches!(c.kind, Kind::Quote { .. }));
+        Style {
+            font: Font::Regular,
+            size: if footnote { FOOTNOTE_SIZE } else { BODY_SIZE },
+            color: if footnote || quote { SECONDARY } else { TEXT },
+            code: false,
+            strike: false,
+            link: None,
+            rise: 0.0,
+        }
+    }
+
+    /// The style of text at this point of the inline content.
+    fn style(&self) -> Style {
+        let base = self.inline.as_ref().map_or_else(|| self.body_style(), |i| i.base);
+        Style {
+            font: Font::new(self.strong > 0 || base.font.is_bold(), self.emphasis > 0, false),
+            strike: self.strike > 0,
+            link: self.link,
+            ..base
+        }
+    }
+
+    // Drawing, in points from the top left of the content area.
+
+    fn page(&mut self) -> &mut Page {
+        self.pages.last_mut().expect("a page")
+    }
+
+    fn pdf_x(&self, x: f32) -> f32 {
+        self.margin + x
+    }
+
+    fn pdf_y(&self, top: f32) -> f32 {
+        self.page_height - self.margin - top
+    }
+
+    fn draw(&mut self, ops: &str) {
+        let page = self.page();
+        page.content.push_str(ops);
+        page.content.push('\n');
+    }
+
+    /// Notes the characters of `text` that will print as `?`.
+    fn check_glyphs(&mut self, text: &str) {
+        let missing = text.chars().filter(|&c| c != '?' && win_ansi(c) == b'?');
+        self.unsupported.extend(missing);
+    }
+
+    fn draw_text(&mut self, x: f32, baseline: f32, style: &Style, text: &str) {
+        self.check_glyphs(text);
+        let ops = format!(
+            "BT /{} {} Tf {} rg {} {} Td {} Tj ET",
+            style.font.resource(),
+            num(style.size),
+            rgb(style.color),
+            num(self.pdf_x(x)),
+            num(self.pdf_y(baseline) + style.rise),
+            pdf_string(text)
+        );
+        self.draw(&ops);
+    }
+
+    fn draw_rule(&mut self, x1: f32, x2: f32, top: f32, width: f32, color: Rgb) {
+        let ops = format!(
+            "{} w {} RG {} {} m {} {} l S",
+            num(width),
+            rgb(color),
+            num(self.pdf_x(x1)),
+            num(self.pdf_y(top)),
+            num(self.pdf_x(x2)),
+            num(self.pdf_y(top))
+        );
+        self.draw(&ops);
+    }
+
+    fn draw_rect(&mut self, x: f32, top: f32, width: f32, height: f32, color: Rgb) {
+        let ops = format!(
+            "{} rg {} {} {} {} re f",
+            rgb(color),
+            num(self.pdf_x(x)),
+            num(self.pdf_y(top + height)),
+            num(width),
+            num(height)
+        );
+        self.draw(&ops);
+    }
+
+    /// A filled and outlined box with rounded corners.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_box(
+        &mut self,
+        x: f32,
+        top: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        fill: Rgb,
+        stroke: (f32, Rgb),
+    ) {
+        let (x0, y0) = (self.pdf_x(x), self.pdf_y(top + height));
+        let (x1, y1) = (x0 + width, y0 + height);
+        let r = radius.min(width / 2.0).min(height / 2.0);
+        let k = r * 0.5523;
+        let n = num;
+        let ops = format!(
+            "{} w {} rg {} RG {} {} m {} {} l {} {} {} {} {} {} c {} {} l {} {} {} {} {} {} c \
+             {} {} l {} {} {} {} {} {} c {} {} l {} {} {} {} {} {} c b",
+            n(stroke.0), rgb(fill), rgb(stroke.1),
+            n(x0 + r), n(y0), n(x1 - r), n(y0),
+            n(x1 - r + k), n(y0), n(x1), n(y0 + r - k), n(x1), n(y0 + r),
+            n(x1), n(y1 - r),
+            n(x1), n(y1 - r + k), n(x1 - r + k), n(y1), n(x1 - r), n(y1),
+            n(x0 + r), n(y1),
+            n(x0 + r - k), n(y1), n(x0), n(y1 - r + k), n(x0), n(y1 - r),
+            n(x0), n(y0 + r),
+            n(x0), n(y0 + r - k), n(x0 + r - k), n(y0), n(x0 + r), n(y0),
+        );
+        self.draw(&ops);
+    }
+
+    fn draw_checkbox(&mut self, x: f32, top: f32, checked: bool) {
+        if !checked {
+            self.draw_box(x, top, CHECKBOX, CHECKBOX, 2.0, WHITE, (0.75, BORDER_300));
+            return;
+        }
+        self.draw_box(x, top, CHECKBOX, CHECKBOX, 2.0, DANGER, (0.75, DANGER));
+        let ops = format!(
+            "1.2 w {} RG {} {} m {} {} l {} {} l S",
+            rgb(WHITE),
+            num(self.pdf_x(x + 2.5)),
+            num(self.pdf_y(top + 5.5)),
+            num(self.pdf_x(x + 4.5)),
+            num(self.pdf_y(top + 7.8)),
+            num(self.pdf_x(x + 8.2)),
+            num(self.pdf_y(top + 3.0))
+        );
+        self.draw(&ops);
+    }
+
+    fn draw_line(&mut self, line: &Line, x: f32, top: f32, height: f32) {
+        let baseline = top + height / 2.0 + line.size * 0.35;
+
+        let code = spans(&line.items, |item| item.style.code.then_some(()));
+        for (start, end, ()) in code {
+            let size = line.size * INLINE_CODE_SCALE;
+            let box_top = baseline - size * 0.85;
+            let (width, height) = (end - start, size * 1.15);
+            let stroke = (HAIRLINE, BORDER_300);
+            self.draw_box(x + start, box_top, width, height, 4.8, INLINE_CODE_BG, stroke);
+        }
+
+        let mut run: Option<(f32, String, Style)> = None;
+        for item in &line.items {
+            match run.as_mut() {
+                Some((_, text, style)) if !item.text.is_empty() && *style == item.style => {
+                    text.push_str(&item.text);
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some((rx, text, style)) = run.take() {
+                self.draw_text(x + rx, baseline, &style, &text);
+            }
+            if !item.text.is_empty() {
+                run = Some((item.x, item.text.clone(), item.style));
+            }
+        }
+        if let Some((rx, text, style)) = run {
+            self.draw_text(x + rx, baseline, &style, &text);
+        }
+
+        for (start, end, link) in spans(&line.items, |item| item.style.link) {
+            self.draw_rule(x + start, x + end, baseline + 2.25, 0.75, LINK_LINE);
+            let rect = [
+                self.pdf_x(x + start),
+                self.pdf_y(top + height),
+                self.pdf_x(x + end),
+                self.pdf_y(top),
+            ];
+            self.page().links.push((rect, link));
+        }
+        let strike = spans(&line.items, |item| item.style.strike.then_some(item.style.color));
+        for (start, end, color) in strike {
+            self.draw_rule(x + start, x + end, baseline - line.size * 0.3, 0.75, color);
+        }
+    }
+
+    /// Draws the marks still waiting for a line, beside the line at `top`.
+    fn draw_marks(&mut self, top: f32, height: f32, size: f32) {
+        let marks: Vec<Mark> = self.containers.iter_mut().filter_map(|c| c.mark.take()).collect();
+        let baseline = top + height / 2.0 + size * 0.35;
+        for mark in marks {
+            match mark {
+                Mark::Text { text, right, style } => {
+                    let width = text_width(&text, style.font, style.size);
+                    self.draw_text(right - width, baseline, &style, &text);
+                }
+                Mark::Checkbox { checked, x } => {
+                    self.draw_checkbox(x, top + (height - CHECKBOX) / 2.0, checked);
+                }
+            }
+        }
+    }
+
+    // Pages and blocks.
+
+    fn new_page(&mut self) {
+        let bottom = self.y;
+        let quotes: Vec<(f32, f32)> = self
+            .containers
+            .iter()
+            .filter_map(|c| match c.kind {
+                Kind::Quote { bar, top } => Some((bar, top)),
+                _ => None,
+            })
+            .collect();
+        for (bar, top) in quotes {
+            self.draw_rect(bar, top, QUOTE_BAR, bottom - top, BORDER_300);
+        }
+        for container in &mut self.containers {
+            if let Kind::Quote { top, .. } = &mut container.kind {
+                *top = 0.0;
+            }
+        }
+        self.pages.push(Page::default());
+        self.y = 0.0;
+    }
+
+    /// Starts a new page unless `height` still fits on this one.
+    fn reserve(&mut self, height: f32) {
+        if self.y > 0.0 && self.y + height > self.content_height() {
+            self.new_page();
+        }
+    }
+
+    fn begin_block(&mut self) {
+        if self.y > 0.0 {
+            self.y += self.pending;
+        }
+        self.pending = 0.0;
+    }
+
+    fn begin_inline(&mut self, base: Style, line_height: f32, keep: f32) {
+        if self.table.is_none() {
+            self.begin_block();
+        }
+        self.inline = Some(Inline { pieces: Vec::new(), base, line_height, keep });
+    }
+
+    /// The inline content being collected, started as a paragraph if there
+    /// is none, as for the text of a tight list item.
+    fn pieces(&mut self) -> &mut Vec<Piece> {
+        if self.inline.is_none() {
+            self.begin_inline(self.body_style(), BODY_LINE, 0.0);
+        }
+        &mut self.inline.as_mut().expect("inline content").pieces
+    }
+
+    fn end_inline(&mut self) {
+        let Some(inline) = self.inline.take() else { return };
+        let x = self.left();
+        let lines = break_lines(&inline.pieces, self.content_width() - x, inline.base.size);
+        for (i, line) in lines.iter().enumerate() {
+            let height = line.size * inline.line_height;
+            self.reserve(height + if i == 0 { inline.keep } else { 0.0 });
+            self.draw_marks(self.y, height, line.size);
+            self.draw_line(line, x, self.y, height);
+            self.y += height;
+        }
+    }
+
+    fn push_text(&mut self, text: &str, style: Style) {
+        let pieces = self.pieces();
+        let mut words = text.split(|c: char| c.is_ascii_whitespace()).peekable();
+        while let Some(word) = words.next() {
+            if !word.is_empty() {
+                pieces.push(Piece::Word(word.to_string(), style));
+            }
+            if words.peek().is_some() && !matches!(pieces.last(), Some(Piece::Space(_))) {
+                pieces.push(Piece::Space(style));
+            }
+        }
+    }
+
+    fn push_code(&mut self, code: &str) {
+        let base = self.style();
+        let style = Style {
+            font: Font::new(base.font.is_bold(), false, true),
+            size: base.size * INLINE_CODE_SCALE,
+            color: DANGER,
+            code: true,
+            ..base
+        };
+        self.pieces().push(Piece::Kern(INLINE_CODE_PAD, style));
+        self.push_text(code, style);
+        self.pieces().push(Piece::Kern(INLINE_CODE_PAD, style));
+    }
+
+    fn footnote_number(&mut self, label: &str) -> usize {
+        let next = self.footnotes.len() + 1;
+        *self.footnotes.entry(label.to_string()).or_insert(next)
+    }
+
+    // Events.
+
+    fn document(&mut self, text: &str) {
+        let mut skip = false;
+        for event in Parser::new_ext(text, markdown::options()) {
+            match event {
+                Event::Start(Tag::MetadataBlock(_)) => skip = true,
+                Event::End(TagEnd::MetadataBlock(_)) => skip = false,
+                _ if skip => {}
+                Event::Start(tag) => self.start(tag),
+                Event::End(tag) => self.end(tag),
+                Event::Text(text) => {
+                    if let Some((_, code)) = self.code.as_mut() {
+                        code.push_str(&text);
+                    } else if let Some((_, alt)) = self.image.as_mut() {
+                        alt.push_str(&text);
+                    } else {
+                        let style = self.style();
+                        self.push_text(&text, style);
+                    }
+                }
+                Event::Code(code) => {
+                    if let Some((_, alt)) = self.image.as_mut() {
+                        alt.push_str(&code);
+                    } else {
+                        self.push_code(&code);
+                    }
+                }
+                Event::SoftBreak => {
+                    let style = self.style();
+                    self.pieces().push(Piece::Space(style));
+                }
+                Event::HardBreak => self.pieces().push(Piece::Break),
+                Event::InlineHtml(html) if html.trim().to_lowercase().starts_with("<br") => {
+                    self.pieces().push(Piece::Break);
+                }
+                Event::Rule => self.rule(),
+                Event::TaskListMarker(checked) => self.task_marker(checked),
+                Event::FootnoteReference(label) => {
+                    let number = self.footnote_number(&label);
+                    let base = self.style();
+                    let style = Style { size: base.size * 0.75, rise: base.size * 0.4, ..base };
+                    self.pieces().push(Piece::Word(number.to_string(), style));
+                }
+                _ => {}
+            }
+        }
+        self.end_inline();
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                self.end_inline();
+                self.begin_inline(self.body_style(), BODY_LINE, 0.0);
+            }
+            Tag::Heading { level, .. } => {
+                self.end_inline();
+                let (size, before) = match level {
+                    HeadingLevel::H1 => (16.5, 9.0),
+                    HeadingLevel::H2 => (13.5, 9.0),
+                    _ => (BODY_SIZE, 6.0),
+                };
+                self.pending += before;
+                let style = Style { font: Font::Bold, size, ..self.body_style() };
+                self.begin_inline(style, HEADING_LINE, BODY_SIZE * BODY_LINE * 2.0);
+            }
+            Tag::BlockQuote(_) => {
+                self.end_inline();
+                self.begin_block();
+                let bar = self.left();
+                self.containers.push(Container {
+                    kind: Kind::Quote { bar, top: self.y },
+                    indent: QUOTE_INDENT,
+                    mark: None,
+                });
+                self.y += QUOTE_PAD;
+            }
+            Tag::CodeBlock(kind) => {
+                self.end_inline();
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((info, String::new()));
+            }
+            Tag::List(first) => {
+                self.end_inline();
+                self.begin_block();
+                self.containers.push(Container {
+                    kind: Kind::List { next: first },
+                    indent: 0.0,
+                    mark: None,
+                });
+            }
+            Tag::Item => {
+                self.end_inline();
+                self.begin_block();
+                let left = self.left();
+                let style = self.body_style();
+                let text = match self.containers.last_mut().map(|c| &mut c.kind) {
+                    Some(Kind::List { next: Some(number) }) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "\u{2022}".to_string(),
+                };
+                let right = left + LIST_INDENT - 8.0;
+                self.containers.push(Container {
+                    kind: Kind::Item,
+                    indent: LIST_INDENT,
+                    mark: Some(Mark::Text { text, right, style }),
+                });
+            }
+            Tag::FootnoteDefinition(label) => {
+                self.end_inline();
+                self.begin_block();
+                let number = self.footnote_number(&label).to_string();
+                let left = self.left();
+                let style = Style { size: FOOTNOTE_SIZE, color: SECONDARY, ..self.body_style() };
+                let width = text_width(&number, style.font, style.size);
+                self.containers.push(Container {
+                    kind: Kind::Footnote,
+                    indent: width + 6.0,
+                    mark: Some(Mark::Text { text: number, right: left + width, style }),
+                });
+            }
+            Tag::Table(aligns) => {
+                self.end_inline();
+                let table = Table { aligns, rows: Vec::new(), head: false, in_head: false };
+                self.table = Some(table);
+            }
+            Tag::TableHead | Tag::TableRow => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                    table.in_head = matches!(tag, Tag::TableHead);
+                    table.head |= table.in_head;
+                }
+            }
+            Tag::TableCell => {
+                let mut style = Style { size: TABLE_SIZE, ..self.body_style() };
+                if self.table.as_ref().is_some_and(|t| t.in_head) {
+                    style.font = Font::Bold;
+                }
+                self.begin_inline(style, BODY_LINE, 0.0);
+            }
+            Tag::Emphasis => self.emphasis += 1,
+            Tag::Strong => self.strong += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Link { dest_url, .. } if has_scheme(&dest_url) => {
+                self.links.push(dest_url.to_string());
+                self.link = Some(self.links.len() - 1);
+            }
+            Tag::Image { dest_url, .. } => self.image = Some((dest_url.to_string(), String::new())),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                self.end_inline();
+                self.pending = self.gap();
+            }
+            TagEnd::Heading(_) => {
+                self.end_inline();
+                self.pending = if self.containers.is_empty() { HEADING_AFTER } else { self.gap() };
+            }
+            TagEnd::BlockQuote(_) => {
+                self.end_inline();
+                self.y += QUOTE_PAD;
+                let quote = self.containers.pop();
+                if let Some(Container { kind: Kind::Quote { bar, top }, .. }) = quote {
+                    self.draw_rect(bar, top, QUOTE_BAR, self.y - top, BORDER_300);
+                }
+                self.pending = self.gap();
+            }
+            TagEnd::CodeBlock => {
+                if let Some((info, code)) = self.code.take() {
+                    self.begin_block();
+                    self.code_block(&info, &code);
+                    self.pending = self.gap();
+                }
+            }
+            TagEnd::List(_) | TagEnd::FootnoteDefinition => {
+                self.end_inline();
+                self.containers.pop();
+                self.pending = self.gap();
+            }
+            TagEnd::Item => {
+                self.end_inline();
+                self.containers.pop();
+                self.pending = ITEM_GAP;
+            }
+            TagEnd::TableCell => {
+                let cell = self.inline.take().map(|i| i.pieces).unwrap_or_default();
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    self.begin_block();
+                    self.draw_table(table);
+                    self.pending = self.gap() + TABLE_AFTER;
+                }
+            }
+            TagEnd::Emphasis => self.emphasis = self.emphasis.saturating_sub(1),
+            TagEnd::Strong => self.strong = self.strong.saturating_sub(1),
+            TagEnd::Strikethrough => self.strike = self.strike.saturating_sub(1),
+            TagEnd::Link => self.link = None,
+            TagEnd::Image => self.end_image(),
+            _ => {}
+        }
+    }
+
+    fn task_marker(&mut self, checked: bool) {
+        let left = self.left();
+        let Some(item) = self.containers.last_mut().filter(|c| matches!(c.kind, Kind::Item)) else {
+            return;
+        };
+        let list = left - item.indent;
+        item.indent = TASK_INDENT;
+        item.mark = Some(Mark::Checkbox { checked, x: list + TASK_INDENT - 6.0 - CHECKBOX });
+    }
+
+    fn rule(&mut self) {
+        self.end_inline();
+        self.begin_block();
+        self.reserve(2.0 * RULE_MARGIN + HAIRLINE);
+        let (x1, x2) = (self.left() + RULE_INSET, self.content_width() - RULE_INSET);
+        self.draw_rule(x1, x2, self.y + RULE_MARGIN, HAIRLINE, BORDER_200);
+        self.y += 2.0 * RULE_MARGIN + HAIRLINE;
+        self.pending = self.gap();
+    }
+
+    // Images.
+
+    /// Loads an image the way the preview does, relative to the document's
+    /// folder and no further out. `None` if it can't be shown.
+    fn load_image(&mut self, url: &str) -> Option<usize> {
+        let dir = self.base_dir?;
+        let path = bundle::image_path(dir, url)?;
+        if let Some(&index) = self.loaded.get(&path) {
+            return index;
+        }
+        let picture = crate::read_image(&path, dir)
+            .ok()
+            .and_then(|(bytes, mime)| Picture::decode(bytes, mime));
+        let index = picture.map(|picture| {
+            self.pictures.push(picture);
+            self.pictures.len() - 1
+        });
+        self.loaded.insert(path, index);
+        index
+    }
+
+    fn end_image(&mut self) {
+        let Some((url, alt)) = self.image.take() else { return };
+        let index = if self.table.is_some() { None } else { self.load_image(&url) };
+        let Some(index) = index else {
+            let style = self.style();
+            self.push_text(&alt, style);
+            return;
+        };
+
+        // Images get a block of their own, splitting the paragraph around
+        // them if there is text too.
+        let resume = self.inline.as_ref().map(|i| (i.base, i.line_height));
+        self.end_inline();
+        self.begin_block();
+        let picture = &self.pictures[index];
+        let (width, height) = (picture.width as f32 * 0.75, picture.height as f32 * 0.75);
+        let x = self.left();
+        let scale = 1f32
+            .min((self.content_width() - x) / width)
+            .min(self.content_height() / height);
+        let (width, height) = (width * scale, height * scale);
+        self.reserve(height);
+        let (px, py) = (self.pdf_x(x), self.pdf_y(self.y + height));
+        self.draw_marks(self.y, height.min(BODY_SIZE * BODY_LINE), BODY_SIZE);
+        self.draw(&format!(
+            "q {} 0 0 {} {} {} cm /Im{} Do Q",
+            num(width),
+            num(height),
+            num(px),
+            num(py),
+            index
+        ));
+        if let Some(link) = self.link {
+            self.page().links.push(([px, py, px + width, py + height], link));
+        }
+        self.y += height;
+        if let Some((base, line_height)) = resume {
+            self.inline = Some(Inline { pieces: Vec::new(), base, line_height, keep: 0.0 });
+        }
+    }
+
+    // Code blocks.
+
+    fn code_block(&mut self, info: &str, code: &str) {
+        let lang = info.split_whitespace().next().unwrap_or("").to_lowercase();
+        let code = code.strip_suffix('\n').unwrap_or(code);
+
+        // Lines of coloured text.
+        let mut lines: Vec<Vec<(String, Rgb)>> = vec![Vec::new()];
+        let mut push = |text: &str, color: Rgb| {
+            for (i, part) in text.split('\n').enumerate() {
+                if i > 0 {
+                    lines.push(Vec::new());
+                }
+                if !part.is_empty() {
+                    let part = part.trim_end_matches('\r').replace('\t', "    ");
+                    lines.last_mut().expect("a line").push((part, color));
+                }
+            }
+        };
+        let mut plain = 0;
+        for (range, class) in highlight::tokens(code, &lang) {
+            push(&code[plain..range.start], TEXT);
+            push(&code[range.clone()], token_color(class));
+            plain = range.end;
+        }
+        push(&code[plain..], TEXT);
+
+        let x = self.left();
+        let width = self.content_width() - x;
+        let advance = CODE_SIZE * 0.6;
+        let columns = (((width - 2.0 * CODE_PAD) / advance).floor() as usize).max(1);
+        let rows: Vec<Vec<(String, Rgb)>> = if self.options.wrap_code {
+            lines.into_iter().flat_map(|line| wrap_code_line(line, columns)).collect()
+        } else {
+            lines
+        };
+
+        let label = if lang.is_empty() { 0.0 } else { LABEL_PAD_TOP + LABEL_SIZE * 1.5 };
+        let row_height = CODE_SIZE * CODE_LINE;
+        let mut start = 0;
+        loop {
+            let head = if start == 0 { label } else { 0.0 } + CODE_PAD_TOP;
+            let room = self.content_height() - self.y - head - CODE_PAD;
+            let fit = (room / row_height).floor().max(0.0) as usize;
+            if fit == 0 && self.y > 0.0 {
+                self.new_page();
+                continue;
+            }
+            let count = fit.max(1).min(rows.len() - start);
+            let height = head + count as f32 * row_height + CODE_PAD;
+            let top = self.y;
+            self.draw_box(x, top, width, height, CODE_RADIUS, CODE_BG, (0.75, CODE_BORDER));
+
+            if start == 0 && !lang.is_empty() {
+                let style = Style {
+                    font: Font::Mono,
+                    size: LABEL_SIZE,
+                    color: SECONDARY,
+                    ..self.body_style()
+                };
+                let baseline = top + LABEL_PAD_TOP + LABEL_SIZE;
+                self.draw_text(x + CODE_PAD, baseline, &style, &lang);
+            }
+            if !self.options.wrap_code {
+                self.draw(&format!(
+                    "q {} {} {} {} re W n",
+                    num(self.pdf_x(x + CODE_PAD)),
+                    num(self.pdf_y(top + height)),
+                    num(width - 2.0 * CODE_PAD),
+                    num(height)
+                ));
+            }
+            for (i, row) in rows[start..start + count].iter().enumerate() {
+                let baseline = top + head + i as f32 * row_height + row_height / 2.0
+                    + CODE_SIZE * 0.35;
+                let mut column = 0;
+                for (text, color) in row {
+                    let style = Style {
+                        font: Font::Mono,
+                        size: CODE_SIZE,
+                        color: *color,
+                        ..self.body_style()
+                    };
+                    let at = x + CODE_PAD + column as f32 * advance;
+                    self.draw_text(at, baseline, &style, text);
+                    column += text.chars().count();
+                }
+            }
+            if !self.options.wrap_code {
+                self.draw("Q");
+            }
+
+            self.y += height;
+            start += count;
+            if start >= rows.len() {
+                break;
+            }
+            self.new_page();
+        }
+    }
+
+    // Tables.
+
+    fn draw_table(&mut self, table: Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0).max(table.aligns.len());
+        if columns == 0 {
+            return;
+        }
+        let x = self.left() + TABLE_INSET;
+        let room = self.content_width() - self.left() - 2.0 * TABLE_INSET;
+
+        // Columns share the width by their content, as `table-layout: auto`
+        // does, filling the width like `min-width: 100%`.
+        let mut natural = vec![0f32; columns];
+        let mut least = vec![0f32; columns];
+        for row in &table.rows {
+            for (j, cell) in row.iter().enumerate() {
+                let width: f32 = cell.iter().map(Piece::width).sum();
+                natural[j] = natural[j].max(width + CELL_PAD_RIGHT);
+                least[j] = least[j].max(widest_word(cell) + CELL_PAD_RIGHT);
+            }
+        }
+        for j in 0..columns {
+            natural[j] = natural[j].max(least[j]).max(CELL_PAD_RIGHT + 12.0);
+            least[j] = least[j].max(CELL_PAD_RIGHT + 12.0);
+        }
+        let (total, minimum): (f32, f32) = (natural.iter().sum(), least.iter().sum());
+        let widths: Vec<f32> = if total <= room {
+            natural.iter().map(|w| w * room / total).collect()
+        } else if minimum <= room {
+            let share = (room - minimum) / (total - minimum);
+            least.iter().zip(&natural).map(|(l, n)| l + (n - l) * share).collect()
+        } else {
+            least.iter().map(|w| w * room / minimum).collect()
+        };
+
+        let line_height = TABLE_SIZE * BODY_LINE;
+        let layouts: Vec<(Vec<Vec<Line>>, f32)> = table
+            .rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<Vec<Line>> = row
+                    .iter()
+                    .enumerate()
+                    .map(|(j, cell)| break_lines(cell, widths[j] - CELL_PAD_RIGHT, TABLE_SIZE))
+                    .collect();
+                let lines = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+                (cells, lines as f32 * line_height + 2.0 * CELL_PAD_Y)
+            })
+            .collect();
+
+        for (i, (cells, height)) in layouts.iter().enumerate() {
+            if self.y > 0.0 && self.y + height > self.content_height() {
+                self.new_page();
+                // Repeat the head on every page the table runs onto.
+                if table.head && i > 0 {
+                    let (head, head_height) = &layouts[0];
+                    self.draw_row(head, *head_height, x, &widths, &table.aligns, true);
+                }
+            }
+            self.draw_row(cells, *height, x, &widths, &table.aligns, table.head && i == 0);
+        }
+    }
+
+    fn draw_row(
+        &mut self,
+        cells: &[Vec<Line>],
+        height: f32,
+        x: f32,
+        widths: &[f32],
+        aligns: &[Alignment],
+        head: bool,
+    ) {
+        let line_height = TABLE_SIZE * BODY_LINE;
+        let mut cell_x = x;
+        for (j, &width) in widths.iter().enumerate() {
+            let inner = width - CELL_PAD_RIGHT;
+            let lines = cells.get(j).map(Vec::as_slice).unwrap_or_default();
+            for (k, line) in lines.iter().enumerate() {
+                let offset = match aligns.get(j) {
+                    Some(Alignment::Center) => (inner - line.width) / 2.0,
+                    Some(Alignment::Right) => inner - line.width,
+                    _ => 0.0,
+                };
+                let top = self.y + CELL_PAD_Y + k as f32 * line_height;
+                self.draw_line(line, cell_x + offset.max(0.0), top, line_height);
+            }
+            let color = if head { TABLE_HEAD_BORDER } else { TABLE_CELL_BORDER };
+            self.draw_rule(cell_x, cell_x + width, self.y + height, HAIRLINE, color);
+            cell_x += width;
+        }
+        self.y += height;
+    }
+
+    // Writing the file.
+
+    fn finish(mut self, title: &str) -> (Vec<u8>, PdfReport) {
+        self.end_inline();
+        let count = self.pages.len();
+        let date = today();
+        if self.options.header {
+            self.check_glyphs(title);
+        }
+        let style = Style {
+            font: Font::Regular,
+            size: HEADER_SIZE,
+            color: SECONDARY,
+            code: false,
+            strike: false,
+            link: None,
+            rise: 0.0,
+        };
+        let width = self.content_width();
+        for number in 1..=count {
+            let mut ops = String::new();
+            let baseline = |top: f32| top + HEADER_SIZE * 0.35;
+            let mut text = |x: f32, top: f32, text: &str| {
+                ops.push_str(&format!(
+                    "BT /{} {} Tf {} rg {} {} Td {} Tj ET\n",
+                    style.font.resource(),
+                    num(style.size),
+                    rgb(style.color),
+                    num(self.margin + x),
+                    num(self.page_height - self.margin - baseline(top)),
+                    pdf_string(text)
+                ));
+            };
+            if self.options.header {
+                let date_width = text_width(&date, style.font, style.size);
+                let title = fit_text(title, width - date_width - 24.0, &style);
+                text(0.0, -self.margin / 2.0, &title);
+                text(width - date_width, -self.margin / 2.0, &date);
+            }
+            if self.options.page_numbers {
+                let footer = format!("Page {} of {}", number, count);
+                let footer_width = text_width(&footer, style.font, style.size);
+                let top = self.content_height() + self.margin / 2.0;
+                text((width - footer_width) / 2.0, top, &footer);
+            }
+            self.pages[number - 1].content.push_str(&ops);
+        }
+        let report = PdfReport { unsupported: self.unsupported.iter().copied().collect() };
+        (write_pdf(&self, title), report)
+    }
+}
+
+/// Whether `url` has a scheme, like `https:`. Only those links go into the
+/// PDF: relative ones and anchors mean nothing outside the app.
+fn has_scheme(url: &str) -> bool {
+    !url.is_empty() && !url.starts_with(['#', '/', '\\', '?']) && !relink::is_relative(url)
+}
+
+/// The widest run of text `pieces` has without a space in it.
+fn widest_word(pieces: &[Piece]) -> f32 {
+    let mut widest = 0f32;
+    let mut word = 0f32;
+    for piece in pieces {
+        match piece {
+            Piece::Word(..) | Piece::Kern(..) => word += piece.width(),
+            _ => word = 0.0,
+        }
+        widest = widest.max(word);
+    }
+    widest
+}
+
+/// Breaks a code line into rows of at most `columns` characters.
+fn wrap_code_line(line: Vec<(String, Rgb)>, columns: usize) -> Vec<Vec<(String, Rgb)>> {
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    for (text, color) in line {
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            if used == columns {
+                rows.push(Vec::new());
+                used = 0;
+            }
+            let take = rest.char_indices().nth(columns - used).map_or(rest.len(), |(i, _)| i);
+            rows.last_mut().expect("a row").push((rest[..take].to_string(), color));
+            used += rest[..take].chars().count();
+            rest = &rest[take..];
+        }
+    }
+    rows
+}
+
+/// `text`, shortened with an ellipsis to fit `width`.
+fn fit_text(text: &str, width: f32, style: &Style) -> String {
+    if text_width(text, style.font, style.size) <= width {
+        return text.to_string();
+    }
+    let mut fitted = String::new();
+    for c in text.chars() {
+        fitted.push(c);
+        if text_width(&fitted, style.font, style.size) + style.size > width {
+            fitted.pop();
+            break;
+        }
+    }
+    fitted.push('\u{2026}');
+    fitted
+}
+
+/// Today's date in UTC, as `YYYY-MM-DD`.
+fn today() -> String {
+    let (year, month, day, _) = now_utc();
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The UTC date and the seconds into the day.
+fn now_utc() -> (i64, u32, u32, u64) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Howard Hinnant's days-to-civil.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, secs % 86_400)
+}
+
+// ─── PDF Objects ─────────────────────────────────────────────
+
+struct Writer {
+    out: Vec<u8>,
+    /// Byte offset of each object, by number; 0 is the free list head.
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn new() -> Writer {
+        Writer { out: b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec(), offsets: vec![0] }
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len() - 1
+    }
+
+    fn object(&mut self, id: usize, body: &str) {
+        self.offsets[id] = self.out.len();
+        self.out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", id, body).as_bytes());
+    }
+
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        self.offsets[id] = self.out.len();
+        let head = format!("{} 0 obj\n<< {} /Length {} >>\nstream\n", id, dict, data.len());
+        self.out.extend_from_slice(head.as_bytes());
+        self.out.extend_from_slice(data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self, root: usize, info: usize) -> Vec<u8> {
+        let xref = self.out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len());
+        for offset in &self.offsets[1..] {
+            table.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        table.push_str(&format!(
+            "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len(),
+            root,
+            info,
+            xref
+        ));
+        self.out.extend_from_slice(table.as_bytes());
+        self.out
+    }
+}
+
+fn write_pdf(layout: &Layout, title: &str) -> Vec<u8> {
+    let mut pdf = Writer::new();
+    let catalog = pdf.reserve();
+    let pages = pdf.reserve();
+    let info = pdf.reserve();
+
+    let mut fonts = String::new();
+    for font in Font::ALL {
+        let id = pdf.reserve();
+        pdf.object(
+            id,
+            &format!(
+                "<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                font.base_font()
+            ),
+        );
+        fonts.push_str(&format!("/{} {} 0 R ", font.resource(), id));
+    }
+
+    let mut images = String::new();
+    for (index, picture) in layout.pictures.iter().enumerate() {
+        let mask = picture.alpha.as_ref().map(|alpha| {
+            let id = pdf.reserve();
+            let dict = format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray \
+                 /BitsPerComponent 8 /Filter /FlateDecode",
+                picture.width, picture.height
+            );
+            pdf.stream(id, &dict, &deflate(alpha));
+            id
+        });
+        let id = pdf.reserve();
+        let mut dict = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+             /BitsPerComponent 8 /Filter /{}",
+            picture.width, picture.height, picture.color_space, picture.filter
+        );
+        if let Some(mask) = mask {
+            dict.push_str(&format!(" /SMask {} 0 R", mask));
+        }
+        pdf.stream(id, &dict, &picture.data);
+        images.push_str(&format!("/Im{} {} 0 R ", index, id));
+    }
+    let resources = format!("<< /Font << {}>> /XObject << {}>> >>", fonts, images);
+
+    let mut kids = Vec::new();
+    for page in &layout.pages {
+        let contents = pdf.reserve();
+        pdf.stream(contents, "/Filter /FlateDecode", &deflate(page.content.as_bytes()));
+
+        let mut annots = Vec::new();
+        for (rect, link) in &page.links {
+            let id = pdf.reserve();
+            pdf.object(
+                id,
+                &format!(
+                    "<< /Type /Annot /Subtype /Link /Rect [{} {} {} {}] /Border [0 0 0] \
+                     /A << /Type /Action /S /URI /URI {} >> >>",
+                    num(rect[0]),
+                    num(rect[1]),
+                    num(rect[2]),
+                    num(rect[3]),
+                    literal(layout.links[*link].bytes())
+                ),
+            );
+            annots.push(format!("{} 0 R", id));
+        }
+
+        let id = pdf.reserve();
+        pdf.object(
+            id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} \
+                 /Contents {} 0 R /Annots [{}] >>",
+                pages,
+                num(layout.page_width),
+                num(layout.page_height),
+                resources,
+                contents,
+                annots.join(" ")
+            ),
+        );
+        kids.push(format!("{} 0 R", id));
+    }
+
+    pdf.object(
+        pages,
+        &format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), kids.len()),
+    );
+    pdf.object(catalog, &format!("<< /Type /Catalog /Pages {} 0 R >>", pages));
+    let (year, month, day, secs) = now_utc();
+    pdf.object(
+        info,
+        &format!(
+            "<< /Title {} /Producer (Paddown) /CreationDate (D:{:04}{:02}{:02}{:02}{:02}{:02}Z) >>",
+            utf16_string(title),
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        ),
+    );
+    pdf.finish(catalog, info)
+}
+
+/// A PDF of `text`. Images are looked up relative to `base_dir`, the
+/// document's folder; without one they print their alt text.
+pub fn render_pdf(
+    text: &str,
+    title: &str,
+    base_dir: Option<&Path>,
+    options: &PdfOptions,
+) -> (Vec<u8>, PdfReport) {
+    let mut layout = Layout::new(options, base_dir);
+    layout.document(text);
+    layout.finish(title)
+}
+
+/// Writes `markdown` as a PDF to `path`, a file the user picked with
+/// `show_export_pdf_dialog`. The report lists the characters that came
+/// out as `?`.
+#[tauri::command]
+pub async fn export_pdf(
+    app: tauri::AppHandle,
+    path: String,
+    markdown: String,
+    title: String,
+    base_dir: Option<String>,
+    options: PdfOptions,
+) -> error::Result<PdfReport> {
+    tasks::blocking(move || {
+        let path = Path::new(&path);
+        scope::check(&app, path)?;
+        let base_dir = base_dir.map(PathBuf::from);
+        if let Some(dir) = &base_dir {
+            scope::check_dir(&app, dir)?;
+        }
+        let (bytes, report) = render_pdf(&markdown, &title, base_dir.as_deref(), &options);
+        crate::write_file_atomic(path, &bytes)?;
+        Ok(report)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_count(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        text.matches("/Type /Page /Parent").count()
+    }
+
+    fn texts(rows: &[Vec<(String, Rgb)>]) -> Vec<String> {
+        rows.iter().map(|row| row.iter().map(|(text, _)| text.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn code_lines_wrap_by_characters() {
+        let line = vec![
+            ("h\u{e9}llo ".to_string(), TEXT),
+            ("\u{4e16}\u{754c}!".to_string(), DANGER),
+        ];
+        let rows = wrap_code_line(line, 4);
+        assert_eq!(texts(&rows), ["h\u{e9}ll", "o \u{4e16}\u{754c}", "!"]);
+        // Runs keep their colour across the break.
+        assert_eq!(rows[1], [("o ".to_string(), TEXT), ("\u{4e16}\u{754c}".to_string(), DANGER)]);
+        assert_eq!(rows[2], [("!".to_string(), DANGER)]);
+    }
+
+    #[test]
+    fn short_code_lines_stay_whole() {
+        let rows = wrap_code_line(vec![("\u{1f600}ab".to_string(), TEXT)], 3);
+        assert_eq!(texts(&rows), ["\u{1f600}ab"]);
+        assert_eq!(texts(&wrap_code_line(Vec::new(), 3)), [""]);
+    }
+
+    fn jpeg_header() -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // An APP0 segment, then a baseline frame of 3 by 2 RGB pixels.
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]);
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x02, 0x00, 0x03, 0x03]);
+        jpeg.extend_from_slice(&[0; 16]);
+        jpeg
+    }
+
+    #[test]
+    fn jpeg_size_comes_from_the_frame() {
+        let picture = Picture::jpeg(jpeg_header()).expect("a picture");
+        assert_eq!((picture.width, picture.height), (3, 2));
+        assert_eq!(picture.color_space, "DeviceRGB");
+        assert_eq!(picture.filter, "DCTDecode");
+    }
+
+    #[test]
+    fn truncated_jpegs_are_rejected() {
+        let jpeg = jpeg_header();
+        for len in [0, 1, 2, 5, 8, 12, 15] {
+            assert!(Picture::jpeg(jpeg[..len].to_vec()).is_none(), "{} bytes", len);
+        }
+        assert!(Picture::jpeg(b"not a jpeg".to_vec()).is_none());
+    }
+
+    fn png_bytes() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 255, 9, 9, 9, 0];
+        writer.write_image_data(&pixels).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn png_alpha_becomes_a_mask() {
+        let picture = Picture::png(&png_bytes()).expect("a picture");
+        assert_eq!((picture.width, picture.height), (2, 2));
+        assert_eq!(picture.color_space, "DeviceRGB");
+        assert_eq!(picture.alpha, Some(vec![255, 128, 255, 0]));
+    }
+
+    #[test]
+    fn truncated_pngs_are_rejected() {
+        let png = png_bytes();
+        for len in [0, 8, 20, 33, png.len() - 20, png.len() - 12] {
+            assert!(Picture::png(&png[..len]).is_none(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn pdf_smoke_test() {
+        let options = PdfOptions::default();
+        let (pdf, report) = render_pdf("# Title\n\nSome *text*.", "Title", None, &options);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert_eq!(page_count(&pdf), 1);
+        assert!(report.unsupported.is_empty());
+    }
+
+    #[test]
+    fn long_code_blocks_run_onto_more_pages() {
+        let options = PdfOptions::default();
+        let layout = Layout::new(&options, None);
+        let room = layout.content_height() - CODE_PAD_TOP - CODE_PAD;
+        let per_page = (room / (CODE_SIZE * CODE_LINE)).floor() as usize;
+
+        let lines = per_page * 2 + 1;
+        let code: String = (0..lines).map(|i| format!("line {}\n", i)).collect();
+        let (pdf, _) = render_pdf(&format!("```\n{}```\n", code), "Code", None, &options);
+        assert_eq!(page_count(&pdf), 3);
+        let (pdf, _) = render_pdf(&format!("```\n{}```\n", code.repeat(2)), "", None, &options);
+        assert_eq!(page_count(&pdf), 5);
+    }
+
+    #[test]
+    fn characters_without_glyphs_are_reported() {
+        let text = "caf\u{e9} \u{2014} \u{3b1}\u{3b2} ok?\n\n```\n\u{2192} \u{3b1}\n```\n";
+        let options = PdfOptions { header: true, ..Default::default() };
+        let (_, report) = render_pdf(text, "\u{1f4c4} Notes", None, &options);
+        assert_eq!(report.unsupported, ['\u{3b1}', '\u{3b2}', '\u{2192}', '\u{1f4c4}']);
+    }
+}
@@ -0,0 +1,450 @@
+//! Keeping relative Markdown links working after a file or folder moves.
+//!
+//! Runs after the move: `preview_link_rewrites` walks every Markdown file in
+//! the pinned folders and lists the link destinations that need a new URL,
+//! both links pointing into the moved entry and links written inside it.
+//! The frontend shows that list and hands it back to `apply_link_rewrites`,
+//! which re-checks every file against its fingerprint and writes them all
+//! with the same temp-and-rename strategy as `write_file`.
+//!
+//! Destinations come from `markdown::parse`: inline links and images
+//! (`[text](dest)`) and reference definitions (`[id]: dest`), never
+//! anything inside code. Absolute URLs, `/`-rooted paths and same-document
+//! `#anchors` are left alone.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{self, PaddownError};
+use crate::{history, markdown, scope, tasks, workspace, Fingerprint};
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct LinkEdit {
+    /// 1-based line of the link, for display.
+    line: usize,
+    /// `[start, end)` UTF-8 byte offsets of the destination in the file.
+    start: usize,
+    end: usize,
+    old_url: String,
+    new_url: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct FileRewrite {
+    path: String,
+    /// The state the edits were computed against.
+    fingerprint: Fingerprint,
+    edits: Vec<LinkEdit>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct RewrittenFile {
+    path: String,
+    fingerprint: Fingerprint,
+}
+
+// ─── URL handling ────────────────────────────────────────────
+
+/// Relative to the document: no scheme, not rooted, not just a fragment.
+pub(crate) fn is_relative(url: &str) -> bool {
+    if url.is_empty() || url.starts_with(['#', '/', '\\', '?']) {
+        return false;
+    }
+    let scheme_len = url
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "+.-".contains(c)))
+        .unwrap_or(url.len());
+    let has_scheme = scheme_len > 0
+        && url.as_bytes()[0].is_ascii_alphabetic()
+        && url[scheme_len..].starts_with(':');
+    !has_scheme
+}
+
+/// Splits `notes.md#intro` into `notes.md` and `#intro`.
+pub(crate) fn split_suffix(url: &str) -> (&str, &str) {
+    match url.find(['#', '?']) {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    }
+}
+
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |b: u8| (b as char).to_digit(16);
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Escapes what would break or change the meaning of a bare destination.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' => out.push_str("%20"),
+            '%' => out.push_str("%25"),
+            '(' => out.push_str("%28"),
+            ')' => out.push_str("%29"),
+            '<' => out.push_str("%3C"),
+            '>' => out.push_str("%3E"),
+            '#' => out.push_str("%23"),
+            '?' => out.push_str("%3F"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Resolves `.` and `..` without touching the filesystem; the paths
+/// involved may no longer exist.
+pub(crate) fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(out.components().next_back(), Some(Component::Normal(_))) {
+                    out.push("..");
+                } else {
+                    out.pop();
+                }
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// The `/`-separated URL that reaches `to` from the directory `from`.
+/// `None` when they share no root, e.g. different Windows drives.
+fn relative_url(from: &Path, to: &Path) -> Option<String> {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    if from.first() != to.first() {
+        return None;
+    }
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().into_owned()));
+    if parts.is_empty() {
+        return Some(".".to_string());
+    }
+    Some(parts.join("/"))
+}
+
+/// Where `path` now lives, given that `old` moved to `new`.
+fn map_moved(path: &Path, old: &Path, new: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(old).ok()?;
+    Some(if rest.as_os_str().is_empty() { new.to_path_buf() } else { new.join(rest) })
+}
+
+// ─── Preview ─────────────────────────────────────────────────
+
+/// The new URL for one link in a document that was at `old_doc` and is now
+/// at `new_doc`, or `None` if the link still resolves correctly.
+fn rewrite_url(
+    url: &str,
+    angle: bool,
+    old_doc: &Path,
+    new_doc: &Path,
+    old: &Path,
+    new: &Path,
+) -> Option<String> {
+    if !is_relative(url) {
+        return None;
+    }
+    let (target, suffix) = split_suffix(url);
+    if target.is_empty() {
+        return None;
+    }
+    let decoded = percent_decode(target);
+    let (old_dir, new_dir) = (old_doc.parent()?, new_doc.parent()?);
+
+    let old_target = lexical_normalize(&old_dir.join(&decoded));
+    let new_target = map_moved(&old_target, old, new).unwrap_or(old_target);
+    if lexical_normalize(&new_dir.join(&decoded)) == new_target {
+        return None;
+    }
+
+    let mut rel = relative_url(new_dir, &new_target)?;
+    if decoded.ends_with('/') && !rel.ends_with('/') {
+        rel.push('/');
+    }
+    let rel = if angle { rel } else { percent_encode(&rel) };
+    Some(format!("{}{}", rel, suffix))
+}
+
+fn preview_file(path: &Path, old: &Path, new: &Path) -> error::Result<Option<FileRewrite>> {
+    let file = crate::read_text_file(path)?;
+    let old_doc = map_moved(path, new, old).unwrap_or_else(|| path.to_path_buf());
+
+    let edits: Vec<LinkEdit> = markdown::parse(&file.contents)
+        .destinations()
+        .into_iter()
+        .filter_map(|dest| {
+            let old_url = &file.contents[dest.start..dest.end];
+            let new_url = rewrite_url(old_url, dest.angle, &old_doc, path, old, new)?;
+            Some(LinkEdit {
+                line: file.contents[..dest.start].matches('\n').count() + 1,
+                start: dest.start,
+                end: dest.end,
+                old_url: old_url.to_string(),
+                new_url,
+            })
+        })
+        .collect();
+
+    Ok((!edits.is_empty()).then(|| FileRewrite {
+        path: path.to_string_lossy().into_owned(),
+        fingerprint: file.fingerprint,
+        edits,
+    }))
+}
+
+fn preview(
+    app: &tauri::AppHandle,
+    old_path: &Path,
+    new_path: &Path,
+    token: &tasks::CancelToken,
+) -> error::Result<Vec<FileRewrite>> {
+    scope::check_pinned_entry(app, new_path)?;
+    let new = std::fs::canonicalize(new_path).map_err(|e| PaddownError::io(new_path, e))?;
+    // The old path is gone; resolve what's left of it.
+    let old = old_path.parent()
+        .and_then(|p| std::fs::canonicalize(p).ok())
+        .zip(old_path.file_name())
+        .map(|(parent, name)| parent.join(name))
+        .unwrap_or_else(|| lexical_normalize(old_path));
+    if old == new {
+        return Ok(Vec::new());
+    }
+
+    // Pinned folders may be nested; visit each file once.
+    let files: BTreeSet<PathBuf> = scope::pinned_folders(app)?
+        .iter()
+        .flat_map(|root| workspace::markdown_files(root, token))
+        .collect();
+
+    let mut rewrites = Vec::new();
+    for file in files {
+        if token.is_cancelled() {
+            return Err(PaddownError::Cancelled);
+        }
+        // Unreadable files (too large, not UTF-8) can't be rewritten anyway.
+        if let Ok(Some(rewrite)) = preview_file(&file, &old, &new) {
+            rewrites.push(rewrite);
+        }
+    }
+    Ok(rewrites)
+}
+
+/// Lists the link edits needed now that `old_path` has moved to `new_path`.
+#[tauri::command]
+pub async fn preview_link_rewrites(
+    app: tauri::AppHandle,
+    old_path: String,
+    new_path: String,
+    task_id: Option<String>,
+) -> error::Result<Vec<FileRewrite>> {
+    let task = tasks::register(&app, task_id);
+    let token = task.token();
+    tasks::blocking(move || preview(&app, Path::new(&old_path), Path::new(&new_path), &token))
+        .await?
+}
+
+// ─── Apply ───────────────────────────────────────────────────
+
+struct Pending {
+    path: PathBuf,
+    staged: crate::StagedFile,
+    previous: Vec<u8>,
+    contents: String,
+}
+
+/// Stages every file before renaming any, so a conflict or write error in
+/// one leaves them all untouched. Each rename is atomic on its own, but the
+/// set isn't: if a rename fails partway the earlier files keep their new
+/// links and the error is returned.
+fn apply(app: &tauri::AppHandle, files: Vec<FileRewrite>) -> error::Result<Vec<RewrittenFile>> {
+    let mut pending: Vec<Pending> = Vec::new();
+    let discard_all = |pending: Vec<Pending>| pending.into_iter().for_each(|p| p.staged.discard());
+
+    for file in files {
+        let path = PathBuf::from(&file.path);
+        let staged = (|| {
+            scope::check(app, &path)?;
+            let current = crate::read_text_file(&path)?;
+            if current.fingerprint.hash != file.fingerprint.hash {
+                return Err(PaddownError::Conflict { path: file.path.clone() });
+            }
+            let splices = file.edits.iter()
+                .map(|e| crate::Splice {
+                    start: e.start,
+                    end: e.end,
+                    expected: &e.old_url,
+                    replacement: &e.new_url,
+                })
+                .collect();
+            let contents = crate::apply_splices(&path, &current.contents, splices)?;
+            let staged = crate::stage_text_file(&path, &contents)?;
+            Ok(Pending {
+                path: path.clone(),
+                staged,
+                previous: current.contents.into_bytes(),
+                contents,
+            })
+        })();
+        match staged {
+            Ok(p) => pending.push(p),
+            Err(e) => {
+                discard_all(pending);
+                return Err(e);
+            }
+        }
+    }
+
+    let mut written = Vec::new();
+    let mut pending = pending.into_iter();
+    for p in pending.by_ref() {
+        if let Err(e) = p.staged.commit() {
+            discard_all(pending.collect());
+            return Err(e);
+        }
+        let _ = history::record(app, &p.path, Some(&p.previous), p.contents.as_bytes());
+        let metadata = std::fs::metadata(&p.path).map_err(|e| PaddownError::io(&p.path, e))?;
+        written.push(RewrittenFile {
+            path: p.path.to_string_lossy().into_owned(),
+            fingerprint: Fingerprint::new(&metadata, p.contents.as_bytes()),
+        });
+    }
+    Ok(written)
+}
+
+/// Applies edits from `preview_link_rewrites`. Fails with `Conflict`,
+/// writing nothing, if any file changed since the preview.
+#[tauri::command]
+pub async fn apply_link_rewrites(
+    app: tauri::AppHandle,
+    files: Vec<FileRewrite>,
+) -> error::Result<Vec<RewrittenFile>> {
+    tasks::blocking(move || apply(&app, files)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_relative_urls_are_rewritten() {
+        assert!(is_relative("notes.md"));
+        assert!(is_relative("../img/a b.png"));
+        assert!(is_relative("C++ notes.md"));
+        assert!(!is_relative("https://example.com"));
+        assert!(!is_relative("mailto:me@example.com"));
+        assert!(!is_relative("#intro"));
+        assert!(!is_relative("/rooted.md"));
+        assert!(!is_relative(""));
+    }
+
+    #[test]
+    fn suffixes_split_at_the_first_fragment_or_query() {
+        assert_eq!(split_suffix("notes.md#intro"), ("notes.md", "#intro"));
+        assert_eq!(split_suffix("page.html?a=1#b"), ("page.html", "?a=1#b"));
+        assert_eq!(split_suffix("notes.md"), ("notes.md", ""));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b.md"), "a b.md");
+        assert_eq!(percent_decode("caf%C3%A9"), "caf\u{E9}");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+        assert_eq!(percent_decode("%41%42"), "AB");
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        let name = "my (draft) 100% #1?.md";
+        let encoded = percent_encode(name);
+        assert_eq!(encoded, "my%20%28draft%29%20100%25%20%231%3F.md");
+        assert_eq!(percent_decode(&encoded), name);
+    }
+
+    #[test]
+    fn lexical_normalisation() {
+        assert_eq!(lexical_normalize(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(lexical_normalize(Path::new("../a/../../b")), Path::new("../../b"));
+    }
+
+    #[test]
+    fn relative_urls_between_folders() {
+        let url = |from: &str, to: &str| relative_url(Path::new(from), Path::new(to));
+        assert_eq!(url("/n/a", "/n/b/c.md").as_deref(), Some("../b/c.md"));
+        assert_eq!(url("/n", "/n/c.md").as_deref(), Some("c.md"));
+        assert_eq!(url("/n/a", "/n/a").as_deref(), Some("."));
+    }
+
+    fn rewrite(url: &str, doc: (&str, &str), moved: (&str, &str)) -> Option<String> {
+        let (old_doc, new_doc) = (Path::new(doc.0), Path::new(doc.1));
+        let (old, new) = (Path::new(moved.0), Path::new(moved.1));
+        rewrite_url(url, false, old_doc, new_doc, old, new)
+    }
+
+    #[test]
+    fn links_into_a_moved_entry_follow_it() {
+        let doc = ("/n/index.md", "/n/index.md");
+        let moved = ("/n/old", "/n/sub/new");
+        assert_eq!(rewrite("old/a.md#top", doc, moved).as_deref(), Some("sub/new/a.md#top"));
+        assert_eq!(rewrite("old/", doc, moved).as_deref(), Some("sub/new/"));
+        assert_eq!(rewrite("other.md", doc, moved), None);
+        assert_eq!(rewrite("#top", doc, moved), None);
+        assert_eq!(rewrite("https://x.org/old/a.md", doc, moved), None);
+    }
+
+    #[test]
+    fn links_inside_a_moved_document_are_rebased() {
+        let doc = ("/n/a/doc.md", "/n/b/c/doc.md");
+        let moved = ("/n/a/doc.md", "/n/b/c/doc.md");
+        assert_eq!(rewrite("img/x%20y.png", doc, moved).as_deref(), Some("../../a/img/x%20y.png"));
+        assert_eq!(rewrite("../b/c/sibling.md", doc, moved).as_deref(), Some("sibling.md"));
+    }
+
+    #[test]
+    fn angle_destinations_stay_unencoded() {
+        let url = rewrite_url(
+            "old/a b.md",
+            true,
+            Path::new("/n/doc.md"),
+            Path::new("/n/doc.md"),
+            Path::new("/n/old"),
+            Path::new("/n/new dir"),
+        );
+        assert_eq!(url.as_deref(), Some("new dir/a b.md"));
+    }
+
+    #[test]
+    fn preview_lists_edits_with_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let text = "# Notes\n\nSee [a](old/a.md) and `[b](old/b.md)`.\n\n[ref]: old/c.md\n";
+        let doc = root.join("index.md");
+        std::fs::write(&doc, text).unwrap();
+        std::fs::create_dir(root.join("new")).unwrap();
+
+        let rewrite = preview_file(&doc, &root.join("old"), &root.join("new")).unwrap().unwrap();
+        let edits: Vec<_> = rewrite.edits.iter()
+            .map(|e| (e.line, &text[e.start..e.end], e.new_url.as_str()))
+            .collect();
+        assert_eq!(edits, [(3, "old/a.md", "new/a.md"), (5, "old/c.md", "new/c.md")]);
+    }
+}
@@ -0,0 +1,477 @@
+//! Markdown to HTML without a webview.
+//!
+//! Produces the markup the preview builds with marked.js and
+//! `renderer.js`, so `markdown.css` styles it the same way: code blocks in
+//! a `code-block-wrapper` with a language label and `highlight` tokens,
+//! tables in a `table-wrapper`, task items as `task-list-item`s with
+//! disabled checkboxes, and headings with the ids `markdown` gives them.
+//! Parsing uses `markdown::options`, so footnotes render too, which the
+//! preview doesn't support.
+//!
+//! `render_page` wraps the result in a standalone document with the app's
+//! stylesheets inlined, like the HTML export. The `_with` variants also
+//! rewrite image URLs, which `bundle` uses to make pages self-contained.
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Parser, Tag, TagEnd};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+use crate::error;
+use crate::{highlight, markdown, tasks};
+
+const TOKENS_CSS: &str = include_str!("../../src/css/tokens.css");
+const MARKDOWN_CSS: &str = include_str!("../../src/css/markdown.css");
+
+/// Escapes text for HTML content and attribute values, as `utils.escapeHtml`.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn code_block(code: &str, info: &str) -> String {
+    let lang = info.split_whitespace().next().unwrap_or("").to_lowercase();
+    let label = if lang.is_empty() {
+        String::new()
+    } else {
+        format!("<div class=\"code-lang-label\">{}</div>", escape_html(&lang))
+    };
+    // marked leaves off the block's final newline.
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    format!(
+        "<div class=\"code-block-wrapper\">{}<pre><code>{}</code></pre></div>\n",
+        label,
+        highlight::highlight(code, &lang)
+    )
+}
+
+/// Maps an image URL to the one to write instead, or `None` to keep it.
+pub type ImageMap<'a> = dyn FnMut(&str) -> Option<String> + 'a;
+
+/// Replaces the `src` of the `<img>` tags in raw HTML through `images`.
+fn map_img_tags(html: &str, images: &mut ImageMap) -> String {
+    static IMG_SRC: OnceLock<Regex> = OnceLock::new();
+    let pattern = IMG_SRC.get_or_init(|| {
+        let pattern = r#"(?i)(<img\b[^>]*?\ssrc\s*=\s*)(?:"([^"]*)"|'([^']*)')"#;
+        Regex::new(pattern).expect("img pattern")
+    });
+    pattern
+        .replace_all(html, |caps: &Captures| {
+            let url = caps.get(2).or_else(|| caps.get(3)).map_or("", |m| m.as_str());
+            match images(url) {
+                Some(src) => format!("{}\"{}\"", &caps[1], escape_html(&src)),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// The preview's markup for `events`, still to be written out as HTML.
+fn restyle<'a>(
+    parser: impl Iterator<Item = Event<'a>>,
+    images: &mut ImageMap,
+) -> Vec<Event<'a>> {
+    let mut out: Vec<Event> = Vec::new();
+    let mut slugger = markdown::Slugger::default();
+    // Indexes into `out` of the open lists and items, so a task marker can
+    // restyle the item and list it belongs to.
+    let mut lists: Vec<usize> = Vec::new();
+    let mut items: Vec<usize> = Vec::new();
+    let mut code: Option<(String, String)> = None;
+    let mut heading: Option<(usize, String)> = None;
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((info, String::new()));
+            }
+            Event::Text(text) if code.is_some() => {
+                if let Some((_, body)) = code.as_mut() {
+                    body.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((info, body)) = code.take() {
+                    out.push(Event::Html(CowStr::from(code_block(&body, &info))));
+                }
+            }
+            Event::Start(Tag::Table(_)) => {
+                out.push(Event::Html(CowStr::Borrowed("<div class=\"table-wrapper\">")));
+                out.push(event);
+            }
+            Event::End(TagEnd::Table) => {
+                out.push(event);
+                out.push(Event::Html(CowStr::Borrowed("</div>\n")));
+            }
+            Event::Start(Tag::List(_)) => {
+                lists.push(out.len());
+                out.push(event);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                out.push(event);
+            }
+            Event::Start(Tag::Item) => {
+                items.push(out.len());
+                out.push(event);
+            }
+            Event::End(TagEnd::Item) => {
+                items.pop();
+                out.push(event);
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(&item) = items.last() {
+                    out[item] = Event::Html(CowStr::Borrowed("<li class=\"task-list-item\">"));
+                }
+                if let Some(&list) = lists.last() {
+                    if matches!(out[list], Event::Start(Tag::List(None))) {
+                        let ul = "<ul class=\"contains-task-list\">\n";
+                        out[list] = Event::Html(CowStr::Borrowed(ul));
+                    }
+                }
+                let checkbox = if checked {
+                    "<input type=\"checkbox\" disabled checked=\"\"> "
+                } else {
+                    "<input type=\"checkbox\" disabled> "
+                };
+                out.push(Event::Html(CowStr::Borrowed(checkbox)));
+            }
+            Event::Start(Tag::Heading { .. }) => {
+                heading = Some((out.len(), String::new()));
+                out.push(event);
+            }
+            Event::Text(ref text) | Event::Code(ref text) if heading.is_some() => {
+                if let Some((_, plain)) = heading.as_mut() {
+                    plain.push_str(text);
+                }
+                out.push(event);
+            }
+            Event::SoftBreak | Event::HardBreak if heading.is_some() => {
+                if let Some((_, plain)) = heading.as_mut() {
+                    plain.push(' ');
+                }
+                out.push(event);
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((start, plain)) = heading.take() {
+                    if let Event::Start(Tag::Heading { id, .. }) = &mut out[start] {
+                        *id = Some(CowStr::from(slugger.slug(plain.trim())));
+                    }
+                }
+                out.push(event);
+            }
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+                let dest_url = images(&dest_url).map_or(dest_url, CowStr::from);
+                out.push(Event::Start(Tag::Image { link_type, dest_url, title, id }));
+            }
+            Event::Html(html) => out.push(Event::Html(CowStr::from(map_img_tags(&html, images)))),
+            Event::InlineHtml(html) => {
+                out.push(Event::InlineHtml(CowStr::from(map_img_tags(&html, images))));
+            }
+            _ => out.push(event),
+        }
+    }
+    out
+}
+
+/// The body HTML for `text`, as the preview would show it.
+pub fn render_html(text: &str) -> String {
+    render_html_with(text, &mut |_| None)
+}
+
+/// `render_html`, with image URLs replaced through `images`.
+pub fn render_html_with(text: &str, images: &mut ImageMap) -> String {
+    let events = restyle(Parser::new_ext(text, markdown::options()), images);
+    let mut html = String::with_capacity(text.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    html
+}
+
+/// Makes the text written so far end with at least `count` newlines,
+/// unless it is still empty.
+fn end_lines(out: &mut String, count: usize) {
+    if out.is_empty() {
+        return;
+    }
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+    let ends = out.len() - out.trim_end_matches('\n').len();
+    out.extend(std::iter::repeat_n('\n', count.saturating_sub(ends)));
+}
+
+/// Plain text for `text`: the words without their Markdown syntax, blocks
+/// separated by blank lines, list items on their own lines with `-` or
+/// their number, and link URLs after their text.
+pub fn render_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    // The next number of each open list, `None` for bullets.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    // Where each open link's text starts, and its URL.
+    let mut links: Vec<(usize, String)> = Vec::new();
+    let mut item_start = false;
+    let mut cells = 0;
+    let mut skip = false;
+
+    for event in Parser::new_ext(text, markdown::options()) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => skip = true,
+            Event::End(TagEnd::MetadataBlock(_)) => skip = false,
+            _ if skip => {}
+            Event::Start(Tag::Paragraph) if item_start => {}
+            Event::Start(
+                Tag::Paragraph
+                | Tag::Heading { .. }
+                | Tag::CodeBlock(_)
+                | Tag::BlockQuote(_)
+                | Tag::Table(_)
+                | Tag::HtmlBlock,
+            ) => end_lines(&mut out, 2),
+            Event::Start(Tag::List(first)) => {
+                end_lines(&mut out, if lists.is_empty() { 2 } else { 1 });
+                lists.push(first);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                end_lines(&mut out, 1);
+            }
+            Event::Start(Tag::Item) => {
+                end_lines(&mut out, 1);
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        out.push_str(&format!("{}. ", number));
+                        *number += 1;
+                    }
+                    _ => out.push_str("- "),
+                }
+                item_start = true;
+                continue;
+            }
+            Event::TaskListMarker(checked) => {
+                out.push_str(if checked { "[x] " } else { "[ ] " });
+                continue;
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                end_lines(&mut out, 2);
+                out.push_str(&format!("[{}]: ", label));
+                item_start = true;
+                continue;
+            }
+            Event::FootnoteReference(label) => out.push_str(&format!("[{}]", label)),
+            Event::Start(Tag::TableHead | Tag::TableRow) => {
+                end_lines(&mut out, 1);
+                cells = 0;
+            }
+            Event::Start(Tag::TableCell) => {
+                if cells > 0 {
+                    out.push('\t');
+                }
+                cells += 1;
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                links.push((out.len(), dest_url.to_string()));
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((start, url)) = links.pop() {
+                    let shown = out[start..].trim();
+                    if !url.is_empty() && !url.starts_with('#') && shown != url {
+                        out.push_str(&format!(" ({})", url));
+                    }
+                }
+            }
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::Rule => {
+                end_lines(&mut out, 2);
+                out.push_str("---");
+            }
+            _ => {}
+        }
+        item_start = false;
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push('\n');
+    out
+}
+
+/// A standalone HTML document for `text`, styled like the preview.
+pub fn render_page(text: &str, title: &str) -> String {
+    render_page_with(text, title, &mut |_| None)
+}
+
+/// `render_page`, with image URLs replaced through `images`.
+pub fn render_page_with(text: &str, title: &str, images: &mut ImageMap) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1.0">
+  <title>{title}</title>
+  <style>
+{tokens}
+{markdown}
+body {{
+  font-family: "Segoe UI", system-ui, -apple-system, Helvetica, Arial, sans-serif;
+  color: #14181f;
+  background: #ffffff;
+  margin: 0;
+  padding: 2rem;
+  display: flex;
+  justify-content: center;
+}}
+.standard-markdown {{
+  max-width: 680px;
+  width: 100%;
+}}
+  </style>
+</head>
+<body>
+  <div class="standard-markdown">
+{body}
+  </div>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        tokens = TOKENS_CSS,
+        markdown = MARKDOWN_CSS,
+        body = render_html_with(text, images),
+    )
+}
+
+/// Renders Markdown to HTML: the body alone, or a standalone page when
+/// `title` is given.
+#[tauri::command]
+pub async fn render_markdown(
+    markdown: String,
+    title: Option<String>,
+) -> error::Result<String> {
+    tasks::blocking(move || match title {
+        Some(title) => render_page(&markdown, &title),
+        None => render_html(&markdown),
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_are_wrapped() {
+        let html = render_html("| a | b |\n|---|---|\n| 1 | 2 |\n");
+        let start = html.find("<div class=\"table-wrapper\"><table>").expect("wrapper");
+        let end = html.find("</table>\n</div>").expect("wrapper end");
+        assert!(start < end);
+    }
+
+    #[test]
+    fn task_items_get_disabled_checkboxes() {
+        let html = render_html("- [ ] todo\n- [x] done\n");
+        assert!(html.contains("<ul class=\"contains-task-list\">"));
+        assert!(html.contains(
+            "<li class=\"task-list-item\"><input type=\"checkbox\" disabled> todo</li>"
+        ));
+        assert!(html.contains(
+            "<li class=\"task-list-item\"><input type=\"checkbox\" disabled checked=\"\"> done</li>"
+        ));
+    }
+
+    #[test]
+    fn plain_lists_are_untouched() {
+        let html = render_html("- one\n- two\n");
+        assert!(html.contains("<ul>\n<li>one</li>"));
+        assert!(!html.contains("task-list"));
+    }
+
+    #[test]
+    fn code_blocks_get_label_and_tokens() {
+        let html = render_html("```JS extra words\nlet x = 1; // note\n```\n");
+        assert!(html.contains("<div class=\"code-block-wrapper\">"));
+        assert!(html.contains("<div class=\"code-lang-label\">js</div>"));
+        assert!(html.contains("<span class=\"tok-keyword\">let</span>"));
+        assert!(html.contains("<span class=\"tok-comment\">// note</span>"));
+        // The block's last newline is dropped, as marked does.
+        assert!(html.contains("</span></code></pre></div>"));
+    }
+
+    #[test]
+    fn unlabelled_code_is_escaped_only() {
+        let html = render_html("    <b>\n");
+        assert!(!html.contains("code-lang-label"));
+        assert!(html.contains("<pre><code>&lt;b&gt;</code></pre>"));
+    }
+
+    #[test]
+    fn strikethrough_and_footnotes() {
+        let html = render_html("~~gone~~ text[^1]\n\n[^1]: The note.\n");
+        assert!(html.contains("<del>gone</del>"));
+        assert!(html.contains("href=\"#1\""));
+        assert!(html.contains("class=\"footnote-definition\" id=\"1\""));
+        assert!(html.contains("The note."));
+    }
+
+    #[test]
+    fn heading_ids_match_slugger() {
+        let text = "# Hello, World!\n\n## Hello, World!\n\n### `code` *and* text\n";
+        let html = render_html(text);
+        let mut slugger = markdown::Slugger::default();
+        for plain in ["Hello, World!", "Hello, World!", "code and text"] {
+            let id = format!("id=\"{}\"", slugger.slug(plain));
+            assert!(html.contains(&id), "{} in {}", id, html);
+        }
+        assert!(html.contains("id=\"hello-world-1\""));
+    }
+
+    fn upper(url: &str) -> Option<String> {
+        (!url.starts_with("http")).then(|| url.to_uppercase())
+    }
+
+    #[test]
+    fn img_tags_are_mapped_in_any_quoting() {
+        let html = r#"<img src="a.png"> <img alt='x' src='b "q".png'> <IMG SRC = "c.png">"#;
+        assert_eq!(
+            map_img_tags(html, &mut upper),
+            r#"<img src="A.PNG"> <img alt='x' src="B &quot;Q&quot;.PNG"> <IMG SRC = "C.PNG">"#
+        );
+    }
+
+    #[test]
+    fn other_tags_and_kept_urls_are_untouched() {
+        let html = r#"<img src="http://x/a.png"> <script src="a.js"></script> <imgx src="a">"#;
+        assert_eq!(map_img_tags(html, &mut upper), html);
+        let html = r#"<img data-src="a.png" src="b.png">"#;
+        assert_eq!(map_img_tags(html, &mut upper), r#"<img data-src="a.png" src="B.PNG">"#);
+    }
+
+    #[test]
+    fn images_are_mapped_in_markdown_and_html() {
+        let text = "![a](one.png)\n\n<p><img src='two.png'></p>\n\ntext <img src=\"three.png\">\n";
+        let html = render_html_with(text, &mut upper);
+        assert!(html.contains("<img src=\"ONE.PNG\" alt=\"a\" />"));
+        assert!(html.contains("<img src=\"TWO.PNG\">"));
+        assert!(html.contains("<img src=\"THREE.PNG\">"));
+    }
+
+    #[test]
+    fn pages_inline_the_stylesheets() {
+        let page = render_page("# Hi", "A <title>");
+        assert!(page.contains("<title>A &lt;title&gt;</title>"));
+        assert!(page.contains("<h1 id=\"hi\">Hi</h1>"));
+    }
+}
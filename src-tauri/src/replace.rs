@@ -0,0 +1,308 @@
+//! Project-wide find and replace.
+//!
+//! `preview_replace` runs a search query (the same options and file filters
+//! as `search_workspace`) over whole files, so a regex may span lines, and
+//! lists every match with its replacement and the fingerprint of the file
+//! it came from. The frontend sends back the matches the user kept ticked;
+//! `apply_replace` writes each file through `save_contents`, which refuses
+//! files that changed since the preview. One file failing doesn't stop the
+//! others, and the report says exactly what was changed and what was
+//! skipped.
+
+use regex::Regex;
+use std::path::Path;
+
+use crate::error::{self, PaddownError};
+use crate::search::{self, SearchQuery};
+use crate::{tasks, Fingerprint};
+
+/// Matches to preview before stopping; the preview says when it was hit.
+const MAX_MATCHES: usize = 10_000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ReplaceEdit {
+    /// `[start, end)` UTF-8 byte offsets of the match in the file.
+    start: usize,
+    end: usize,
+    old_text: String,
+    new_text: String,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct PreviewMatch {
+    #[serde(flatten)]
+    edit: ReplaceEdit,
+    /// 1-based line of the start of the match.
+    line: usize,
+    /// The whole line the match starts on, for display.
+    line_text: String,
+    /// `[start, end)` UTF-16 range of the match within `line_text`, cut off
+    /// at the end of the line for matches that span lines.
+    range: [usize; 2],
+}
+
+#[derive(serde::Serialize, Debug)]
+struct PreviewFile {
+    path: String,
+    fingerprint: Fingerprint,
+    matches: Vec<PreviewMatch>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ReplacePreview {
+    files: Vec<PreviewFile>,
+    /// The preview stopped early at `MAX_MATCHES`.
+    truncated: bool,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ReplaceFile {
+    path: String,
+    /// The fingerprint the preview reported for this file.
+    fingerprint: Fingerprint,
+    /// The matches to replace; unticked ones are simply left out.
+    edits: Vec<ReplaceEdit>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct ChangedFile {
+    path: String,
+    replacements: usize,
+    fingerprint: Fingerprint,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SkippedFile {
+    path: String,
+    error: PaddownError,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct ReplaceReport {
+    changed: Vec<ChangedFile>,
+    skipped: Vec<SkippedFile>,
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// The replacement for one match: `$1`, `${name}` and `$$` expand in regex
+/// mode, while a literal replacement is used exactly as typed.
+fn replacement_for(caps: &regex::Captures, replacement: &str, expand: bool) -> String {
+    if !expand {
+        return replacement.to_string();
+    }
+    let mut out = String::new();
+    caps.expand(replacement, &mut out);
+    out
+}
+
+fn preview_file(
+    matcher: &Regex,
+    path: &Path,
+    replacement: &str,
+    expand: bool,
+    limit: usize,
+) -> Option<PreviewFile> {
+    // Files too large to open or not valid UTF-8 are skipped, as in search.
+    let file = crate::read_text_file(path).ok()?;
+    let contents = &file.contents;
+
+    let mut matches = Vec::new();
+    for caps in matcher.captures_iter(contents).take(limit) {
+        let Some(m) = caps.get(0) else { continue };
+        if m.is_empty() {
+            continue;
+        }
+        let line_start = contents[..m.start()].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = contents[m.start()..].find('\n').map_or(contents.len(), |i| m.start() + i);
+        let line_text = contents[line_start..line_end].trim_end_matches('\r');
+        let start16 = utf16_len(&contents[line_start..m.start()]);
+        let end16 = start16 + utf16_len(&contents[m.start()..m.end().min(line_end)]);
+        matches.push(PreviewMatch {
+            edit: ReplaceEdit {
+                start: m.start(),
+                end: m.end(),
+                old_text: m.as_str().to_string(),
+                new_text: replacement_for(&caps, replacement, expand),
+            },
+            line: contents[..m.start()].matches('\n').count() + 1,
+            line_text: line_text.to_string(),
+            range: [start16, end16.min(utf16_len(line_text))],
+        });
+    }
+    (!matches.is_empty()).then(|| PreviewFile {
+        path: path.to_string_lossy().into_owned(),
+        fingerprint: file.fingerprint,
+        matches,
+    })
+}
+
+fn preview(
+    app: &tauri::AppHandle,
+    query: &SearchQuery,
+    replacement: &str,
+    token: &tasks::CancelToken,
+) -> error::Result<ReplacePreview> {
+    let matcher = search::build_matcher(query)?;
+    let mut files = Vec::new();
+    let mut total = 0;
+    search::for_each_file(app, query, token, &mut |path| {
+        let limit = MAX_MATCHES - total;
+        if let Some(file) = preview_file(&matcher, path, replacement, query.regex, limit) {
+            total += file.matches.len();
+            files.push(file);
+        }
+        total < MAX_MATCHES
+    })?;
+    Ok(ReplacePreview { files, truncated: total >= MAX_MATCHES })
+}
+
+/// Lists what replacing `query` with `replacement` would change.
+#[tauri::command]
+pub async fn preview_replace(
+    app: tauri::AppHandle,
+    query: SearchQuery,
+    replacement: String,
+    task_id: Option<String>,
+) -> error::Result<ReplacePreview> {
+    let task = tasks::register(&app, task_id);
+    let token = task.token();
+    tasks::blocking(move || preview(&app, &query, &replacement, &token)).await?
+}
+
+fn replace_in_file(app: &tauri::AppHandle, file: &ReplaceFile) -> error::Result<Fingerprint> {
+    let path = Path::new(&file.path);
+    crate::scope::check(app, path)?;
+    let current = crate::read_text_file(path)?;
+    if current.fingerprint.hash != file.fingerprint.hash {
+        return Err(PaddownError::Conflict { path: file.path.clone() });
+    }
+    let splices = file.edits.iter()
+        .map(|e| crate::Splice {
+            start: e.start,
+            end: e.end,
+            expected: &e.old_text,
+            replacement: &e.new_text,
+        })
+        .collect();
+    let contents = crate::apply_splices(path, &current.contents, splices)?;
+    // Checks the fingerprint again right before the write.
+    crate::save_contents(app, path, &contents, Some(&current.fingerprint))
+}
+
+fn apply(app: &tauri::AppHandle, files: Vec<ReplaceFile>) -> ReplaceReport {
+    let mut report = ReplaceReport { changed: Vec::new(), skipped: Vec::new() };
+    for file in files.into_iter().filter(|f| !f.edits.is_empty()) {
+        match replace_in_file(app, &file) {
+            Ok(fingerprint) => report.changed.push(ChangedFile {
+                replacements: file.edits.len(),
+                path: file.path,
+                fingerprint,
+            }),
+            Err(error) => report.skipped.push(SkippedFile { path: file.path, error }),
+        }
+    }
+    report
+}
+
+/// Applies the ticked matches from `preview_replace`. Each file is written
+/// atomically; files that changed since the preview are skipped.
+#[tauri::command]
+pub async fn apply_replace(
+    app: tauri::AppHandle,
+    files: Vec<ReplaceFile>,
+) -> error::Result<ReplaceReport> {
+    tasks::blocking(move || apply(&app, files)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Splice;
+
+    fn splice<'a>(start: usize, end: usize, expected: &'a str, replacement: &'a str) -> Splice<'a> {
+        Splice { start, end, expected, replacement }
+    }
+
+    #[test]
+    fn splices_apply_in_offset_order() {
+        let path = Path::new("notes.md");
+        let splices = vec![splice(10, 13, "fox", "cat"), splice(4, 9, "quick", "slow")];
+        let out = crate::apply_splices(path, "the quick fox", splices).unwrap();
+        assert_eq!(out, "the slow cat");
+    }
+
+    #[test]
+    fn stale_or_overlapping_splices_conflict() {
+        let path = Path::new("notes.md");
+        let stale = vec![splice(4, 9, "quick", "slow")];
+        let result = crate::apply_splices(path, "the QUICK fox", stale);
+        assert!(matches!(result, Err(PaddownError::Conflict { .. })));
+
+        let overlapping = vec![splice(4, 9, "quick", "a"), splice(8, 13, "k fox", "b")];
+        let result = crate::apply_splices(path, "the quick fox", overlapping);
+        assert!(matches!(result, Err(PaddownError::Conflict { .. })));
+
+        let out_of_bounds = vec![splice(10, 20, "fox", "cat")];
+        let result = crate::apply_splices(path, "the quick fox", out_of_bounds);
+        assert!(matches!(result, Err(PaddownError::Conflict { .. })));
+    }
+
+    fn query(pattern: &str, regex: bool) -> SearchQuery {
+        SearchQuery { pattern: pattern.into(), regex, case_sensitive: true, ..Default::default() }
+    }
+
+    fn preview_text(text: &str, query: &SearchQuery, replacement: &str) -> Option<PreviewFile> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, text).unwrap();
+        let matcher = search::build_matcher(query).unwrap();
+        preview_file(&matcher, &path, replacement, query.regex, MAX_MATCHES)
+    }
+
+    #[test]
+    fn regex_replacements_expand_groups() {
+        let query = query(r"(\w+)@(?P<host>\w+)", true);
+        let file = preview_text("mail ann@home or bob@work", &query, "${host}:$1 $$").unwrap();
+        let new: Vec<&str> = file.matches.iter().map(|m| m.edit.new_text.as_str()).collect();
+        assert_eq!(new, ["home:ann $", "work:bob $"]);
+    }
+
+    #[test]
+    fn literal_replacements_are_used_as_typed() {
+        let file = preview_text("cost: $1", &query("$1", false), "$2").unwrap();
+        assert_eq!(file.matches[0].edit.new_text, "$2");
+        assert_eq!(file.matches[0].edit.old_text, "$1");
+    }
+
+    #[test]
+    fn previews_locate_matches_by_line() {
+        let text = "first\r\n\u{1F600} old text\nold";
+        let file = preview_text(text, &query("old", false), "new").unwrap();
+        let m = &file.matches[0];
+        assert_eq!((m.line, m.line_text.as_str(), m.range), (2, "\u{1F600} old text", [3, 6]));
+        assert_eq!((m.edit.start, m.edit.end), (12, 15));
+        assert_eq!(file.matches[1].line, 3);
+        assert!(preview_text(text, &query("absent", false), "x").is_none());
+    }
+
+    #[test]
+    fn multiline_matches_are_cut_at_the_line_end() {
+        let file = preview_text("one two\nthree", &query(r"two\nthree", true), "x").unwrap();
+        let m = &file.matches[0];
+        assert_eq!((m.line_text.as_str(), m.range), ("one two", [4, 7]));
+        assert_eq!(m.edit.old_text, "two\nthree");
+    }
+
+    #[test]
+    fn preview_and_splices_round_trip() {
+        let text = "a-1 b-2 c-3";
+        let file = preview_text(text, &query(r"(\w)-(\d)", true), "$2$1").unwrap();
+        let splices = file.matches.iter()
+            .map(|m| splice(m.edit.start, m.edit.end, &m.edit.old_text, &m.edit.new_text))
+            .collect();
+        assert_eq!(crate::apply_splices(Path::new("n.md"), text, splices).unwrap(), "1a 2b 3c");
+    }
+}
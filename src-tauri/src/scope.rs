@@ -0,0 +1,412 @@
+//! Which paths the webview is allowed to touch.
+//!
+//! `withGlobalTauri` exposes every command to any script running in the
+//! webview, so path-taking commands must not trust the paths they receive.
+//! Access is granted only by things the user did: pinning a folder in the
+//! sidebar, picking a file in a dialog, passing it on the command line or
+//! dropping it on the window. The app's recovery and config directories are
+//! always allowed.
+//!
+//! Grants are persisted to `<app_data_dir>/scope.json`. That file is never
+//! reachable through `write_file` or the sidebar's file management, so a
+//! script can't widen its own access by editing, moving or deleting it;
+//! `settings.json` is only consulted once, to seed the registry for users
+//! upgrading from a version without one.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::error::{self, PaddownError};
+use crate::bundle;
+
+/// Individually granted files to remember across restarts, most recent last.
+/// Enough to cover the open tabs and the recent-files list.
+const MAX_REMEMBERED_FILES: usize = 200;
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct Persisted {
+    folders: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+struct Scope {
+    /// Canonical paths of pinned folders; everything beneath them is allowed.
+    folders: Vec<PathBuf>,
+    /// Canonical paths of individually granted files.
+    files: Vec<PathBuf>,
+    /// App-owned directories and files, kept as given and resolved on each
+    /// check since they may not exist yet at startup.
+    app_dirs: Vec<PathBuf>,
+    app_files: Vec<PathBuf>,
+    /// Backend-only state that must stay out of reach even where it lives
+    /// inside an allowed directory (on Windows and macOS the config and data
+    /// directories are the same).
+    private: Vec<PathBuf>,
+    store: Option<PathBuf>,
+}
+
+pub struct ScopeState(Mutex<Scope>);
+
+/// Resolves `path` to the canonical form grants are stored in. A path that
+/// doesn't exist yet (a file about to be created by Save As) resolves
+/// through its parent. `None` means the path can't be resolved at all and
+/// must be refused.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if let Ok(p) = std::fs::canonicalize(path) {
+        return Some(p);
+    }
+    let parent = std::fs::canonicalize(path.parent()?).ok()?;
+    let name = path.file_name()?;
+    Some(parent.join(name))
+}
+
+impl Scope {
+    fn private_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.private.iter().filter_map(|p| normalize(p))
+    }
+
+    fn allows(&self, path: &Path) -> bool {
+        let Some(path) = normalize(path) else { return false };
+        if self.private_paths().any(|p| path.starts_with(p)) {
+            return false;
+        }
+        self.folders.iter().any(|f| path.starts_with(f))
+            || self.files.contains(&path)
+            || self.app_dirs.iter().filter_map(|d| normalize(d)).any(|d| path.starts_with(d))
+            || self.app_files.iter().filter_map(|f| normalize(f)).any(|f| path == f)
+    }
+
+    /// A directory is allowed if it lies inside a pinned folder or holds a
+    /// granted file — the latter so a document opened on its own can still
+    /// show images stored next to it. So is the assets folder beside a
+    /// granted file, where an HTML export saved there copies its images.
+    fn allows_dir(&self, dir: &Path) -> bool {
+        let Some(dir) = normalize(dir) else { return false };
+        self.folders.iter().any(|f| dir.starts_with(f))
+            || self.files.iter().filter_map(|f| f.parent()).any(|parent| {
+                parent == dir || parent.join(bundle::ASSETS_DIR) == dir
+            })
+    }
+
+    /// Whether entries may be created in the canonical `dir`.
+    fn allows_pinned_dir(&self, dir: &Path) -> bool {
+        !self.private_paths().any(|p| dir.starts_with(p))
+            && self.folders.iter().any(|f| dir.starts_with(f))
+    }
+
+    /// Whether the canonical `entry` may be created, renamed, moved or
+    /// deleted. Besides private state itself, this refuses any folder
+    /// holding it, so pinning the home folder doesn't let a script trash
+    /// or replace the app's data directory wholesale.
+    fn allows_pinned_entry(&self, entry: &Path) -> bool {
+        !self.private_paths().any(|p| p.starts_with(entry) || entry.starts_with(&p))
+            && self.folders.iter().any(|f| entry.starts_with(f) && entry != f)
+    }
+
+    fn save(&self) {
+        let Some(store) = &self.store else { return };
+        let persisted = Persisted {
+            folders: self.folders.clone(),
+            files: self.files.clone(),
+        };
+        if let Ok(json) = serde_json::to_vec_pretty(&persisted) {
+            if let Some(dir) = store.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(store, json);
+        }
+    }
+}
+
+/// Seeds grants from an existing `settings.json`: the sidebar's projects,
+/// the tabs to restore and the recent files. Only used when `scope.json`
+/// doesn't exist yet.
+fn seed_from_settings(settings: &Path) -> Persisted {
+    let mut seeded = Persisted::default();
+    let Ok(raw) = std::fs::read_to_string(settings) else { return seeded };
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&raw) else { return seeded };
+
+    let strings = |key: &str, field: Option<&str>| -> Vec<PathBuf> {
+        json.get(key)
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|item| match field {
+                Some(field) => item.get(field)?.as_str(),
+                None => item.as_str(),
+            })
+            .map(PathBuf::from)
+            .collect()
+    };
+    seeded.folders = strings("sidebarProjects", Some("path"));
+    seeded.files = strings("openTabs", Some("filePath"));
+    seeded.files.extend(strings("recentFiles", None));
+    seeded
+}
+
+impl ScopeState {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        let data_dir = app.path().app_data_dir().ok();
+        let config_dir = app.path().app_config_dir().ok();
+        let store = data_dir.as_ref().map(|d| d.join("scope.json"));
+        let portable = crate::portable_settings_path();
+
+        let persisted = match store.as_ref().and_then(|s| std::fs::read(s).ok()) {
+            Some(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+            None => {
+                let settings = portable.clone()
+                    .or_else(|| config_dir.as_ref().map(|d| d.join("settings.json")));
+                settings.map(|s| seed_from_settings(&s)).unwrap_or_default()
+            }
+        };
+
+        let mut scope = Scope {
+            folders: Vec::new(),
+            files: Vec::new(),
+            app_dirs: data_dir.iter().map(|d| d.join("recovery")).chain(config_dir).collect(),
+            app_files: portable.into_iter().collect(),
+            private: store.iter()
+                .cloned()
+                .chain(crate::history::history_dir_path(app).ok())
+                .chain(crate::search_index::index_dir_path(app).ok())
+                .collect(),
+            store,
+        };
+        for folder in persisted.folders {
+            if let Some(folder) = normalize(&folder) {
+                if !scope.folders.contains(&folder) {
+                    scope.folders.push(folder);
+                }
+            }
+        }
+        for file in persisted.files {
+            if let Some(file) = normalize(&file) {
+                scope.files.retain(|f| f != &file);
+                scope.files.push(file);
+            }
+        }
+        let excess = scope.files.len().saturating_sub(MAX_REMEMBERED_FILES);
+        scope.files.drain(..excess);
+        scope.save();
+        ScopeState(Mutex::new(scope))
+    }
+}
+
+fn with_scope<T>(app: &tauri::AppHandle, f: impl FnOnce(&mut Scope) -> T) -> error::Result<T> {
+    let state = app.state::<ScopeState>();
+    let mut scope = state.0.lock().map_err(PaddownError::internal)?;
+    Ok(f(&mut scope))
+}
+
+fn outside(path: &Path) -> PaddownError {
+    PaddownError::invalid_path(path, "Outside the folders and files Paddown was given access to")
+}
+
+/// Fails with `InvalidPath` unless `path` is covered by a grant.
+pub fn check(app: &tauri::AppHandle, path: &Path) -> error::Result<()> {
+    if with_scope(app, |scope| scope.allows(path))? {
+        Ok(())
+    } else {
+        Err(outside(path))
+    }
+}
+
+/// Like `check`, for a directory whose files will be read (a document's
+/// image folder).
+pub fn check_dir(app: &tauri::AppHandle, dir: &Path) -> error::Result<()> {
+    if with_scope(app, |scope| scope.allows_dir(dir))? {
+        Ok(())
+    } else {
+        Err(outside(dir))
+    }
+}
+
+/// Resolves an entry without following it if it is itself a symlink, so a
+/// link is renamed or deleted rather than whatever it points at.
+fn normalize_entry(path: &Path) -> Option<PathBuf> {
+    let parent = std::fs::canonicalize(path.parent()?).ok()?;
+    let name = path.file_name()?;
+    Some(parent.join(name))
+}
+
+/// Fails with `InvalidPath` unless `dir` is a pinned folder or lies inside
+/// one. Individually granted files don't count: file management is limited
+/// to the sidebar's folders. Like `check`, the app's private state is out of
+/// reach even inside a pinned folder.
+pub fn check_pinned_dir(app: &tauri::AppHandle, dir: &Path) -> error::Result<()> {
+    let resolved = std::fs::canonicalize(dir).map_err(|e| PaddownError::io(dir, e))?;
+    if with_scope(app, |scope| scope.allows_pinned_dir(&resolved))? {
+        Ok(())
+    } else {
+        Err(PaddownError::invalid_path(dir, "Not inside a pinned folder"))
+    }
+}
+
+/// Fails with `InvalidPath` unless `path` is an entry strictly inside a
+/// pinned folder, and neither is nor holds the app's private state. The
+/// pinned folder itself can't be renamed, moved or deleted from here.
+/// `path` need not exist, so the same check covers where an entry is about
+/// to be created, renamed or moved to.
+pub fn check_pinned_entry(app: &tauri::AppHandle, path: &Path) -> error::Result<()> {
+    let Some(resolved) = normalize_entry(path) else {
+        return Err(PaddownError::invalid_path(path, "Not inside a pinned folder"));
+    };
+    if with_scope(app, |scope| scope.allows_pinned_entry(&resolved))? {
+        Ok(())
+    } else {
+        Err(PaddownError::invalid_path(path, "Not inside a pinned folder"))
+    }
+}
+
+/// The pinned folders, canonicalised.
+pub fn pinned_folders(app: &tauri::AppHandle) -> error::Result<Vec<PathBuf>> {
+    with_scope(app, |scope| scope.folders.clone())
+}
+
+/// The outermost pinned folder containing the canonical `path`, if any.
+pub fn pinned_root(app: &tauri::AppHandle, path: &Path) -> error::Result<Option<PathBuf>> {
+    Ok(pinned_folders(app)?
+        .into_iter()
+        .filter(|root| path.starts_with(root))
+        .min_by_key(|root| root.as_os_str().len()))
+}
+
+/// Grants access to a single file. Paths that can't be resolved are ignored;
+/// the command using them will fail on its own.
+pub fn grant_file(app: &tauri::AppHandle, path: &Path) {
+    let Some(path) = normalize(path) else { return };
+    let _ = with_scope(app, |scope| {
+        scope.files.retain(|f| f != &path);
+        scope.files.push(path);
+        if scope.files.len() > MAX_REMEMBERED_FILES {
+            let excess = scope.files.len() - MAX_REMEMBERED_FILES;
+            scope.files.drain(..excess);
+        }
+        scope.save();
+    });
+}
+
+/// Grants access to a folder and everything beneath it.
+pub fn pin_folder(app: &tauri::AppHandle, path: &Path) {
+    let Some(path) = normalize(path) else { return };
+    let _ = with_scope(app, |scope| {
+        if !scope.folders.contains(&path) {
+            scope.folders.push(path);
+            scope.save();
+        }
+    });
+}
+
+/// Grants whatever `path` is: a folder is pinned, anything else is granted
+/// as a file. Used for paths from the command line and from drag and drop.
+pub fn grant_path(app: &tauri::AppHandle, path: &Path) {
+    if path.is_dir() {
+        pin_folder(app, path);
+    } else {
+        grant_file(app, path);
+    }
+}
+
+/// Removes a pinned folder, e.g. when its project is removed from the
+/// sidebar. Files granted individually are unaffected.
+#[tauri::command]
+pub fn unpin_folder(app: tauri::AppHandle, path: String) -> error::Result<()> {
+    let Some(path) = normalize(Path::new(&path)) else { return Ok(()) };
+    with_scope(&app, |scope| {
+        let before = scope.folders.len();
+        scope.folders.retain(|f| f != &path);
+        if scope.folders.len() != before {
+            scope.save();
+        }
+    })?;
+    crate::search_index::forget(&app, &path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scope with `root` pinned and app data kept in `root/data`, as when
+    /// the home folder is pinned.
+    fn scope_with_data_inside(root: &Path) -> (Scope, PathBuf) {
+        let data = root.join("data");
+        std::fs::create_dir_all(data.join("history")).unwrap();
+        std::fs::write(data.join("scope.json"), "{}").unwrap();
+        let scope = Scope {
+            folders: vec![root.to_path_buf()],
+            files: Vec::new(),
+            app_dirs: Vec::new(),
+            app_files: Vec::new(),
+            private: vec![data.join("scope.json"), data.join("history"), data.join("index")],
+            store: None,
+        };
+        (scope, data)
+    }
+
+    #[test]
+    fn private_state_inside_a_pinned_folder_is_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(tmp.path()).unwrap();
+        let (scope, data) = scope_with_data_inside(&root);
+
+        assert!(!scope.allows(&data.join("scope.json")));
+        assert!(!scope.allows_pinned_entry(&data.join("scope.json")));
+        assert!(!scope.allows_pinned_entry(&data.join("history")));
+        assert!(!scope.allows_pinned_entry(&data.join("history").join("abc")));
+        assert!(!scope.allows_pinned_dir(&data.join("history")));
+        // Not yet created, but still private.
+        assert!(!scope.allows_pinned_entry(&data.join("index")));
+    }
+
+    #[test]
+    fn folders_holding_private_state_are_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(tmp.path()).unwrap();
+        let (scope, data) = scope_with_data_inside(&root);
+
+        assert!(!scope.allows_pinned_entry(&data));
+        // Entries may still be created next to it.
+        assert!(scope.allows_pinned_dir(&data));
+        assert!(scope.allows_pinned_entry(&data.join("notes.md")));
+    }
+
+    #[test]
+    fn assets_beside_a_granted_file_are_allowed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(tmp.path()).unwrap();
+        std::fs::create_dir(root.join("elsewhere")).unwrap();
+        let scope = Scope {
+            folders: Vec::new(),
+            files: vec![root.join("page.html")],
+            app_dirs: Vec::new(),
+            app_files: Vec::new(),
+            private: Vec::new(),
+            store: None,
+        };
+
+        assert!(scope.allows_dir(&root));
+        assert!(scope.allows_dir(&root.join("assets")));
+        assert!(!scope.allows_dir(&root.join("other")));
+        assert!(!scope.allows_dir(&root.join("elsewhere")));
+        #[cfg(unix)]
+        {
+            // Not when the assets folder leads somewhere else.
+            std::os::unix::fs::symlink(root.join("elsewhere"), root.join("assets")).unwrap();
+            assert!(!scope.allows_dir(&root.join("assets")));
+        }
+    }
+
+    #[test]
+    fn ordinary_entries_in_a_pinned_folder_are_allowed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(tmp.path()).unwrap();
+        let (scope, _) = scope_with_data_inside(&root);
+
+        assert!(scope.allows_pinned_dir(&root));
+        assert!(scope.allows_pinned_entry(&root.join("notes.md")));
+        assert!(!scope.allows_pinned_entry(&root));
+        assert!(!scope.allows_pinned_entry(Path::new("/elsewhere/notes.md")));
+    }
+}
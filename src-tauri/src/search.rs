@@ -0,0 +1,393 @@
+//! Full-text search across the pinned folders.
+//!
+//! Files are walked with the same rules as the sidebar tree (hidden entries,
+//! symlinks and the extension list) and matched line by line, like grep.
+//! Results stream to the window as `search-results` events, one per file
+//! with matches, so the first hits show up while the rest of a large
+//! workspace is still being read. The command itself resolves with totals
+//! once the walk is done, or with `Cancelled` after `cancel_task`.
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+
+use crate::error::{self, PaddownError};
+use crate::{scope, tasks, workspace};
+
+/// Lines of context sent before and after each matching line by default.
+const DEFAULT_CONTEXT: usize = 2;
+
+/// Matches to report before stopping; the summary says when it was hit.
+const MAX_MATCHES: usize = 10_000;
+
+/// Emit a `task-progress` event every this many files searched.
+const PROGRESS_EVERY: u64 = 50;
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression rather than literal text.
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Globs matched against paths relative to their pinned folder, with `/`
+    /// separators. Empty `include` means every file.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// File extensions to search, as the sidebar's `sidebarFileExtensions`.
+    pub extensions: Vec<String>,
+    /// Pinned folders (or folders inside them) to search; all pinned
+    /// folders when empty.
+    pub roots: Vec<String>,
+    pub context: Option<usize>,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        SearchQuery {
+            pattern: String::new(),
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            extensions: workspace::MARKDOWN_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            roots: Vec::new(),
+            context: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+struct LineMatch {
+    /// 1-based line number.
+    line: usize,
+    /// 1-based UTF-16 column of the first match on the line.
+    column: usize,
+    text: String,
+    /// `[start, end)` UTF-16 ranges within `text` that matched.
+    ranges: Vec<[usize; 2]>,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+struct FileResults<'a> {
+    task_id: &'a str,
+    path: &'a str,
+    matches: &'a [LineMatch],
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct SearchSummary {
+    files_searched: u64,
+    files_matched: u64,
+    matches: u64,
+    /// The search stopped early at `MAX_MATCHES`.
+    truncated: bool,
+}
+
+/// Compiles the query's pattern with its case and whole-word options. `^`
+/// and `$` match at line boundaries, whether it runs on a line or a file.
+pub(crate) fn build_matcher(query: &SearchQuery) -> error::Result<Regex> {
+    if query.pattern.is_empty() {
+        return Err(PaddownError::InvalidQuery { message: "The search pattern is empty".into() });
+    }
+    let pattern = if query.regex { query.pattern.clone() } else { regex::escape(&query.pattern) };
+    let pattern = if query.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .multi_line(true)
+        .crlf(true)
+        .build()
+        .map_err(|e| PaddownError::InvalidQuery { message: e.to_string() })
+}
+
+fn build_globs(globs: &[String]) -> error::Result<Option<GlobSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = Glob::new(glob)
+            .map_err(|e| PaddownError::InvalidQuery { message: e.to_string() })?;
+        builder.add(glob);
+    }
+    builder.build()
+        .map(Some)
+        .map_err(|e| PaddownError::InvalidQuery { message: e.to_string() })
+}
+
+/// Which files under a root a query covers.
+pub(crate) struct FileFilter {
+    extensions: Vec<String>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl FileFilter {
+    pub fn new(query: &SearchQuery) -> error::Result<Self> {
+        Ok(FileFilter {
+            extensions: query.extensions.clone(),
+            include: build_globs(&query.include)?,
+            exclude: build_globs(&query.exclude)?,
+        })
+    }
+
+    pub fn accepts(&self, root: &Path, path: &Path) -> bool {
+        if !workspace::has_extension(path, &self.extensions) {
+            return false;
+        }
+        let relative = path.strip_prefix(root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        self.include.as_ref().is_none_or(|set| set.is_match(&relative))
+            && !self.exclude.as_ref().is_some_and(|set| set.is_match(&relative))
+    }
+}
+
+/// The folders a query searches: the ones it names, each of which must be
+/// inside a pinned folder, or every pinned folder. Nested roots are dropped
+/// so no file is searched twice.
+pub(crate) fn search_roots(
+    app: &tauri::AppHandle,
+    query: &SearchQuery,
+) -> error::Result<Vec<PathBuf>> {
+    let roots = if query.roots.is_empty() {
+        scope::pinned_folders(app)?
+    } else {
+        query.roots.iter()
+            .map(|root| {
+                let root = Path::new(root);
+                scope::check_pinned_dir(app, root)?;
+                std::fs::canonicalize(root).map_err(|e| PaddownError::io(root, e))
+            })
+            .collect::<error::Result<Vec<_>>>()?
+    };
+    Ok(outermost(roots))
+}
+
+/// `roots` sorted, without duplicates or folders inside another of them.
+pub(crate) fn outermost(mut roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    roots.sort();
+    roots.dedup();
+    let all = roots.clone();
+    roots.retain(|r| !all.iter().any(|other| other != r && r.starts_with(other)));
+    roots
+}
+
+/// Calls `visit` with each file the query covers, until it returns `false`
+/// or the token is cancelled.
+pub(crate) fn for_each_file(
+    app: &tauri::AppHandle,
+    query: &SearchQuery,
+    token: &tasks::CancelToken,
+    visit: &mut dyn FnMut(&Path) -> bool,
+) -> error::Result<()> {
+    let filter = FileFilter::new(query)?;
+    let mut stopped = false;
+    for root in search_roots(app, query)? {
+        workspace::walk_files(&root, token, &mut |path| {
+            if !stopped && filter.accepts(&root, path) {
+                stopped = !visit(path);
+            }
+        });
+        if stopped {
+            break;
+        }
+    }
+    if token.is_cancelled() {
+        return Err(PaddownError::Cancelled);
+    }
+    Ok(())
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// Every matching line in `contents`, stopping after `limit` matches.
+fn search_text(
+    matcher: &Regex,
+    contents: &str,
+    context: usize,
+    limit: usize,
+) -> (Vec<LineMatch>, usize) {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut results = Vec::new();
+    let mut count = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if count >= limit {
+            break;
+        }
+        let mut ranges: Vec<[usize; 2]> = Vec::new();
+        for m in matcher.find_iter(line) {
+            // An empty match (e.g. `^`) says nothing useful in a results list.
+            if m.is_empty() {
+                continue;
+            }
+            let start = utf16_len(&line[..m.start()]);
+            ranges.push([start, start + utf16_len(m.as_str())]);
+        }
+        let Some(first) = ranges.first() else { continue };
+        count += ranges.len();
+        let owned = |slice: &[&str]| slice.iter().map(|l| l.to_string()).collect();
+        results.push(LineMatch {
+            line: i + 1,
+            column: first[0] + 1,
+            text: line.to_string(),
+            before: owned(&lines[i.saturating_sub(context)..i]),
+            after: owned(&lines[i + 1..(i + 1 + context).min(lines.len())]),
+            ranges,
+        });
+    }
+    (results, count)
+}
+
+fn search(
+    app: &tauri::AppHandle,
+    query: &SearchQuery,
+    task_id: &str,
+    token: &tasks::CancelToken,
+    progress: &tasks::Progress,
+) -> error::Result<SearchSummary> {
+    let matcher = build_matcher(query)?;
+    let context = query.context.unwrap_or(DEFAULT_CONTEXT);
+    let mut summary = SearchSummary::default();
+
+    for_each_file(app, query, token, &mut |path| {
+        summary.files_searched += 1;
+        if summary.files_searched.is_multiple_of(PROGRESS_EVERY) {
+            progress.report(summary.files_searched, None);
+        }
+        // Files too large to open or not valid UTF-8 are skipped, as the
+        // editor couldn't show them anyway.
+        let Ok(file) = crate::read_text_file(path) else { return true };
+        let remaining = MAX_MATCHES - summary.matches as usize;
+        let (matches, count) = search_text(&matcher, &file.contents, context, remaining);
+        if !matches.is_empty() {
+            summary.files_matched += 1;
+            summary.matches += count as u64;
+            let _ = app.emit_to("main", "search-results", FileResults {
+                task_id,
+                path: &path.to_string_lossy(),
+                matches: &matches,
+            });
+        }
+        if summary.matches as usize >= MAX_MATCHES {
+            summary.truncated = true;
+            return false;
+        }
+        true
+    })?;
+    Ok(summary)
+}
+
+/// Searches the pinned folders, streaming matches as `search-results`
+/// events tagged with `task_id`.
+#[tauri::command]
+pub async fn search_workspace(
+    app: tauri::AppHandle,
+    query: SearchQuery,
+    task_id: String,
+) -> error::Result<SearchSummary> {
+    let task = tasks::register(&app, Some(task_id.clone()));
+    let token = task.token();
+    let progress = task.progress();
+    tasks::blocking(move || search(&app, &query, &task_id, &token, &progress)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> SearchQuery {
+        SearchQuery { pattern: pattern.into(), ..SearchQuery::default() }
+    }
+
+    fn ranges(matcher: &Regex, text: &str) -> Vec<Vec<[usize; 2]>> {
+        let (matches, _) = search_text(matcher, text, 0, MAX_MATCHES);
+        matches.into_iter().map(|m| m.ranges).collect()
+    }
+
+    #[test]
+    fn literal_patterns_are_escaped_and_case_insensitive() {
+        let matcher = build_matcher(&query("a.b")).unwrap();
+        assert!(matcher.is_match("A.B"));
+        assert!(!matcher.is_match("axb"));
+
+        let matcher = build_matcher(&SearchQuery { case_sensitive: true, ..query("a.b") }).unwrap();
+        assert!(!matcher.is_match("A.B"));
+    }
+
+    #[test]
+    fn regex_and_whole_word_options() {
+        let matcher = build_matcher(&SearchQuery { regex: true, ..query("^to+do$") }).unwrap();
+        assert_eq!(ranges(&matcher, "x\ntoooDo\ntodo later"), [[[0, 6]]]);
+
+        let whole = SearchQuery { whole_word: true, ..query("cat") };
+        let matcher = build_matcher(&whole).unwrap();
+        assert_eq!(ranges(&matcher, "cat concat cat."), [[[0, 3], [11, 14]]]);
+    }
+
+    #[test]
+    fn bad_patterns_are_invalid_queries() {
+        assert!(matches!(build_matcher(&query("")), Err(PaddownError::InvalidQuery { .. })));
+        let bad = SearchQuery { regex: true, ..query("(unclosed") };
+        assert!(matches!(build_matcher(&bad), Err(PaddownError::InvalidQuery { .. })));
+        let bad_glob = SearchQuery { include: vec!["a[".into()], ..query("x") };
+        assert!(matches!(FileFilter::new(&bad_glob), Err(PaddownError::InvalidQuery { .. })));
+    }
+
+    #[test]
+    fn matches_report_utf16_columns_and_context() {
+        let matcher = build_matcher(&query("needle")).unwrap();
+        let text = "one\ntwo\n\u{1F600} needle\nfour\nfive\nsix";
+        let (matches, count) = search_text(&matcher, text, 2, MAX_MATCHES);
+        assert_eq!(count, 1);
+        let m = &matches[0];
+        assert_eq!((m.line, m.column), (3, 4));
+        assert_eq!(m.ranges, [[3, 9]]);
+        assert_eq!(m.before, ["one", "two"]);
+        assert_eq!(m.after, ["four", "five"]);
+    }
+
+    #[test]
+    fn search_stops_at_the_limit() {
+        let matcher = build_matcher(&query("x")).unwrap();
+        let (matches, count) = search_text(&matcher, "xx\nx\nx\n", 0, 3);
+        assert_eq!(count, 3);
+        assert_eq!(matches.len(), 2);
+    }
+
+    #[test]
+    fn empty_regex_matches_are_ignored() {
+        let matcher = build_matcher(&SearchQuery { regex: true, ..query("^") }).unwrap();
+        assert!(search_text(&matcher, "a\nb", 0, MAX_MATCHES).0.is_empty());
+    }
+
+    #[test]
+    fn filters_match_globs_relative_to_the_root() {
+        let root = Path::new("/notes");
+        let filter = FileFilter::new(&SearchQuery {
+            include: vec!["docs/**".into()],
+            exclude: vec!["**/draft*".into()],
+            ..query("x")
+        })
+        .unwrap();
+        assert!(filter.accepts(root, Path::new("/notes/docs/a.md")));
+        assert!(filter.accepts(root, Path::new("/notes/docs/deep/b.markdown")));
+        assert!(!filter.accepts(root, Path::new("/notes/docs/draft-1.md")));
+        assert!(!filter.accepts(root, Path::new("/notes/other/a.md")));
+        assert!(!filter.accepts(root, Path::new("/notes/docs/a.txt")));
+    }
+
+    #[test]
+    fn nested_roots_collapse_to_the_outermost() {
+        let roots = ["/b/inner", "/a", "/b", "/a", "/ab"].map(PathBuf::from).to_vec();
+        assert_eq!(outermost(roots), ["/a", "/ab", "/b"].map(PathBuf::from));
+    }
+}
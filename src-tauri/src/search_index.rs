@@ -0,0 +1,903 @@
+//! Persistent full-text index of the pinned folders, ranked with BM25.
+//!
+//! Each pinned folder gets an inverted index of its Markdown files, kept in
+//! memory for queries and saved to `<app_data_dir>/search-index` so the next
+//! start only re-reads files whose size or modification time changed. A
+//! single worker thread owns every update: it loads or builds an index when
+//! the sidebar starts watching a folder, and applies the folder watcher's
+//! events in debounced batches. Files are read and tokenized before the
+//! index is locked, and the results swapped in under a short write lock,
+//! so queries never wait on a file being read. Changed indexes are written
+//! back at most once a minute, and when the app exits.
+//!
+//! Alongside the terms, each document keeps its `[[wiki-links]]`, so the
+//! link graph (see `wikilinks`) is maintained by the same updates.
+//!
+//! Removed documents are tombstoned rather than pulled out of every posting
+//! list; the lists are compacted once tombstones make up a quarter of the
+//! index.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tauri::Manager;
+
+use crate::error::{self, PaddownError};
+use crate::markdown::{self, WikiLink};
+use crate::{scope, search, tasks, workspace};
+
+/// Bumped whenever the on-disk format or tokenizer changes; older indexes
+/// are rebuilt.
+const FORMAT_VERSION: u32 = 3;
+
+/// BM25 parameters, at their usual values.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Longer tokens (base64 blobs, long URLs) aren't worth indexing.
+const MAX_TOKEN_LEN: usize = 64;
+
+/// How many indexed terms a trailing prefix may expand to.
+const MAX_PREFIX_TERMS: usize = 64;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 200;
+
+/// Characters of context in a result snippet.
+const SNIPPET_CHARS: usize = 160;
+const SNIPPET_LEAD: usize = 40;
+
+/// Watcher events are applied once they've been quiet this long…
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// …or after this long regardless, so a steady stream can't starve updates.
+const MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Longest a changed index waits to be saved. Writing a large index takes a
+/// while, so it isn't done for every batch.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub(crate) struct Doc {
+    /// Relative to the root, with `/` separators.
+    pub path: String,
+    /// First level-1 heading, or the file name.
+    pub title: String,
+    mtime_ns: u64,
+    size: u64,
+    /// Number of tokens.
+    len: u32,
+    pub links: Vec<WikiLink>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct RootIndex {
+    version: u32,
+    root: PathBuf,
+    /// Indexed by document id; `None` for removed documents.
+    docs: Vec<Option<Doc>>,
+    /// Term to `(document id, term frequency)`, by ascending id.
+    postings: BTreeMap<String, Vec<(u32, u32)>>,
+    #[serde(skip)]
+    ids: BTreeMap<String, u32>,
+    #[serde(skip)]
+    live: u32,
+    #[serde(skip)]
+    total_len: u64,
+    /// Changed since it was queued for saving.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// A file read and tokenized outside any lock, ready to go into an index.
+struct Scanned {
+    doc: Doc,
+    counts: HashMap<String, u32>,
+}
+
+/// One update to an index, worked out from the disk before the index is
+/// locked for writing.
+enum Change {
+    /// Index this file, replacing any earlier version. `None` leaves it out:
+    /// it is gone, too large or not UTF-8, as search skips those too.
+    File(String, Option<Scanned>),
+    /// Remove this path and everything beneath it.
+    Gone(String),
+}
+
+/// Lowercased alphanumeric runs with their byte ranges in `text`.
+fn tokens(text: &str) -> impl Iterator<Item = (usize, usize, String)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || loop {
+        let (start, c) = chars.next()?;
+        if !c.is_alphanumeric() {
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, c)) = chars.peek() {
+            if !c.is_alphanumeric() {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+        if end - start <= MAX_TOKEN_LEN {
+            return Some((start, end, text[start..end].to_lowercase()));
+        }
+    })
+}
+
+fn title_of(doc: &markdown::Document, path: &Path) -> String {
+    doc.title()
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+        })
+}
+
+fn mtime_ns(metadata: &std::fs::Metadata) -> u64 {
+    metadata.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// `path` relative to `root`, with `/` separators.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let rest = path.strip_prefix(root).ok()?;
+    Some(rest.to_string_lossy().replace('\\', "/"))
+}
+
+/// Reads and tokenizes the file at `path`, known in its index as `rel`.
+fn scan_file(path: &Path, rel: String, metadata: &std::fs::Metadata) -> Option<Scanned> {
+    let file = crate::read_text_file(path).ok()?;
+    let doc = markdown::parse(&file.contents);
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    let mut len = 0u32;
+    for (_, _, token) in tokens(&file.contents) {
+        *counts.entry(token).or_default() += 1;
+        len += 1;
+    }
+    Some(Scanned {
+        doc: Doc {
+            path: rel,
+            title: title_of(&doc, path),
+            mtime_ns: mtime_ns(metadata),
+            size: metadata.len(),
+            len,
+            links: doc.wiki_links,
+        },
+        counts,
+    })
+}
+
+impl RootIndex {
+    fn new(root: &Path) -> Self {
+        RootIndex { version: FORMAT_VERSION, root: root.to_path_buf(), ..Default::default() }
+    }
+
+    /// Rebuilds the fields that aren't persisted.
+    fn restore(&mut self) {
+        self.ids.clear();
+        self.live = 0;
+        self.total_len = 0;
+        for (id, doc) in self.docs.iter().enumerate() {
+            if let Some(doc) = doc {
+                self.ids.insert(doc.path.clone(), id as u32);
+                self.live += 1;
+                self.total_len += doc.len as u64;
+            }
+        }
+    }
+
+    fn remove(&mut self, rel: &str) {
+        if let Some(id) = self.ids.remove(rel) {
+            if let Some(doc) = self.docs[id as usize].take() {
+                self.live -= 1;
+                self.total_len -= doc.len as u64;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Removes `rel` and everything beneath it.
+    fn remove_tree(&mut self, rel: &str) {
+        let prefix = format!("{}/", rel);
+        let under: Vec<String> = self.ids.range(prefix.clone()..)
+            .take_while(|(k, _)| k.starts_with(&prefix))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in under {
+            self.remove(&key);
+        }
+        self.remove(rel);
+    }
+
+    /// Whether the indexed copy of `rel` matches a file with `metadata`.
+    fn is_current(&self, rel: &str, metadata: &std::fs::Metadata) -> bool {
+        self.ids.get(rel)
+            .and_then(|&id| self.docs[id as usize].as_ref())
+            .is_some_and(|doc| doc.size == metadata.len() && doc.mtime_ns == mtime_ns(metadata))
+    }
+
+    fn insert(&mut self, scanned: Scanned) {
+        let Scanned { doc, counts } = scanned;
+        self.remove(&doc.path);
+        let id = self.docs.len() as u32;
+        for (term, tf) in counts {
+            self.postings.entry(term).or_default().push((id, tf));
+        }
+        self.ids.insert(doc.path.clone(), id);
+        self.live += 1;
+        self.total_len += doc.len as u64;
+        self.docs.push(Some(doc));
+        self.dirty = true;
+    }
+
+    /// The indexed files under the folder `rel_dir` that aren't in `seen`.
+    fn missing_under(&self, rel_dir: &str, seen: &HashSet<String>) -> Vec<String> {
+        let prefix = if rel_dir.is_empty() { String::new() } else { format!("{}/", rel_dir) };
+        self.ids.range(prefix.clone()..)
+            .take_while(|(k, _)| k.starts_with(&prefix))
+            .filter(|(k, _)| !seen.contains(*k))
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    fn apply(&mut self, changes: Vec<Change>) {
+        for change in changes {
+            match change {
+                Change::File(_, Some(scanned)) => self.insert(scanned),
+                Change::File(rel, None) => self.remove(&rel),
+                Change::Gone(rel) => self.remove_tree(&rel),
+            }
+        }
+        self.compact_if_needed();
+    }
+
+    /// Drops tombstones from the posting lists once they pile up.
+    fn compact_if_needed(&mut self) {
+        let dead = self.docs.len() - self.live as usize;
+        if dead < 64 || dead * 4 < self.docs.len() {
+            return;
+        }
+        let mut remap = vec![None; self.docs.len()];
+        let mut docs = Vec::with_capacity(self.live as usize);
+        for (old, doc) in self.docs.drain(..).enumerate() {
+            if let Some(doc) = doc {
+                remap[old] = Some(docs.len() as u32);
+                docs.push(Some(doc));
+            }
+        }
+        self.docs = docs;
+        self.postings.retain(|_, list| {
+            list.retain_mut(|(id, _)| match remap[*id as usize] {
+                Some(new) => {
+                    *id = new;
+                    true
+                }
+                None => false,
+            });
+            !list.is_empty()
+        });
+        self.restore();
+        self.dirty = true;
+    }
+
+    /// BM25 scores for `terms`; the last one also matches as a prefix when
+    /// `prefix` is set, for results while typing.
+    fn score(&self, terms: &[String], prefix: bool) -> Vec<(u32, f64)> {
+        if self.live == 0 {
+            return Vec::new();
+        }
+        let n = self.live as f64;
+        let avgdl = (self.total_len as f64 / n).max(1.0);
+        let mut scores: HashMap<u32, f64> = HashMap::new();
+
+        for (i, term) in terms.iter().enumerate() {
+            let lists: Vec<&Vec<(u32, u32)>> = if prefix && i == terms.len() - 1 {
+                self.postings.range(term.clone()..)
+                    .take_while(|(t, _)| t.starts_with(term.as_str()))
+                    .take(MAX_PREFIX_TERMS)
+                    .map(|(_, list)| list)
+                    .collect()
+            } else {
+                self.postings.get(term).into_iter().collect()
+            };
+            for list in lists {
+                let live: Vec<(&Doc, u32, u32)> = list.iter()
+                    .filter_map(|&(id, tf)| Some((self.docs[id as usize].as_ref()?, id, tf)))
+                    .collect();
+                let df = live.len() as f64;
+                let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                for (doc, id, tf) in live {
+                    let tf = tf as f64;
+                    let norm = K1 * (1.0 - B + B * doc.len as f64 / avgdl);
+                    *scores.entry(id).or_default() += idf * tf * (K1 + 1.0) / (tf + norm);
+                }
+            }
+        }
+        scores.into_iter().collect()
+    }
+}
+
+// ─── Updates ─────────────────────────────────────────────────
+
+/// The change for the file at `path`, or `None` if the index already has
+/// it as it is.
+fn file_change(index: &RwLock<RootIndex>, path: &Path, rel: String) -> Option<Change> {
+    let Ok(metadata) = std::fs::metadata(path) else { return Some(Change::File(rel, None)) };
+    if index.read().is_ok_and(|index| index.is_current(&rel, &metadata)) {
+        return None;
+    }
+    let scanned = scan_file(path, rel.clone(), &metadata);
+    Some(Change::File(rel, scanned))
+}
+
+/// Works out how `paths` under `root` changed on disk: new and changed
+/// files are read, vanished ones removed, and a folder is brought in line
+/// with everything beneath it. The index is only read-locked, briefly, to
+/// tell which files changed. Only the worker writes to an index, so what
+/// it saw still holds when the changes are applied.
+fn changes(
+    index: &RwLock<RootIndex>,
+    root: &Path,
+    paths: impl IntoIterator<Item = PathBuf>,
+    token: &tasks::CancelToken,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for path in paths {
+        if workspace::is_hidden_path(root, &path) {
+            continue;
+        }
+        let Some(rel) = relative(root, &path) else { continue };
+        match std::fs::symlink_metadata(&path) {
+            Ok(m) if m.is_dir() => {
+                let mut seen = HashSet::new();
+                for file in workspace::markdown_files(&path, token) {
+                    let Some(file_rel) = relative(root, &file) else { continue };
+                    seen.insert(file_rel.clone());
+                    changes.extend(file_change(index, &file, file_rel));
+                }
+                if token.is_cancelled() {
+                    break;
+                }
+                if let Ok(index) = index.read() {
+                    let missing = index.missing_under(&rel, &seen);
+                    changes.extend(missing.into_iter().map(|rel| Change::File(rel, None)));
+                }
+            }
+            Ok(m) if m.is_file() && workspace::is_markdown(&path) => {
+                changes.extend(file_change(index, &path, rel));
+            }
+            Ok(_) => {}
+            Err(_) => changes.push(Change::Gone(rel)),
+        }
+    }
+    changes
+}
+
+// ─── Storage ─────────────────────────────────────────────────
+
+pub(crate) fn index_dir_path(app: &tauri::AppHandle) -> error::Result<PathBuf> {
+    let dir = app.path().app_data_dir().map_err(PaddownError::internal)?;
+    Ok(dir.join("search-index"))
+}
+
+fn index_file(app: &tauri::AppHandle, root: &Path) -> error::Result<PathBuf> {
+    let hash = crate::content_hash(root.to_string_lossy().as_bytes());
+    Ok(index_dir_path(app)?.join(format!("{}.json", &hash[..32])))
+}
+
+/// The saved index for `root`, or an empty one if there is none or it was
+/// written by an incompatible version.
+fn load(app: &tauri::AppHandle, root: &Path) -> RootIndex {
+    let saved = index_file(app, root).ok()
+        .and_then(|file| std::fs::read(file).ok())
+        .and_then(|bytes| serde_json::from_slice::<RootIndex>(&bytes).ok())
+        .filter(|index| index.version == FORMAT_VERSION && index.root == root);
+    let mut index = saved.unwrap_or_else(|| RootIndex::new(root));
+    index.restore();
+    index
+}
+
+/// Writes `root`'s index to disk. It is serialized under a read lock, so
+/// queries carry on meanwhile, and written out with no lock held.
+fn save(app: &tauri::AppHandle, root: &Path, index: &RwLock<RootIndex>) -> error::Result<()> {
+    let json = {
+        let index = index.read().map_err(PaddownError::internal)?;
+        serde_json::to_vec(&*index).map_err(PaddownError::internal)?
+    };
+    let file = index_file(app, root)?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| PaddownError::io(dir, e))?;
+    }
+    let tmp = file.with_extension("tmp");
+    std::fs::write(&tmp, json).map_err(|e| PaddownError::io(&tmp, e))?;
+    std::fs::rename(&tmp, &file).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        PaddownError::io(&file, e)
+    })
+}
+
+/// Saves every index changed since it was last saved. Called by the worker
+/// every `SAVE_INTERVAL` and once more when the app exits.
+pub fn flush(app: &tauri::AppHandle) {
+    let state = app.state::<IndexState>();
+    // Held throughout, so the worker and the exit handler never write the
+    // same file at once.
+    let Ok(mut unsaved) = state.unsaved.lock() else { return };
+    for root in unsaved.drain() {
+        if let Some(index) = state.get(&root) {
+            let _ = save(app, &root, &index);
+        }
+    }
+}
+
+// ─── Worker ──────────────────────────────────────────────────
+
+enum Message {
+    Ensure(PathBuf),
+    Changed { root: PathBuf, paths: Vec<PathBuf> },
+    Forget(PathBuf),
+}
+
+pub struct IndexState {
+    /// Indexes ready to query, by canonical root.
+    roots: RwLock<HashMap<PathBuf, Arc<RwLock<RootIndex>>>>,
+    /// Roots being loaded or built.
+    pending: Mutex<HashSet<PathBuf>>,
+    /// Roots whose index changed since it was last saved.
+    unsaved: Mutex<HashSet<PathBuf>>,
+    tx: Mutex<mpsc::Sender<Message>>,
+}
+
+impl IndexState {
+    pub fn start(app: tauri::AppHandle) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || worker(app, rx));
+        IndexState {
+            roots: RwLock::new(HashMap::new()),
+            pending: Mutex::new(HashSet::new()),
+            unsaved: Mutex::new(HashSet::new()),
+            tx: Mutex::new(tx),
+        }
+    }
+
+    fn send(&self, message: Message) {
+        if let Ok(tx) = self.tx.lock() {
+            let _ = tx.send(message);
+        }
+    }
+
+    fn get(&self, root: &Path) -> Option<Arc<RwLock<RootIndex>>> {
+        self.roots.read().ok()?.get(root).cloned()
+    }
+}
+
+/// Applies `changes` to `root`'s index under a short write lock and queues
+/// it for saving if anything changed.
+fn commit(app: &tauri::AppHandle, root: &Path, index: &RwLock<RootIndex>, changes: Vec<Change>) {
+    let changed = match index.write() {
+        Ok(mut index) => {
+            index.apply(changes);
+            std::mem::take(&mut index.dirty)
+        }
+        Err(_) => false,
+    };
+    if changed {
+        if let Ok(mut unsaved) = app.state::<IndexState>().unsaved.lock() {
+            unsaved.insert(root.to_path_buf());
+        }
+    }
+}
+
+/// Loads `root`'s saved index, catches it up with whatever changed while
+/// the app wasn't running, and makes it available to queries.
+fn build(app: &tauri::AppHandle, root: &Path) {
+    let state = app.state::<IndexState>();
+    if state.get(root).is_none() {
+        let index = RwLock::new(load(app, root));
+        let token = tasks::CancelToken::default();
+        let changes = changes(&index, root, [root.to_path_buf()], &token);
+        commit(app, root, &index, changes);
+        if let Ok(mut roots) = state.roots.write() {
+            roots.insert(root.to_path_buf(), Arc::new(index));
+        }
+    }
+    if let Ok(mut pending) = state.pending.lock() {
+        pending.remove(root);
+    };
+}
+
+/// Applies a batch of changed paths to `root`'s index.
+fn apply(app: &tauri::AppHandle, root: &Path, paths: HashSet<PathBuf>) {
+    // Not loaded yet: the initial reconcile will see these changes anyway.
+    let Some(index) = app.state::<IndexState>().get(root) else { return };
+    let changes = changes(&index, root, paths, &tasks::CancelToken::default());
+    commit(app, root, &index, changes);
+}
+
+fn worker(app: tauri::AppHandle, rx: mpsc::Receiver<Message>) {
+    let mut changed: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+    // When the pending batch's first and latest events came in.
+    let mut batch: Option<(Instant, Instant)> = None;
+    let mut next_save: Option<Instant> = None;
+    let batch_due = |(first, latest): (Instant, Instant)| (latest + DEBOUNCE).min(first + MAX_DELAY);
+    loop {
+        let due = batch.map(batch_due).into_iter().chain(next_save).min();
+        let message = match due {
+            None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            Some(due) => rx.recv_timeout(due.saturating_duration_since(Instant::now())),
+        };
+        match message {
+            Ok(Message::Ensure(root)) => build(&app, &root),
+            Ok(Message::Changed { root, paths }) => {
+                changed.entry(root).or_default().extend(paths);
+                let now = Instant::now();
+                batch = Some(batch.map_or((now, now), |(first, _)| (first, now)));
+            }
+            Ok(Message::Forget(root)) => {
+                changed.remove(&root);
+                let state = app.state::<IndexState>();
+                if let Ok(mut roots) = state.roots.write() {
+                    roots.remove(&root);
+                }
+                if let Ok(mut pending) = state.pending.lock() {
+                    pending.remove(&root);
+                }
+                if let Ok(mut unsaved) = state.unsaved.lock() {
+                    unsaved.remove(&root);
+                }
+                if let Ok(file) = index_file(&app, &root) {
+                    let _ = std::fs::remove_file(file);
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+
+        let now = Instant::now();
+        if batch.is_some_and(|b| now >= batch_due(b)) {
+            for (root, paths) in changed.drain() {
+                apply(&app, &root, paths);
+            }
+            batch = None;
+        }
+        if next_save.is_some_and(|at| now >= at) {
+            flush(&app);
+            next_save = None;
+        }
+        let unsaved = app.state::<IndexState>().unsaved.lock().is_ok_and(|u| !u.is_empty());
+        if unsaved && next_save.is_none() {
+            next_save = Some(now + SAVE_INTERVAL);
+        }
+    }
+}
+
+/// Starts loading or building the index for `path` if it's a pinned folder
+/// that isn't indexed yet.
+pub fn ensure(app: &tauri::AppHandle, path: &Path) {
+    let Ok(root) = std::fs::canonicalize(path) else { return };
+    if !scope::pinned_folders(app).is_ok_and(|f| f.contains(&root)) {
+        return;
+    }
+    let state = app.state::<IndexState>();
+    if state.get(&root).is_some() {
+        return;
+    }
+    let newly_pending = state.pending.lock().is_ok_and(|mut p| p.insert(root.clone()));
+    if newly_pending {
+        state.send(Message::Ensure(root));
+    }
+}
+
+/// Queues paths reported by the watcher on `watch_path` for re-indexing.
+pub fn changed(app: &tauri::AppHandle, watch_path: &Path, paths: &[PathBuf]) {
+    let Ok(root) = std::fs::canonicalize(watch_path) else { return };
+    // Some backends report paths under the folder as it was given, others
+    // under its resolved location.
+    let paths: Vec<PathBuf> = paths.iter()
+        .filter_map(|p| {
+            if p.starts_with(&root) {
+                Some(p.clone())
+            } else {
+                p.strip_prefix(watch_path).ok().map(|rest| root.join(rest))
+            }
+        })
+        .collect();
+    if !paths.is_empty() {
+        app.state::<IndexState>().send(Message::Changed { root, paths });
+    }
+}
+
+/// Runs `f` over the live documents of `root`'s index, or returns `None`
+/// (and starts indexing) if it isn't ready yet.
+pub(crate) fn with_documents<T>(
+    app: &tauri::AppHandle,
+    root: &Path,
+    f: impl FnOnce(&[&Doc]) -> T,
+) -> error::Result<Option<T>> {
+    let Some(index) = app.state::<IndexState>().get(root) else {
+        ensure(app, root);
+        return Ok(None);
+    };
+    let index = index.read().map_err(PaddownError::internal)?;
+    let docs: Vec<&Doc> = index.docs.iter().flatten().collect();
+    Ok(Some(f(&docs)))
+}
+
+/// Drops the index of a folder that is no longer pinned.
+pub fn forget(app: &tauri::AppHandle, root: &Path) {
+    app.state::<IndexState>().send(Message::Forget(root.to_path_buf()));
+}
+
+// ─── Queries ─────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Debug)]
+pub struct IndexHit {
+    path: String,
+    title: String,
+    score: f64,
+    /// 1-based line of the snippet, if a query term was found in the file.
+    line: Option<usize>,
+    snippet: String,
+    /// `[start, end)` UTF-16 ranges within `snippet` that matched.
+    ranges: Vec<[usize; 2]>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct IndexResults {
+    hits: Vec<IndexHit>,
+    /// Some pinned folders are still being indexed; results may be missing.
+    indexing: bool,
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.encode_utf16().count()
+}
+
+/// The text around the first query term in `contents`, within its line.
+fn snippet(
+    contents: &str,
+    terms: &[String],
+    prefix: bool,
+) -> (Option<usize>, String, Vec<[usize; 2]>) {
+    let matches = |token: &str| {
+        terms.iter().enumerate().any(|(i, t)| {
+            token == t || (prefix && i == terms.len() - 1 && token.starts_with(t.as_str()))
+        })
+    };
+    let Some((first, _, _)) = tokens(contents).find(|(_, _, t)| matches(t)) else {
+        return (None, String::new(), Vec::new());
+    };
+
+    let line_start = contents[..first].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = contents[first..].find('\n').map_or(contents.len(), |i| first + i);
+    let lead: usize = contents[line_start..first]
+        .chars()
+        .rev()
+        .take(SNIPPET_LEAD)
+        .map(char::len_utf8)
+        .sum();
+    let start = first - lead;
+    let len: usize = contents[start..line_end]
+        .chars()
+        .take(SNIPPET_CHARS)
+        .map(char::len_utf8)
+        .sum();
+    let text = contents[start..start + len].trim_end_matches('\r');
+
+    let ranges = tokens(text)
+        .filter(|(_, _, t)| matches(t))
+        .map(|(s, e, _)| {
+            let s16 = utf16_len(&text[..s]);
+            [s16, s16 + utf16_len(&text[s..e])]
+        })
+        .collect();
+    let line = contents[..first].matches('\n').count() + 1;
+    (Some(line), text.to_string(), ranges)
+}
+
+fn query(app: &tauri::AppHandle, text: &str, limit: usize) -> error::Result<IndexResults> {
+    let terms: Vec<String> = tokens(text).map(|(_, _, t)| t).collect();
+    let prefix = !text.ends_with(char::is_whitespace);
+    let state = app.state::<IndexState>();
+
+    let mut indexing = false;
+    let mut scored: Vec<(f64, PathBuf, Doc)> = Vec::new();
+    // Nested pinned folders are indexed on their own too; search each file
+    // once, through the outermost folder.
+    for root in search::outermost(scope::pinned_folders(app)?) {
+        let Some(index) = state.get(&root) else {
+            ensure(app, &root);
+            indexing = true;
+            continue;
+        };
+        if terms.is_empty() {
+            continue;
+        }
+        let index = index.read().map_err(PaddownError::internal)?;
+        for (id, score) in index.score(&terms, prefix) {
+            if let Some(doc) = &index.docs[id as usize] {
+                scored.push((score, index.root.clone(), doc.clone()));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.truncate(limit);
+
+    let hits = scored.into_iter()
+        .map(|(score, root, doc)| {
+            let path = root.join(&doc.path);
+            let (line, snippet, ranges) = crate::read_text_file(&path)
+                .map(|f| snippet(&f.contents, &terms, prefix))
+                .unwrap_or((None, String::new(), Vec::new()));
+            IndexHit {
+                path: path.to_string_lossy().into_owned(),
+                title: doc.title,
+                score,
+                line,
+                snippet,
+                ranges,
+            }
+        })
+        .collect();
+    Ok(IndexResults { hits, indexing })
+}
+
+/// Ranked search over the indexed pinned folders. The last word also
+/// matches as a prefix unless the query ends in a space.
+#[tauri::command]
+pub async fn query_index(
+    app: tauri::AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> error::Result<IndexResults> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    tasks::blocking(move || self::query(&app, &query, limit)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        tokens(text).map(|(_, _, t)| t).collect()
+    }
+
+    /// An index of `files` (name and contents), written under a fresh
+    /// folder, as the worker would build it.
+    fn indexed(files: &[(&str, &str)]) -> (tempfile::TempDir, PathBuf, RwLock<RootIndex>) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        for (name, text) in files {
+            std::fs::write(root.join(name), text).unwrap();
+        }
+        let index = RwLock::new(RootIndex::new(&root));
+        refresh(&index, &root, [root.clone()]);
+        (dir, root, index)
+    }
+
+    fn refresh(index: &RwLock<RootIndex>, root: &Path, paths: impl IntoIterator<Item = PathBuf>) {
+        let changes = changes(index, root, paths, &tasks::CancelToken::default());
+        index.write().unwrap().apply(changes);
+    }
+
+    /// Document paths by descending score.
+    fn ranked(index: &RwLock<RootIndex>, query: &str, prefix: bool) -> Vec<String> {
+        let index = index.read().unwrap();
+        let mut scores = index.score(&words(query), prefix);
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores.into_iter()
+            .map(|(id, _)| index.docs[id as usize].as_ref().unwrap().path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn tokens_are_lowercased_alphanumeric_runs() {
+        let text = "Hello, W\u{F6}rld! x2-fast_car caf\u{E9}";
+        assert_eq!(words(text), ["hello", "w\u{F6}rld", "x2", "fast", "car", "caf\u{E9}"]);
+        let ranges: Vec<(usize, usize)> = tokens(text).map(|(s, e, _)| (s, e)).collect();
+        assert_eq!(ranges[1], (7, 13));
+        assert_eq!(&text[ranges[5].0..ranges[5].1], "caf\u{E9}");
+    }
+
+    #[test]
+    fn overlong_tokens_are_skipped() {
+        let long = "a".repeat(MAX_TOKEN_LEN + 1);
+        assert_eq!(words(&format!("short {} end", long)), ["short", "end"]);
+    }
+
+    #[test]
+    fn more_frequent_terms_rank_higher() {
+        let (_dir, _, index) = indexed(&[
+            ("once.md", "rust and other words here"),
+            ("twice.md", "rust and rust words here"),
+            ("none.md", "nothing relevant in this one"),
+        ]);
+        assert_eq!(ranked(&index, "rust", false), ["twice.md", "once.md"]);
+    }
+
+    #[test]
+    fn rare_terms_outweigh_common_ones() {
+        let (_dir, _, index) = indexed(&[
+            ("common.md", "apple apple banana"),
+            ("rare.md", "apple cherry banana"),
+            ("other.md", "apple banana"),
+        ]);
+        assert_eq!(ranked(&index, "apple cherry", false)[0], "rare.md");
+    }
+
+    #[test]
+    fn shorter_documents_rank_higher_for_the_same_frequency() {
+        let (_dir, _, index) = indexed(&[
+            ("long.md", "needle and a great many other words to pad this out"),
+            ("short.md", "needle here"),
+            ("none.md", "hay"),
+        ]);
+        assert_eq!(ranked(&index, "needle", false), ["short.md", "long.md"]);
+    }
+
+    #[test]
+    fn the_last_term_matches_as_a_prefix() {
+        let (_dir, _, index) = indexed(&[("a.md", "searching things"), ("b.md", "sea shells")]);
+        assert_eq!(ranked(&index, "searc", true), ["a.md"]);
+        assert!(ranked(&index, "searc", false).is_empty());
+    }
+
+    #[test]
+    fn updates_follow_the_disk() {
+        let (_dir, root, index) = indexed(&[("a.md", "alpha"), ("b.md", "beta")]);
+        std::fs::write(root.join("a.md"), "gamma and more").unwrap();
+        std::fs::remove_file(root.join("b.md")).unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/c.md"), "delta").unwrap();
+        std::fs::write(root.join("sub/skip.txt"), "delta").unwrap();
+
+        refresh(&index, &root, [root.join("a.md"), root.join("b.md"), root.join("sub")]);
+
+        assert!(ranked(&index, "alpha", false).is_empty());
+        assert!(ranked(&index, "beta", false).is_empty());
+        assert_eq!(ranked(&index, "gamma", false), ["a.md"]);
+        assert_eq!(ranked(&index, "delta", false), ["sub/c.md"]);
+
+        std::fs::remove_dir_all(root.join("sub")).unwrap();
+        refresh(&index, &root, [root.join("sub")]);
+        assert!(ranked(&index, "delta", false).is_empty());
+        assert_eq!(index.read().unwrap().live, 1);
+    }
+
+    #[test]
+    fn unchanged_files_are_not_reread() {
+        let (_dir, root, index) = indexed(&[("a.md", "alpha")]);
+        let changes = changes(&index, &root, [root.clone()], &tasks::CancelToken::default());
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn tombstones_are_compacted() {
+        let names: Vec<String> = (0..100).map(|n| format!("{}.md", n)).collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "word")).collect();
+        let (_dir, root, index) = indexed(&files);
+        for name in &names[..80] {
+            std::fs::remove_file(root.join(name)).unwrap();
+        }
+        refresh(&index, &root, [root.clone()]);
+
+        let index = index.read().unwrap();
+        assert_eq!(index.docs.len(), 20);
+        assert_eq!(index.postings["word"].len(), 20);
+    }
+
+    #[test]
+    fn snippets_centre_on_the_first_match() {
+        let terms = words("needle");
+        let text = "first line\nsome hay then the Needle and more\nlast";
+        let (line, snippet, ranges) = snippet(text, &terms, false);
+        assert_eq!(line, Some(2));
+        assert_eq!(snippet, "some hay then the Needle and more");
+        assert_eq!(ranges, [[18, 24]]);
+    }
+}
@@ -0,0 +1,232 @@
+//! Background work for long-running commands.
+//!
+//! Commands that touch the disk or the network run on tokio's blocking pool
+//! so the webview never waits on them. Callers that may want to abort a
+//! command pass a `task_id`; `cancel_task` flips the matching token and the
+//! worker bails out at its next checkpoint. Progress is reported through the
+//! `task-progress` event, keyed by the same id.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
+use tokio::sync::Notify;
+
+use crate::error::{self, PaddownError};
+
+#[derive(Default)]
+struct TokenInner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<TokenInner>);
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        self.0.notify.notify_waiters();
+    }
+
+    /// Resolves once the token is cancelled. Never resolves otherwise, so it
+    /// is meant to be raced against the actual work with `tokio::select!`.
+    pub async fn cancelled(&self) {
+        loop {
+            // Register interest before checking the flag so a cancel that
+            // lands in between still wakes us.
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+pub struct TaskState(Mutex<HashMap<String, CancelToken>>);
+
+impl TaskState {
+    pub fn new() -> Self {
+        TaskState(Mutex::new(HashMap::new()))
+    }
+
+    /// Makes `token` the one cancelled for `id`, cancelling the one it
+    /// replaces.
+    fn insert(&self, id: &str, token: &CancelToken) {
+        if let Ok(mut tasks) = self.0.lock() {
+            if let Some(previous) = tasks.insert(id.to_string(), token.clone()) {
+                previous.cancel();
+            }
+        }
+    }
+
+    /// Forgets `id`, unless a newer task has taken it over since `token`
+    /// was registered.
+    fn remove(&self, id: &str, token: &CancelToken) {
+        if let Ok(mut tasks) = self.0.lock() {
+            if tasks.get(id).is_some_and(|current| Arc::ptr_eq(&current.0, &token.0)) {
+                tasks.remove(id);
+            }
+        }
+    }
+
+    fn cancel(&self, id: &str) -> error::Result<()> {
+        let tasks = self.0.lock().map_err(PaddownError::internal)?;
+        if let Some(token) = tasks.get(id) {
+            token.cancel();
+        }
+        Ok(())
+    }
+}
+
+/// A registered task. Dropping it unregisters the id, so a late
+/// `cancel_task` for a finished task is a harmless no-op. A task that was
+/// superseded leaves the id to the one that replaced it.
+pub struct Task {
+    app: tauri::AppHandle,
+    id: Option<String>,
+    token: CancelToken,
+}
+
+impl Task {
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    /// Returns a progress reporter that can be moved onto a worker thread.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            app: self.app.clone(),
+            id: self.id.clone(),
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            self.app.state::<TaskState>().remove(id, &self.token);
+        }
+    }
+}
+
+/// Registers a task under `id` (if the caller supplied one). Re-using an id
+/// that is still running cancels the older task first.
+pub fn register(app: &tauri::AppHandle, id: Option<String>) -> Task {
+    let token = CancelToken::default();
+    if let Some(id) = &id {
+        app.state::<TaskState>().insert(id, &token);
+    }
+    Task {
+        app: app.clone(),
+        id,
+        token,
+    }
+}
+
+#[derive(serde::Serialize, Clone)]
+struct TaskProgress<'a> {
+    task_id: &'a str,
+    processed: u64,
+    total: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct Progress {
+    app: tauri::AppHandle,
+    id: Option<String>,
+}
+
+impl Progress {
+    pub fn report(&self, processed: u64, total: Option<u64>) {
+        if let Some(id) = &self.id {
+            let _ = self.app.emit_to("main", "task-progress", TaskProgress {
+                task_id: id,
+                processed,
+                total,
+            });
+        }
+    }
+}
+
+/// Runs `f` on the blocking thread pool and waits for it without tying up
+/// the async runtime.
+pub async fn blocking<T, F>(f: F) -> error::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| PaddownError::internal(format!("Background task failed: {}", e)))
+}
+
+#[tauri::command]
+pub fn cancel_task(app: tauri::AppHandle, task_id: String) -> error::Result<()> {
+    app.state::<TaskState>().cancel(&task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_reaches_the_registered_token() {
+        let state = TaskState::new();
+        let token = CancelToken::default();
+        state.insert("scan", &token);
+        state.cancel("other").unwrap();
+        assert!(!token.is_cancelled());
+        state.cancel("scan").unwrap();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn reusing_an_id_cancels_the_older_task() {
+        let state = TaskState::new();
+        let (older, newer) = (CancelToken::default(), CancelToken::default());
+        state.insert("scan", &older);
+        state.insert("scan", &newer);
+        assert!(older.is_cancelled());
+        assert!(!newer.is_cancelled());
+    }
+
+    #[test]
+    fn a_superseded_task_leaves_the_newer_one_registered() {
+        let state = TaskState::new();
+        let (older, newer) = (CancelToken::default(), CancelToken::default());
+        state.insert("scan", &older);
+        state.insert("scan", &newer);
+        state.remove("scan", &older);
+        state.cancel("scan").unwrap();
+        assert!(newer.is_cancelled());
+    }
+
+    #[test]
+    fn removing_forgets_the_id() {
+        let state = TaskState::new();
+        let token = CancelToken::default();
+        state.insert("scan", &token);
+        state.remove("scan", &token);
+        assert!(state.0.lock().unwrap().is_empty());
+        // A late cancel is a no-op.
+        state.cancel("scan").unwrap();
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn cancelled_resolves_once_cancelled() {
+        let token = CancelToken::default();
+        let waiter = token.clone();
+        let wait = std::thread::spawn(move || {
+            tauri::async_runtime::block_on(waiter.cancelled());
+        });
+        token.cancel();
+        wait.join().unwrap();
+    }
+}
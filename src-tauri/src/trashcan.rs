@@ -0,0 +1,501 @@
+//! Deleting to the system trash, with an in-app undo.
+//!
+//! On Linux entries go to the freedesktop.org trash: `$XDG_DATA_HOME/Trash`
+//! for the home filesystem, or the `.Trash/$uid` / `.Trash-$uid` directory
+//! at the top of the mount for files on other filesystems, each with a
+//! `.trashinfo` file so desktop trash views can list and restore them.
+//! Elsewhere the `trash` crate hands the entry to the OS.
+//!
+//! Every delete pushes a batch onto an undo stack; `undo_delete` puts the
+//! most recent batch back where it came from and reports entries that are
+//! no longer in the trash.
+
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::error::{self, PaddownError};
+use crate::tasks;
+
+/// Delete batches remembered for undo.
+const MAX_UNDO: usize = 20;
+
+pub struct TrashState(Mutex<Vec<Vec<platform::Trashed>>>);
+
+impl TrashState {
+    pub fn new() -> Self {
+        TrashState(Mutex::new(Vec::new()))
+    }
+}
+
+/// Moves `paths` to the trash as one undoable batch. Stops at the first
+/// failure; whatever was trashed before it can still be undone.
+pub fn trash_entries(app: &tauri::AppHandle, paths: &[&Path]) -> error::Result<()> {
+    let mut batch = Vec::new();
+    let mut result = Ok(());
+    for path in paths {
+        match platform::trash(path) {
+            Ok(trashed) => batch.push(trashed),
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+    if !batch.is_empty() {
+        let state = app.state::<TrashState>();
+        let mut stack = state.0.lock().map_err(PaddownError::internal)?;
+        stack.push(batch);
+        let excess = stack.len().saturating_sub(MAX_UNDO);
+        stack.drain(..excess);
+    }
+    result
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct MissingEntry {
+    pub path: String,
+    pub error: PaddownError,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+pub struct UndoReport {
+    /// Paths put back where they were.
+    pub restored: Vec<String>,
+    /// Entries that can't be restored any more, e.g. because the trash was
+    /// emptied. They are dropped from the undo stack.
+    pub missing: Vec<MissingEntry>,
+}
+
+/// Restores the batch on top of `stack`. An entry with something new in its
+/// place stops the undo and leaves the rest of the batch on the stack, so
+/// the user can move the blocker aside and retry; any other failure means
+/// the entry is gone for good and is reported instead, so one lost batch
+/// never blocks the older ones.
+fn undo_batch(stack: &mut Vec<Vec<platform::Trashed>>) -> error::Result<UndoReport> {
+    let mut report = UndoReport::default();
+    let Some(mut batch) = stack.pop() else { return Ok(report) };
+
+    while let Some(item) = batch.pop() {
+        let path = item.original().to_string_lossy().into_owned();
+        match platform::restore(&item) {
+            Ok(()) => report.restored.push(path),
+            Err(e @ PaddownError::AlreadyExists { .. }) => {
+                batch.push(item);
+                stack.push(batch);
+                return Err(e);
+            }
+            Err(error) => report.missing.push(MissingEntry { path, error }),
+        }
+    }
+    Ok(report)
+}
+
+/// Restores the most recently deleted batch.
+#[tauri::command]
+pub async fn undo_delete(app: tauri::AppHandle) -> error::Result<UndoReport> {
+    tasks::blocking(move || {
+        let state = app.state::<TrashState>();
+        let mut stack = state.0.lock().map_err(PaddownError::internal)?;
+        undo_batch(&mut stack)
+    })
+    .await?
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::io::Write;
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+
+    use crate::error::{self, PaddownError};
+
+    pub struct Trashed {
+        original: PathBuf,
+        file: PathBuf,
+        info: PathBuf,
+    }
+
+    impl Trashed {
+        pub fn original(&self) -> &Path {
+            &self.original
+        }
+    }
+
+    /// A trash directory and how `Path=` is written for entries in it.
+    struct TrashDir {
+        root: PathBuf,
+        /// Top of the mount for per-volume trashes, whose `Path=` keys are
+        /// relative to it; `None` for the home trash (absolute paths).
+        top: Option<PathBuf>,
+    }
+
+    fn home_trash() -> error::Result<TrashDir> {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+            .ok_or_else(|| PaddownError::Unsupported {
+                message: "Can't locate the trash: neither XDG_DATA_HOME nor HOME is set".into(),
+            })?;
+        Ok(TrashDir { root: data_home.join("Trash"), top: None })
+    }
+
+    /// The top directory of the mount `path` lives on.
+    fn mount_top(path: &Path) -> Option<PathBuf> {
+        let mut top = std::fs::canonicalize(path.parent()?).ok()?;
+        let dev = std::fs::metadata(&top).ok()?.dev();
+        while let Some(parent) = top.parent() {
+            if std::fs::metadata(parent).ok()?.dev() != dev {
+                break;
+            }
+            top = parent.to_path_buf();
+        }
+        Some(top)
+    }
+
+    /// The per-volume trash for `path`: `$top/.Trash/$uid` when an admin set
+    /// up a shared, sticky `.Trash`, otherwise `$top/.Trash-$uid`.
+    fn volume_trash(path: &Path) -> error::Result<TrashDir> {
+        let top = mount_top(path)
+            .ok_or_else(|| PaddownError::invalid_path(path, "Can't find the mount point"))?;
+        let uid = unsafe { libc::getuid() };
+
+        let shared = top.join(".Trash");
+        let usable_shared = std::fs::symlink_metadata(&shared)
+            .is_ok_and(|m| m.is_dir() && m.permissions().mode() & 0o1000 != 0);
+        let root = if usable_shared {
+            shared.join(uid.to_string())
+        } else {
+            top.join(format!(".Trash-{}", uid))
+        };
+        Ok(TrashDir { root, top: Some(top) })
+    }
+
+    fn ensure_dirs(trash: &TrashDir) -> std::io::Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true).mode(0o700);
+        builder.create(trash.root.join("files"))?;
+        builder.create(trash.root.join("info"))?;
+        // Refuse a trash someone else planted for us.
+        let metadata = std::fs::symlink_metadata(&trash.root)?;
+        if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "trash directory is not owned by the current user",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Percent-encodes a path for `Path=`, keeping `/` and unreserved
+    /// characters as they are.
+    fn encode_path(path: &Path) -> String {
+        use std::os::unix::ffi::OsStrExt;
+        let mut out = String::new();
+        for &b in path.as_os_str().as_bytes() {
+            if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+                out.push(b as char);
+            } else {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        }
+        out
+    }
+
+    /// Local time as `YYYY-MM-DDThh:mm:ss`, which is what the spec asks
+    /// `DeletionDate` to be.
+    fn deletion_date() -> String {
+        let now = unsafe { libc::time(std::ptr::null_mut()) };
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        unsafe { libc::localtime_r(&now, &mut tm) };
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+
+    /// `notes.md`, `notes.2.md`, `notes.3.md`, …
+    fn candidate_name(name: &str, n: u32) -> String {
+        if n == 1 {
+            return name.to_string();
+        }
+        match name.rfind('.') {
+            Some(dot) if dot > 0 => format!("{}.{}{}", &name[..dot], n, &name[dot..]),
+            _ => format!("{}.{}", name, n),
+        }
+    }
+
+    /// Claims a unique name by creating its `.trashinfo` exclusively, then
+    /// moves the entry in. The info file is removed again if the move fails.
+    fn trash_into(trash: &TrashDir, path: &Path) -> std::io::Result<Trashed> {
+        ensure_dirs(trash)?;
+        let name = path.file_name()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no file name"))?
+            .to_string_lossy()
+            .into_owned();
+        let original = std::fs::canonicalize(path.parent().unwrap_or(Path::new("/")))?
+            .join(&name);
+        let key = match &trash.top {
+            Some(top) => original.strip_prefix(top).unwrap_or(&original).to_path_buf(),
+            None => original.clone(),
+        };
+
+        for n in 1.. {
+            let candidate = candidate_name(&name, n);
+            let file = trash.root.join("files").join(&candidate);
+            let info = trash.root.join("info").join(format!("{}.trashinfo", candidate));
+            if std::fs::symlink_metadata(&file).is_ok() {
+                continue;
+            }
+            let mut info_file = match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info)
+            {
+                Ok(f) => f,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            let written = write!(
+                info_file,
+                "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                encode_path(&key),
+                deletion_date()
+            )
+            .and_then(|_| info_file.sync_all())
+            .and_then(|_| std::fs::rename(path, &file));
+            if let Err(e) = written {
+                let _ = std::fs::remove_file(&info);
+                return Err(e);
+            }
+            return Ok(Trashed { original, file, info });
+        }
+        unreachable!()
+    }
+
+    pub fn trash(path: &Path) -> error::Result<Trashed> {
+        std::fs::symlink_metadata(path).map_err(|e| PaddownError::io(path, e))?;
+        match trash_into(&home_trash()?, path) {
+            // The home trash is on another filesystem; use the one at the
+            // top of the file's own mount instead of copying across.
+            Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+                trash_into(&volume_trash(path)?, path).map_err(|e| PaddownError::io(path, e))
+            }
+            other => other.map_err(|e| PaddownError::io(path, e)),
+        }
+    }
+
+    pub fn restore(item: &Trashed) -> error::Result<()> {
+        if std::fs::symlink_metadata(&item.original).is_ok() {
+            return Err(PaddownError::AlreadyExists {
+                path: item.original.to_string_lossy().into_owned(),
+            });
+        }
+        if let Some(parent) = item.original.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| PaddownError::io(parent, e))?;
+        }
+        std::fs::rename(&item.file, &item.original)
+            .map_err(|e| PaddownError::io(&item.original, e))?;
+        let _ = std::fs::remove_file(&item.info);
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    use crate::error::{self, PaddownError};
+
+    pub struct Trashed {
+        original: PathBuf,
+    }
+
+    impl Trashed {
+        pub fn original(&self) -> &Path {
+            &self.original
+        }
+    }
+
+    pub fn trash(path: &Path) -> error::Result<Trashed> {
+        // The trash reports where items came from without the `\\?\`
+        // prefix, so `restore` can only find them by the plain path.
+        let original = std::fs::canonicalize(path).map_err(|e| PaddownError::io(path, e))?;
+        let original = crate::plain_path(original);
+        trash::delete(&original).map_err(|e| PaddownError::Io {
+            kind: "Trash".into(),
+            path: original.to_string_lossy().into_owned(),
+            message: e.to_string(),
+        })?;
+        Ok(Trashed { original })
+    }
+
+    /// Finds the newest trash entry that came from the original path and
+    /// asks the OS to put it back.
+    #[cfg(windows)]
+    pub fn restore(item: &Trashed) -> error::Result<()> {
+        let path = || item.original.to_string_lossy().into_owned();
+        let entry = trash::os_limited::list()
+            .map_err(PaddownError::internal)?
+            .into_iter()
+            .filter(|e| crate::plain_path(e.original_path()) == item.original)
+            .max_by_key(|e| e.time_deleted)
+            .ok_or_else(|| PaddownError::NotFound { path: path() })?;
+        trash::os_limited::restore_all([entry]).map_err(|e| match e {
+            trash::Error::RestoreCollision { .. } => PaddownError::AlreadyExists { path: path() },
+            e => PaddownError::internal(e),
+        })
+    }
+
+    /// macOS offers no API to find where an item landed in the Trash, so
+    /// there's nothing reliable to restore from.
+    #[cfg(not(windows))]
+    pub fn restore(item: &Trashed) -> error::Result<()> {
+        Err(PaddownError::Unsupported {
+            message: format!(
+                "{} is in the Trash; restore it from there with Put Back",
+                item.original.display()
+            ),
+        })
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Tests share `XDG_DATA_HOME`, so they take turns.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    struct Sandbox {
+        _guard: std::sync::MutexGuard<'static, ()>,
+        _dir: tempfile::TempDir,
+        trash: PathBuf,
+        docs: PathBuf,
+    }
+
+    /// A fresh home trash and a folder of documents on the same filesystem.
+    fn sandbox() -> Sandbox {
+        let guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let docs = root.join("docs");
+        std::fs::create_dir(&docs).unwrap();
+        std::env::set_var("XDG_DATA_HOME", root.join("data"));
+        Sandbox { _guard: guard, _dir: dir, trash: root.join("data/Trash"), docs }
+    }
+
+    #[test]
+    fn trashinfo_records_the_encoded_path_and_date() {
+        let sandbox = sandbox();
+        let path = sandbox.docs.join("my notes 100%.md");
+        std::fs::write(&path, "text").unwrap();
+
+        platform::trash(&path).unwrap();
+
+        assert!(!path.exists());
+        let trashed = sandbox.trash.join("files/my notes 100%.md");
+        assert_eq!(std::fs::read_to_string(trashed).unwrap(), "text");
+        let info = sandbox.trash.join("info/my notes 100%.md.trashinfo");
+        let info = std::fs::read_to_string(info).unwrap();
+        let mut lines = info.lines();
+        assert_eq!(lines.next(), Some("[Trash Info]"));
+        let expected = format!("Path={}/my%20notes%20100%25.md", sandbox.docs.display());
+        assert_eq!(lines.next(), Some(expected.as_str()));
+        let date = lines.next().unwrap().strip_prefix("DeletionDate=").unwrap();
+        assert_eq!(date.len(), "2024-01-31T12:00:00".len());
+        assert!(date.chars().enumerate().all(|(i, c)| match i {
+            4 | 7 => c == '-',
+            10 => c == 'T',
+            13 | 16 => c == ':',
+            _ => c.is_ascii_digit(),
+        }));
+    }
+
+    #[test]
+    fn same_names_get_numbered_in_the_trash() {
+        let sandbox = sandbox();
+        for folder in ["a", "b", "c"] {
+            let dir = sandbox.docs.join(folder);
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("notes.md"), folder).unwrap();
+            platform::trash(&dir.join("notes.md")).unwrap();
+        }
+
+        let files = sandbox.trash.join("files");
+        assert_eq!(std::fs::read_to_string(files.join("notes.md")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(files.join("notes.2.md")).unwrap(), "b");
+        assert_eq!(std::fs::read_to_string(files.join("notes.3.md")).unwrap(), "c");
+        assert!(sandbox.trash.join("info/notes.3.md.trashinfo").exists());
+    }
+
+    #[test]
+    fn undo_puts_the_batch_back() {
+        let sandbox = sandbox();
+        let file = sandbox.docs.join("notes.md");
+        let folder = sandbox.docs.join("folder");
+        std::fs::write(&file, "text").unwrap();
+        std::fs::create_dir(&folder).unwrap();
+        std::fs::write(folder.join("inner.md"), "inner").unwrap();
+
+        let batch = vec![platform::trash(&file).unwrap(), platform::trash(&folder).unwrap()];
+        let mut stack = vec![batch];
+        let report = undo_batch(&mut stack).unwrap();
+
+        assert_eq!(report.restored.len(), 2);
+        assert!(report.missing.is_empty());
+        assert!(stack.is_empty());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "text");
+        assert_eq!(std::fs::read_to_string(folder.join("inner.md")).unwrap(), "inner");
+        let info = std::fs::read_dir(sandbox.trash.join("info")).unwrap();
+        assert_eq!(info.count(), 0);
+    }
+
+    #[test]
+    fn entries_gone_from_the_trash_are_reported_and_dropped() {
+        let sandbox = sandbox();
+        let file = sandbox.docs.join("notes.md");
+        std::fs::write(&file, "text").unwrap();
+        let older = sandbox.docs.join("older.md");
+        std::fs::write(&older, "older").unwrap();
+
+        let mut stack = vec![vec![platform::trash(&older).unwrap()]];
+        stack.push(vec![platform::trash(&file).unwrap()]);
+        // Emptying the trash.
+        std::fs::remove_file(sandbox.trash.join("files/notes.md")).unwrap();
+
+        let report = undo_batch(&mut stack).unwrap();
+        assert!(report.restored.is_empty());
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].path, file.to_string_lossy());
+
+        // The older batch is next in line.
+        let report = undo_batch(&mut stack).unwrap();
+        assert_eq!(report.restored, [older.to_string_lossy()]);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn undo_onto_a_new_file_keeps_the_batch() {
+        let sandbox = sandbox();
+        let file = sandbox.docs.join("notes.md");
+        std::fs::write(&file, "old").unwrap();
+        let mut stack = vec![vec![platform::trash(&file).unwrap()]];
+        std::fs::write(&file, "new").unwrap();
+
+        let result = undo_batch(&mut stack);
+        assert!(matches!(result, Err(PaddownError::AlreadyExists { .. })));
+        assert_eq!(stack.len(), 1);
+
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(undo_batch(&mut stack).unwrap().restored.len(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "old");
+    }
+}
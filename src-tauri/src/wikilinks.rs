@@ -0,0 +1,497 @@
+//! `[[wiki-links]]` between the notes of a pinned folder.
+//!
+//! Links are parsed (by `markdown::parse`) when the search index reads a
+//! file and stored with its document, so the graph follows the folder
+//! watcher without a second walk or a second copy of the file list. Targets
+//! are resolved when queried, which keeps the stored links valid however the
+//! files around them move:
+//!
+//! - `[[Note]]` matches a note named `Note.md` anywhere in the folder, case
+//!   insensitively, preferring one next to the linking file and then the
+//!   one closest to the root.
+//! - `[[sub/Note]]` matches by path, relative to the linking file first and
+//!   then to the root, and otherwise any note whose path ends that way.
+//! - Anything else that names an existing file, such as `![[diagram.png]]`,
+//!   resolves to that file as an attachment.
+//! - `[[#Heading]]` links to the file it is in.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, PaddownError};
+use crate::markdown::{self, WikiLink};
+use crate::search_index::{self, Doc};
+use crate::{scope, tasks, workspace};
+
+// ─── Resolving ───────────────────────────────────────────────
+
+fn strip_markdown_extension(path: &str) -> &str {
+    match path.rsplit_once('.') {
+        Some((stem, _)) if workspace::is_markdown(Path::new(path)) => stem,
+        _ => path,
+    }
+}
+
+fn parent_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// `rel` joined onto `dir`, both `/`-separated and relative to the root.
+/// `None` if it climbs out of the root.
+fn join_relative(dir: &str, rel: &str) -> Option<String> {
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in rel.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Looks up link targets among the documents of one pinned folder.
+struct Resolver<'a> {
+    root: &'a Path,
+    /// Lowercased path without its Markdown extension.
+    by_path: HashMap<String, &'a str>,
+    /// Lowercased file stem.
+    by_name: HashMap<String, Vec<&'a str>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(root: &'a Path, docs: &[&'a Doc]) -> Self {
+        let mut by_path = HashMap::new();
+        let mut by_name: HashMap<String, Vec<&str>> = HashMap::new();
+        for doc in docs {
+            let key = strip_markdown_extension(&doc.path).to_lowercase();
+            let name = key.rsplit('/').next().unwrap_or(&key).to_string();
+            by_name.entry(name).or_default().push(&doc.path);
+            by_path.insert(key, doc.path.as_str());
+        }
+        Resolver { root, by_path, by_name }
+    }
+
+    /// The root-relative path `target` points to from `source`.
+    fn resolve(&self, source: &str, target: &str) -> Option<String> {
+        let target = target.replace('\\', "/");
+        let target = target.trim_start_matches('/');
+        if target.is_empty() {
+            return Some(source.to_string());
+        }
+        let dir = parent_of(source);
+        let wanted = strip_markdown_extension(target).to_lowercase();
+
+        let by_path = join_relative(dir, &wanted)
+            .into_iter()
+            .chain(join_relative("", &wanted))
+            .find_map(|key| self.by_path.get(&key));
+        if let Some(found) = by_path {
+            return Some(found.to_string());
+        }
+
+        let closest = |paths: &mut dyn Iterator<Item = &'a str>| {
+            paths.min_by_key(|p| (parent_of(p) != dir, p.matches('/').count(), *p))
+                .map(str::to_string)
+        };
+        let found = if wanted.contains('/') {
+            let suffix = format!("/{}", wanted);
+            closest(&mut self.by_path.iter()
+                .filter(|(key, _)| key.ends_with(&suffix))
+                .map(|(_, path)| *path))
+        } else {
+            self.by_name.get(&wanted).and_then(|paths| closest(&mut paths.iter().copied()))
+        };
+        if found.is_some() {
+            return found;
+        }
+
+        // Not a note; perhaps an attachment such as an image or a PDF.
+        [join_relative(dir, target), join_relative("", target)]
+            .into_iter()
+            .flatten()
+            .find(|rel| !workspace::is_markdown(Path::new(rel)) && self.root.join(rel).is_file())
+    }
+}
+
+// ─── Commands ────────────────────────────────────────────────
+
+#[derive(serde::Serialize, Debug)]
+pub struct LinkResults<T> {
+    links: Vec<T>,
+    /// The pinned folder is still being indexed; results may be missing.
+    indexing: bool,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct OutgoingLink {
+    #[serde(flatten)]
+    link: WikiLink,
+    /// The file the link points to, if it resolves.
+    resolved: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct LinkingFile {
+    /// The file containing the link.
+    source: String,
+    title: String,
+    #[serde(flatten)]
+    link: WikiLink,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct GraphNode {
+    id: String,
+    title: String,
+    /// `"note"`, `"attachment"`, or `"missing"` for unresolved targets.
+    kind: &'static str,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct GraphEdge {
+    source: String,
+    target: String,
+    /// Links from `source` to `target`.
+    count: u32,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct LinkGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    indexing: bool,
+}
+
+/// The indexes to read for a graph query, with the root-relative folder to
+/// limit it to: `folder` (which must be inside a pinned folder), or every
+/// pinned folder.
+fn graph_roots(
+    app: &tauri::AppHandle,
+    folder: Option<&str>,
+) -> error::Result<Vec<(PathBuf, String)>> {
+    let Some(folder) = folder else {
+        return Ok(scope::pinned_folders(app)?.into_iter().map(|r| (r, String::new())).collect());
+    };
+    let folder = Path::new(folder);
+    scope::check_pinned_dir(app, folder)?;
+    let folder = std::fs::canonicalize(folder).map_err(|e| PaddownError::io(folder, e))?;
+    let root = scope::pinned_root(app, &folder)?
+        .ok_or_else(|| PaddownError::invalid_path(&folder, "Not inside a pinned folder"))?;
+    let prefix = folder.strip_prefix(&root)
+        .unwrap_or(Path::new(""))
+        .to_string_lossy()
+        .replace('\\', "/");
+    Ok(vec![(root, prefix)])
+}
+
+fn in_folder(path: &str, prefix: &str) -> bool {
+    prefix.is_empty() || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn absolute(root: &Path, rel: &str) -> String {
+    root.join(rel).to_string_lossy().into_owned()
+}
+
+fn outgoing(
+    app: &tauri::AppHandle,
+    path: &Path,
+    contents: Option<String>,
+) -> error::Result<LinkResults<OutgoingLink>> {
+    scope::check(app, path)?;
+    let contents = match contents {
+        Some(contents) => contents,
+        None => crate::read_text_file(path)?.contents,
+    };
+    let links = markdown::parse(&contents).wiki_links;
+    let path = std::fs::canonicalize(path).map_err(|e| PaddownError::io(path, e))?;
+
+    let resolved = match scope::pinned_root(app, &path)? {
+        Some(root) => {
+            let source = path.strip_prefix(&root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            search_index::with_documents(app, &root, |docs| {
+                let resolver = Resolver::new(&root, docs);
+                links.iter()
+                    .map(|l| resolver.resolve(&source, &l.target).map(|rel| absolute(&root, &rel)))
+                    .collect::<Vec<_>>()
+            })?
+        }
+        // A file opened on its own has no folder to resolve against.
+        None => Some(vec![None; links.len()]),
+    };
+    let indexing = resolved.is_none();
+    let resolved = resolved.unwrap_or_else(|| vec![None; links.len()]);
+    let links = links.into_iter()
+        .zip(resolved)
+        .map(|(link, resolved)| OutgoingLink { link, resolved })
+        .collect();
+    Ok(LinkResults { links, indexing })
+}
+
+/// The wiki-links in a file and where each one points. `contents`, if
+/// given, is used instead of what's on disk, e.g. for unsaved edits.
+#[tauri::command]
+pub async fn outgoing_links(
+    app: tauri::AppHandle,
+    path: String,
+    contents: Option<String>,
+) -> error::Result<LinkResults<OutgoingLink>> {
+    tasks::blocking(move || outgoing(&app, Path::new(&path), contents)).await?
+}
+
+fn linking_files(app: &tauri::AppHandle, path: &Path) -> error::Result<LinkResults<LinkingFile>> {
+    scope::check(app, path)?;
+    let path = std::fs::canonicalize(path).map_err(|e| PaddownError::io(path, e))?;
+    let Some(root) = scope::pinned_root(app, &path)? else {
+        return Ok(LinkResults { links: Vec::new(), indexing: false });
+    };
+    let target = path.strip_prefix(&root)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    let links = search_index::with_documents(app, &root, |docs| {
+        let resolver = Resolver::new(&root, docs);
+        let mut links = Vec::new();
+        for doc in docs.iter().filter(|d| d.path != target) {
+            for link in &doc.links {
+                if resolver.resolve(&doc.path, &link.target).as_deref() == Some(target.as_str()) {
+                    links.push(LinkingFile {
+                        source: absolute(&root, &doc.path),
+                        title: doc.title.clone(),
+                        link: link.clone(),
+                    });
+                }
+            }
+        }
+        links.sort_by(|a, b| (&a.source, a.link.line).cmp(&(&b.source, b.link.line)));
+        links
+    })?;
+    let indexing = links.is_none();
+    Ok(LinkResults { links: links.unwrap_or_default(), indexing })
+}
+
+/// The wiki-links in other files that point to `path`.
+#[tauri::command]
+pub async fn backlinks(
+    app: tauri::AppHandle,
+    path: String,
+) -> error::Result<LinkResults<LinkingFile>> {
+    tasks::blocking(move || linking_files(&app, Path::new(&path))).await?
+}
+
+fn unresolved(
+    app: &tauri::AppHandle,
+    folder: Option<&str>,
+) -> error::Result<LinkResults<LinkingFile>> {
+    let mut links = Vec::new();
+    let mut indexing = false;
+    for (root, prefix) in graph_roots(app, folder)? {
+        let found = search_index::with_documents(app, &root, |docs| {
+            let resolver = Resolver::new(&root, docs);
+            let mut found = Vec::new();
+            for doc in docs.iter().filter(|d| in_folder(&d.path, &prefix)) {
+                for link in &doc.links {
+                    if resolver.resolve(&doc.path, &link.target).is_none() {
+                        found.push(LinkingFile {
+                            source: absolute(&root, &doc.path),
+                            title: doc.title.clone(),
+                            link: link.clone(),
+                        });
+                    }
+                }
+            }
+            found
+        })?;
+        match found {
+            Some(found) => links.extend(found),
+            None => indexing = true,
+        }
+    }
+    links.sort_by(|a, b| (&a.source, a.link.line).cmp(&(&b.source, b.link.line)));
+    Ok(LinkResults { links, indexing })
+}
+
+/// Wiki-links that don't point to any file, in `folder` or every pinned
+/// folder.
+#[tauri::command]
+pub async fn unresolved_links(
+    app: tauri::AppHandle,
+    folder: Option<String>,
+) -> error::Result<LinkResults<LinkingFile>> {
+    tasks::blocking(move || unresolved(&app, folder.as_deref())).await?
+}
+
+fn graph(app: &tauri::AppHandle, folder: Option<&str>) -> error::Result<LinkGraph> {
+    let mut graph = LinkGraph { nodes: Vec::new(), edges: Vec::new(), indexing: false };
+    for (root, prefix) in graph_roots(app, folder)? {
+        let built = search_index::with_documents(app, &root, |docs| {
+            let resolver = Resolver::new(&root, docs);
+            let docs: Vec<&Doc> = docs.iter()
+                .copied()
+                .filter(|d| in_folder(&d.path, &prefix))
+                .collect();
+            let mut nodes: Vec<GraphNode> = docs.iter()
+                .map(|doc| GraphNode {
+                    id: absolute(&root, &doc.path),
+                    title: doc.title.clone(),
+                    kind: "note",
+                })
+                .collect();
+            let mut seen: HashSet<String> = nodes.iter().map(|n| n.id.clone()).collect();
+            let mut counts: HashMap<(String, String), u32> = HashMap::new();
+
+            for doc in &docs {
+                let source = absolute(&root, &doc.path);
+                for link in &doc.links {
+                    let target = match resolver.resolve(&doc.path, &link.target) {
+                        Some(rel) if rel == doc.path => continue,
+                        Some(rel) => {
+                            let id = absolute(&root, &rel);
+                            if seen.insert(id.clone()) {
+                                let title = rel.rsplit('/').next().unwrap_or(&rel).to_string();
+                                let kind = if workspace::is_markdown(Path::new(&rel)) {
+                                    "note"
+                                } else {
+                                    "attachment"
+                                };
+                                nodes.push(GraphNode { id: id.clone(), title, kind });
+                            }
+                            id
+                        }
+                        // Missing notes are keyed by name, so every link to
+                        // the same one meets at a single node.
+                        None => {
+                            let name = link.target.to_lowercase();
+                            let id = format!("missing:{}:{}", root.display(), name);
+                            if seen.insert(id.clone()) {
+                                nodes.push(GraphNode {
+                                    id: id.clone(),
+                                    title: link.target.clone(),
+                                    kind: "missing",
+                                });
+                            }
+                            id
+                        }
+                    };
+                    *counts.entry((source.clone(), target)).or_default() += 1;
+                }
+            }
+            let mut edges: Vec<GraphEdge> = counts.into_iter()
+                .map(|((source, target), count)| GraphEdge { source, target, count })
+                .collect();
+            edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+            (nodes, edges)
+        })?;
+        match built {
+            Some((nodes, edges)) => {
+                graph.nodes.extend(nodes);
+                graph.edges.extend(edges);
+            }
+            None => graph.indexing = true,
+        }
+    }
+    Ok(graph)
+}
+
+/// Every note in `folder` (or every pinned folder) as graph nodes, with an
+/// edge for each pair of linked files.
+#[tauri::command]
+pub async fn link_graph(
+    app: tauri::AppHandle,
+    folder: Option<String>,
+) -> error::Result<LinkGraph> {
+    tasks::blocking(move || graph(&app, folder.as_deref())).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(path: &str) -> Doc {
+        serde_json::from_value(serde_json::json!({
+            "path": path,
+            "title": path,
+            "mtime_ns": 0,
+            "size": 0,
+            "len": 0,
+            "links": [],
+        }))
+        .unwrap()
+    }
+
+    const NOTES: &[&str] = &[
+        "Note.md",
+        "index.md",
+        "sub/Note.md",
+        "sub/deep/Other.markdown",
+        "a/b/Target.md",
+        "x/b/Target.md",
+    ];
+
+    fn check(root: &Path, cases: &[(&str, &str, Option<&str>)]) {
+        let docs: Vec<Doc> = NOTES.iter().map(|p| doc(p)).collect();
+        let refs: Vec<&Doc> = docs.iter().collect();
+        let resolver = Resolver::new(root, &refs);
+        for (source, target, expected) in cases {
+            let found = resolver.resolve(source, target);
+            assert_eq!(found.as_deref(), *expected, "[[{}]] from {}", target, source);
+        }
+    }
+
+    #[test]
+    fn names_resolve_next_to_the_source_then_nearest_the_root() {
+        check(Path::new("/notes"), &[
+            ("index.md", "Note", Some("Note.md")),
+            ("index.md", "NOTE", Some("Note.md")),
+            ("sub/deep/page.md", "note", Some("Note.md")),
+            ("sub/page.md", "Note", Some("sub/Note.md")),
+            ("index.md", "Other", Some("sub/deep/Other.markdown")),
+            ("index.md", "Other.markdown", Some("sub/deep/Other.markdown")),
+            ("index.md", "Nowhere", None),
+        ]);
+    }
+
+    #[test]
+    fn paths_resolve_relative_then_from_the_root_then_by_suffix() {
+        check(Path::new("/notes"), &[
+            ("index.md", "sub/Note", Some("sub/Note.md")),
+            ("sub/deep/page.md", "../Note.md", Some("sub/Note.md")),
+            ("sub/deep/page.md", "/Note", Some("Note.md")),
+            ("x/page.md", "b/Target", Some("x/b/Target.md")),
+            ("index.md", "b/Target", Some("a/b/Target.md")),
+            ("index.md", "deep/other", Some("sub/deep/Other.markdown")),
+            ("index.md", "sub\\Note", Some("sub/Note.md")),
+        ]);
+    }
+
+    #[test]
+    fn heading_links_point_at_their_own_file() {
+        check(Path::new("/notes"), &[("sub/page.md", "", Some("sub/page.md"))]);
+    }
+
+    #[test]
+    fn existing_files_resolve_as_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("sub/img")).unwrap();
+        std::fs::write(dir.path().join("sub/img/diagram.png"), "png").unwrap();
+        std::fs::write(dir.path().join("top.pdf"), "pdf").unwrap();
+        check(dir.path(), &[
+            ("sub/page.md", "img/diagram.png", Some("sub/img/diagram.png")),
+            ("index.md", "top.pdf", Some("top.pdf")),
+            ("index.md", "missing.png", None),
+        ]);
+    }
+
+    #[test]
+    fn relative_joins_stay_inside_the_root() {
+        assert_eq!(join_relative("a/b", "../c").as_deref(), Some("a/c"));
+        assert_eq!(join_relative("", "./x/y").as_deref(), Some("x/y"));
+        assert_eq!(join_relative("a", "../../x"), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Walking the pinned folders for workspace-wide features.
+//!
+//! Uses the same rules as the sidebar tree: hidden entries and symlinks are
+//! skipped (the latter so a link cycle can't recurse forever) and recursion
+//! stops at `MAX_DEPTH`.
+
+use std::path::{Path, PathBuf};
+
+use crate::tasks;
+
+pub const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+pub const MAX_DEPTH: u32 = 20;
+
+/// Dot-files on Unix, entries with the hidden attribute on Windows.
+pub fn is_hidden(entry: &std::fs::DirEntry) -> bool {
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        entry.metadata().is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+    }
+    #[cfg(not(windows))]
+    {
+        entry.file_name().to_string_lossy().starts_with('.')
+    }
+}
+
+/// Whether any entry from `root` down to `path` is hidden, for paths that
+/// come from watcher events rather than from a walk.
+pub fn is_hidden_path(root: &Path, path: &Path) -> bool {
+    let Ok(rest) = path.strip_prefix(root) else { return false };
+    let mut current = root.to_path_buf();
+    rest.components().any(|component| {
+        current.push(component);
+        #[cfg(windows)]
+        {
+            use std::os::windows::fs::MetadataExt;
+            const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+            std::fs::symlink_metadata(&current)
+                .is_ok_and(|m| m.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+        }
+        #[cfg(not(windows))]
+        {
+            component.as_os_str().to_string_lossy().starts_with('.')
+        }
+    })
+}
+
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| MARKDOWN_EXTENSIONS.iter().any(|m| m.eq_ignore_ascii_case(e)))
+}
+
+/// Whether `path` has one of `extensions` (compared case-insensitively,
+/// without the dot).
+pub fn has_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(e)))
+}
+
+/// Calls `visit` for every regular file under `root`, stopping early once
+/// `token` is cancelled.
+pub fn walk_files(root: &Path, token: &tasks::CancelToken, visit: &mut dyn FnMut(&Path)) {
+    fn walk(dir: &Path, depth: u32, token: &tasks::CancelToken, visit: &mut dyn FnMut(&Path)) {
+        if depth > MAX_DEPTH || token.is_cancelled() {
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            if token.is_cancelled() {
+                return;
+            }
+            if is_hidden(&entry) {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else { continue };
+            if file_type.is_dir() {
+                walk(&entry.path(), depth + 1, token, visit);
+            } else if file_type.is_file() {
+                visit(&entry.path());
+            }
+        }
+    }
+    walk(root, 0, token, visit);
+}
+
+/// Every Markdown file under `root`.
+pub fn markdown_files(root: &Path, token: &tasks::CancelToken) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    walk_files(root, token, &mut |path| {
+        if is_markdown(path) {
+            files.push(path.to_path_buf());
+        }
+    });
+    files
+}
@@ -4,7 +4,7 @@
  * wires menu/toolbar actions, and handles window events.
  */
 document.addEventListener('DOMContentLoaded', async () => {
-  const { renderer, editor, fileIO, tabs, views, menus, contextMenu, find, toolbar, settings, exportHtml, updater, recovery, welcome, sidebar } = window.Paddown;
+  const { renderer, editor, fileIO, tabs, views, menus, contextMenu, find, toolbar, settings, exportHtml, exportPdf, updater, recovery, welcome, sidebar } = window.Paddown;
 
   // ─── Initialize Modules ─────────────────────────────────────
 
@@ -76,7 +76,7 @@ document.addEventListener('DOMContentLoaded', async () => {
 
       const active = tabs.getActiveTab();
       if (tabs.isTabBlankUntitled(active)) {
-        tabs.loadIntoTab(active.id, result.content, result.filePath, result.lineEnding, result.mtime);
+        tabs.loadIntoTab(active.id, result.content, result.filePath, result.lineEnding, result.mtime, result.fingerprint);
         editor.render();
         sidebar.updateActiveHighlight();
         return;
@@ -87,7 +87,8 @@ document.addEventListener('DOMContentLoaded', async () => {
         filePath: result.filePath,
         content: result.content,
         lineEnding: result.lineEnding,
-        lastModified: result.mtime
+        lastModified: result.mtime,
+        fingerprint: result.fingerprint
       });
     } catch (err) {
       console.error('Open failed:', err);
@@ -98,11 +99,19 @@ document.addEventListener('DOMContentLoaded', async () => {
     if (!fileIO.isDesktop()) return;
     const tab = tabs.getActiveTab();
     if (!tab) return;
+    const ta = tabs.getActiveTextarea();
     try {
-      const ta = tabs.getActiveTextarea();
-      const result = await fileIO.saveFile(ta.value, tab.filePath, tab.lineEnding);
+      let result;
+      try {
+        result = await fileIO.saveFile(ta.value, tab.filePath, tab.lineEnding, tab.fingerprint);
+      } catch (err) {
+        if (err?.code !== 'Conflict') throw err;
+        const ok = confirm(`"${tab.title}" was changed on disk since you opened it.\n\nOverwrite it with your version?`);
+        if (!ok) return;
+        result = await fileIO.saveFile(ta.value, tab.filePath, tab.lineEnding, null);
+      }
       if (result) {
-        tabs.markTabSaved(tab.id, result.path, result.mtime);
+        tabs.markTabSaved(tab.id, result.path, result.mtime, result.fingerprint);
         if (fileIO.isDesktop()) settings.addRecentFile(result.path);
       }
     } catch (err) {
@@ -118,7 +127,7 @@ document.addEventListener('DOMContentLoaded', async () => {
       const ta = tabs.getActiveTextarea();
       const result = await fileIO.saveFileAs(ta.value, tab.lineEnding, tab.title);
       if (result) {
-        tabs.markTabSaved(tab.id, result.path, result.mtime);
+        tabs.markTabSaved(tab.id, result.path, result.mtime, result.fingerprint);
         if (fileIO.isDesktop()) settings.addRecentFile(result.path);
       }
     } catch (err) {
@@ -142,7 +151,7 @@ document.addEventListener('DOMContentLoaded', async () => {
     save:      () => handleSave(),
     saveAs:    () => handleSaveAs(),
     exportHtml: () => exportHtml.exportToHtml(),
-    exportPdf: () => window.print(),
+    exportPdf: () => exportPdf.exportToPdf(),
     closeTab:  () => { const t = tabs.getActiveTab(); if (t) tabs.requestCloseTab(t.id); },
     exit:      () => {
       if (window.__TAURI__?.window?.getCurrentWindow) {
@@ -153,6 +162,7 @@ document.addEventListener('DOMContentLoaded', async () => {
     // Edit
     undo:      () => { focusEditor(); document.execCommand('undo'); },
     redo:      () => { focusEditor(); document.execCommand('redo'); },
+    undoDelete: () => { sidebar.undoDelete(); },
     cut:       () => { focusEditor(); document.execCommand('cut'); },
     copy:      () => { focusEditor(); document.execCommand('copy'); },
     paste:     () => { focusEditor(); document.execCommand('paste'); },
@@ -180,6 +190,12 @@ document.addEventListener('DOMContentLoaded', async () => {
     // Settings
     settings: () => window.Paddown.settingsUI.open(),
 
+    // History
+    versionHistory: () => { if (fileIO.isDesktop()) window.Paddown.historyUI.open(); },
+
+    // Search
+    findInFolders: () => { if (fileIO.isDesktop()) window.Paddown.searchUI.open(); },
+
     // Help
     about: async () => {
       if (document.getElementById('about-overlay')) return;
@@ -277,6 +293,7 @@ document.addEventListener('DOMContentLoaded', async () => {
     if (ctrl && !e.shiftKey && e.key === 'w') { e.preventDefault(); actionMap.closeTab(); }
     if (ctrl && !e.shiftKey && e.key === 'f') { e.preventDefault(); actionMap.find(); }
     if (ctrl && !e.shiftKey && e.key === 'h') { e.preventDefault(); actionMap.replace(); }
+    if (ctrl && e.shiftKey && e.key === 'F')  { e.preventDefault(); actionMap.findInFolders(); }
     if (ctrl && !e.shiftKey && e.key === 'b') { e.preventDefault(); actionMap.bold(); }
     if (ctrl && !e.shiftKey && e.key === 'i') { e.preventDefault(); actionMap.italic(); }
     if (ctrl && !e.shiftKey && e.key === 'k') { e.preventDefault(); actionMap.link(); }
@@ -353,7 +370,7 @@ document.addEventListener('DOMContentLoaded', async () => {
 
         const active = tabs.getActiveTab();
         if (tabs.isTabBlankUntitled(active)) {
-          tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime);
+          tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime, result.fingerprint);
           editor.render();
         } else {
           tabs.createTab({
@@ -361,7 +378,8 @@ document.addEventListener('DOMContentLoaded', async () => {
             filePath,
             content: result.content,
             lineEnding: result.lineEnding,
-            lastModified: result.mtime
+            lastModified: result.mtime,
+            fingerprint: result.fingerprint
           });
         }
       } catch (err) {
@@ -372,11 +390,12 @@ document.addEventListener('DOMContentLoaded', async () => {
 
   // ─── Open File by Path (shared by CLI args + single-instance) ──
 
+  // Returns the tab the file is open in, or null if it couldn't be read.
   async function openFileFromPath(filePath) {
-    if (!filePath || filePath.startsWith('-')) return;
+    if (!filePath) return null;
 
     const existing = tabs.getAllTabs().find(t => t.filePath === filePath);
-    if (existing) { tabs.switchTab(existing.id); return; }
+    if (existing) { tabs.switchTab(existing.id); return existing; }
 
     try {
       const result = await fileIO.readFileContent(filePath);
@@ -384,30 +403,82 @@ document.addEventListener('DOMContentLoaded', async () => {
 
       const active = tabs.getActiveTab();
       if (tabs.isTabBlankUntitled(active)) {
-        tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime);
+        tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime, result.fingerprint);
         editor.render();
-      } else {
-        tabs.createTab({
-          title: window.Paddown.utils.basename(filePath),
-          filePath,
-          content: result.content,
-          lineEnding: result.lineEnding,
-          lastModified: result.mtime
-        });
+        return active;
       }
+      return tabs.createTab({
+        title: window.Paddown.utils.basename(filePath),
+        filePath,
+        content: result.content,
+        lineEnding: result.lineEnding,
+        lastModified: result.mtime,
+        fingerprint: result.fingerprint
+      });
     } catch (err) {
       console.error('Failed to open file:', filePath, err);
+      return null;
+    }
+  }
+
+  // Puts the cursor at a 1-based line and column of the active tab.
+  function revealPosition(line, column) {
+    const ta = tabs.getActiveTextarea();
+    if (!ta) return;
+    const lines = ta.value.split('\n');
+    const row = Math.min(line, lines.length) - 1;
+    let offset = 0;
+    for (let i = 0; i < row; i++) offset += lines[i].length + 1;
+    offset += Math.min((column || 1) - 1, lines[row].replace(/\r$/, '').length);
+    ta.focus();
+    ta.setSelectionRange(offset, offset);
+    const lineHeight = parseFloat(getComputedStyle(ta).lineHeight) || 20;
+    ta.scrollTop = Math.max(0, (row - 4) * lineHeight);
+  }
+
+  // Carries out an OpenRequest parsed by the backend from a command line.
+  async function handleOpenRequest(request) {
+    for (const folder of request.folders || []) {
+      await window.Paddown.sidebar.pinProject(folder);
+    }
+    for (const file of request.files || []) {
+      const tab = await openFileFromPath(file.path);
+      if (!tab) continue;
+      if (request.readonly) tabs.setReadOnly(tab.id, true);
+      if (file.line) revealPosition(file.line, file.column);
+    }
+    // Piped text starts out unsaved, so closing the tab asks first.
+    for (const text of request.untitled || []) {
+      const active = tabs.getActiveTab();
+      const tab = tabs.isTabBlankUntitled(active) ? active : tabs.createTab();
+      tabs.getActiveTextarea().value = text;
+      tabs.refreshDirtyState();
+      editor.render();
+      if (request.readonly) tabs.setReadOnly(tab.id, true);
+    }
+    // Edits from --edit-stdin: the calling command waits until the tab closes.
+    for (const edit of request.edits || []) {
+      const tab = tabs.createTab({ title: 'stdin', content: edit.text, editId: edit.id });
+      editor.render();
+      if (request.readonly) tabs.setReadOnly(tab.id, true);
+    }
+    if (request.new_tab) {
+      const active = tabs.getActiveTab();
+      if (!tabs.isTabBlankUntitled(active)) tabs.createTab();
+    }
+    const errors = request.errors || [];
+    if (errors.length) {
+      console.error('Command line paths could not be opened:', errors);
+      alert(`Could not open:\n\n${errors.map(e => e.message).join('\n')}`);
     }
   }
 
-  // ─── Open Files from CLI Args ──────────────────────────────
+  // ─── Open Files from the Command Line ──────────────────────
 
   if (fileIO.isDesktop()) {
     try {
-      const args = await window.__TAURI__.core.invoke('get_cli_args');
-      for (const arg of args) {
-        await openFileFromPath(arg);
-      }
+      const request = await window.__TAURI__.core.invoke('get_open_request');
+      await handleOpenRequest(request);
     } catch (_) {}
   }
 
@@ -416,10 +487,7 @@ document.addEventListener('DOMContentLoaded', async () => {
   if (fileIO.isDesktop() && window.__TAURI__?.event?.listen) {
     try {
       await window.__TAURI__.event.listen('second-instance', async (event) => {
-        const argv = event.payload || [];
-        for (const arg of argv) {
-          await openFileFromPath(arg);
-        }
+        await handleOpenRequest(event.payload || {});
       });
     } catch (_) {}
   }
@@ -444,14 +512,15 @@ document.addEventListener('DOMContentLoaded', async () => {
 
             const active = tabs.getActiveTab();
             if (restored === 0 && tabs.isTabBlankUntitled(active)) {
-              tabs.loadIntoTab(active.id, result.content, saved.filePath, result.lineEnding, result.mtime);
+              tabs.loadIntoTab(active.id, result.content, saved.filePath, result.lineEnding, result.mtime, result.fingerprint);
             } else {
               tabs.createTab({
                 title: window.Paddown.utils.basename(saved.filePath),
                 filePath: saved.filePath,
                 content: result.content,
                 lineEnding: result.lineEnding,
-                lastModified: result.mtime
+                lastModified: result.mtime,
+                fingerprint: result.fingerprint
               });
             }
 
@@ -533,8 +602,14 @@ document.addEventListener('DOMContentLoaded', async () => {
         await settings.save();
       }
 
+      // Hand --edit-stdin tabs back to their commands on the way out.
+      const finishEdits = () => Promise.all(
+        tabs.getAllTabs().filter(t => t.editId).map(t => tabs.finishEdit(t))
+      );
+
       const dirtyTabs = tabs.getDirtyTabs();
       if (dirtyTabs.length === 0) {
+        await finishEdits();
         await recovery.clearRecovery();
         appWindow.destroy();
         return;
@@ -543,6 +618,7 @@ document.addEventListener('DOMContentLoaded', async () => {
       const names = dirtyTabs.map(t => t.title).join(', ');
       const ok = confirm(`You have unsaved changes in: ${names}\n\nClose without saving?`);
       if (ok) {
+        await finishEdits();
         await recovery.clearRecovery();
         appWindow.destroy();
       }
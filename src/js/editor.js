@@ -246,9 +246,12 @@ window.Paddown.editor = (() => {
     const bar = document.createElement('div');
     bar.id = 'external-mod-bar';
     const safeTitle = window.Paddown.utils.escapeHtml(tab.title);
+    // Offer a merge only when there are unsaved edits worth keeping
+    const canMerge = window.Paddown.tabs.isTabDirty(tab);
     bar.innerHTML =
       `<span>File "${safeTitle}" was modified externally.</span>` +
       `<button id="mod-reload">Reload</button>` +
+      (canMerge ? `<button id="mod-merge">Merge</button>` : '') +
       `<button class="mod-dismiss" title="Dismiss">\u00D7</button>`;
 
     // Insert before workspace
@@ -260,11 +263,23 @@ window.Paddown.editor = (() => {
       bar.remove();
     });
 
+    if (canMerge) {
+      document.getElementById('mod-merge').addEventListener('click', async () => {
+        await mergeTabWithDisk(tab);
+        bar.remove();
+      });
+    }
+
     bar.querySelector('.mod-dismiss').addEventListener('click', () => {
-      // Update lastModified to suppress repeated prompts
-      window.Paddown.fileIO.getMtime(tab.filePath).then(mtime => {
+      // Update lastModified to suppress repeated prompts, and adopt the
+      // new fingerprint so the next save doesn't report a conflict.
+      const { fileIO } = window.Paddown;
+      fileIO.getMtime(tab.filePath).then(mtime => {
         if (mtime) tab.lastModified = mtime;
       }).catch(() => {});
+      fileIO.getFingerprint(tab.filePath).then(fingerprint => {
+        if (fingerprint) tab.fingerprint = fingerprint;
+      }).catch(() => {});
       bar.remove();
     });
   }
@@ -278,6 +293,7 @@ window.Paddown.editor = (() => {
       tab.savedContent = result.content;
       tab.lineEnding = result.lineEnding;
       tab.lastModified = result.mtime;
+      tab.fingerprint = result.fingerprint;
       ta.value = result.content;
       render();
     } catch (err) {
@@ -285,6 +301,35 @@ window.Paddown.editor = (() => {
     }
   }
 
+  // Three-way merge: the tab's last loaded/saved content is the common
+  // base, the disk version and the buffer are the two sides. Conflicts are
+  // left in the buffer as git-style markers for the user to resolve.
+  async function mergeTabWithDisk(tab) {
+    const { tabs, fileIO } = window.Paddown;
+    try {
+      const ta = document.getElementById(`editor-${tab.id}`);
+      if (!ta) return;
+      const disk = await fileIO.readFileContent(tab.filePath);
+      const result = await window.__TAURI__.core.invoke('merge_three_way', {
+        base: tab.savedContent,
+        disk: disk.content,
+        buffer: ta.value
+      });
+      tab.savedContent = disk.content;
+      tab.lineEnding = disk.lineEnding;
+      tab.lastModified = disk.mtime;
+      tab.fingerprint = disk.fingerprint;
+      ta.value = result.text;
+      if (tabs.getActiveTab() === tab) render();
+      if (result.status === 'conflict') {
+        const n = result.conflicts;
+        alert(`${n} conflicting change${n !== 1 ? 's' : ''} could not be merged automatically.\n\nLook for the <<<<<<< markers in the editor.`);
+      }
+    } catch (err) {
+      console.error('Merge failed:', err);
+    }
+  }
+
   function init() {
     previewEl    = document.getElementById('preview');
     statChars    = document.getElementById('stat-chars');
@@ -314,5 +359,5 @@ window.Paddown.editor = (() => {
     }
   }
 
-  return { init, render, attachToTextarea, checkExternalModification };
+  return { init, render, attachToTextarea, checkExternalModification, reloadTabFromDisk };
 })();
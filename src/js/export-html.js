@@ -1,6 +1,8 @@
 /**
  * Paddown — HTML Export
- * Generates a standalone HTML file from the preview, with all styles inlined.
+ * Writes the current document as an HTML file, rendered by the backend,
+ * with its local images linked as they are, embedded, or copied into an
+ * assets folder.
  */
 window.Paddown = window.Paddown || {};
 
@@ -10,84 +12,15 @@ window.Paddown.exportHtml = (() => {
   }
 
   /**
-   * Collect all CSS rules from the app's stylesheets that
-   * apply to .standard-markdown, tokens, and code blocks.
-   */
-  function collectStyles() {
-    const rules = [];
-    for (const sheet of document.styleSheets) {
-      try {
-        for (const rule of sheet.cssRules) {
-          const text = rule.cssText;
-          // Include markdown styles, token colors, font/color tokens
-          if (text.includes('.standard-markdown') ||
-              text.includes('.tok-') ||
-              text.includes('.code-block-wrapper') ||
-              text.includes('.code-lang-label') ||
-              text.includes('.table-wrapper') ||
-              text.includes('.task-list-item') ||
-              text.includes(':root')) {
-            rules.push(text);
-          }
-        }
-      } catch (_) {
-        // Cross-origin stylesheet, skip
-      }
-    }
-    return rules.join('\n');
-  }
-
-  /**
-   * Build a standalone HTML document from the current preview.
-   */
-  function buildHtml(title) {
-    const previewEl = document.getElementById('preview');
-    if (!previewEl) return '';
-
-    const css = collectStyles();
-    const body = previewEl.innerHTML;
-    const safeTitle = (title || 'Untitled').replace(/</g, '&lt;').replace(/>/g, '&gt;');
-
-    return `<!DOCTYPE html>
-<html lang="en">
-<head>
-  <meta charset="UTF-8">
-  <meta name="viewport" content="width=device-width, initial-scale=1.0">
-  <title>${safeTitle}</title>
-  <style>
-${css}
-body {
-  font-family: "Segoe UI", system-ui, -apple-system, Helvetica, Arial, sans-serif;
-  color: #14181f;
-  background: #ffffff;
-  margin: 0;
-  padding: 2rem;
-  display: flex;
-  justify-content: center;
-}
-.standard-markdown {
-  max-width: 680px;
-  width: 100%;
-}
-  </style>
-</head>
-<body>
-  <div class="standard-markdown">
-${body}
-  </div>
-</body>
-</html>`;
-  }
-
-  /**
-   * Export the current preview as a standalone HTML file.
+   * Export the active tab as a standalone HTML file.
    */
   async function exportToHtml() {
-    const { fileIO, tabs } = window.Paddown;
+    const { fileIO, tabs, settings, utils } = window.Paddown;
     if (!fileIO.isDesktop()) return;
 
     const tab = tabs.getActiveTab();
-    if (!tab) return;
+    const ta = tabs.getActiveTextarea();
+    if (!tab || !ta) return;
 
     // Derive default filename from tab title
     const baseName = (tab.title || 'Untitled').replace(/\.(md|markdown|txt)$/i, '');
@@ -97,8 +30,20 @@ ${body}
       const path = await invoke('show_export_html_dialog', { defaultName });
       if (!path) return;
 
-      const html = buildHtml(baseName);
-      await invoke('write_file', { path, contents: html });
+      // Images load relative to the document, as in the preview.
+      const baseDir = tab.filePath ? utils.dirname(tab.filePath) : null;
+      const mode = settings.get('htmlImages');
+      const report = await invoke('export_html', {
+        path,
+        markdown: ta.value,
+        title: baseName,
+        baseDir,
+        images: mode === 'keep' ? null : mode
+      });
+      if (report.skipped.length > 0) {
+        const lines = report.skipped.map(s => `${s.url}: ${s.error.message}`);
+        alert(`Some images were left as links:\n\n${lines.join('\n')}`);
+      }
     } catch (err) {
       console.error('HTML export failed:', err);
     }
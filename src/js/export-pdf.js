@@ -0,0 +1,66 @@
+/**
+ * Paddown — PDF Export
+ * Writes the current document as a PDF, laid out by the backend with the
+ * page settings from the PDF Export section of Settings.
+ */
+window.Paddown = window.Paddown || {};
+
+window.Paddown.exportPdf = (() => {
+  function invoke(cmd, args) {
+    return window.__TAURI__.core.invoke(cmd, args);
+  }
+
+  /**
+   * The export options from settings, in the shape the backend expects.
+   */
+  function pdfOptions() {
+    const { settings } = window.Paddown;
+    return {
+      page_size: settings.get('pdfPageSize'),
+      landscape: settings.get('pdfLandscape'),
+      margin_mm: Number(settings.get('pdfMarginMm')),
+      header: settings.get('pdfHeader'),
+      page_numbers: settings.get('pdfPageNumbers'),
+      wrap_code: settings.get('pdfWrapCode')
+    };
+  }
+
+  /**
+   * Export the active tab as a PDF file.
+   */
+  async function exportToPdf() {
+    const { fileIO, tabs, utils } = window.Paddown;
+    if (!fileIO.isDesktop()) return;
+
+    const tab = tabs.getActiveTab();
+    const ta = tabs.getActiveTextarea();
+    if (!tab || !ta) return;
+
+    // Derive default filename from tab title
+    const baseName = (tab.title || 'Untitled').replace(/\.(md|markdown|txt)$/i, '');
+    const defaultName = baseName + '.pdf';
+
+    try {
+      const path = await invoke('show_export_pdf_dialog', { defaultName });
+      if (!path) return;
+
+      // Images load relative to the document, as in the preview.
+      const baseDir = tab.filePath ? utils.dirname(tab.filePath) : null;
+      const report = await invoke('export_pdf', {
+        path,
+        markdown: ta.value,
+        title: baseName,
+        baseDir,
+        options: pdfOptions()
+      });
+      if (report.unsupported.length > 0) {
+        const chars = report.unsupported.join(' ');
+        alert(`Some characters are missing from the PDF fonts and print as "?":\n\n${chars}`);
+      }
+    } catch (err) {
+      console.error('PDF export failed:', err);
+    }
+  }
+
+  return { exportToPdf };
+})();
@@ -48,15 +48,14 @@ window.Paddown.fileIO = (() => {
     const filePath = await invoke('show_open_dialog');
     if (!filePath) return null;
 
-    const rawContent = await invoke('read_file', { path: filePath });
-    const lineEnding = detectLineEnding(rawContent);
-    const content = normalizeForEditor(rawContent);
-    const mtime = await getMtime(filePath);
-
-    return { filePath, content, lineEnding, mtime };
+    const result = await readFileContent(filePath);
+    return { filePath, ...result };
   }
 
-  async function saveFile(content, currentPath, lineEnding) {
+  // `fingerprint` is what the tab was loaded from; the backend rejects the
+  // write with a Conflict error if the file has changed since. Pass null to
+  // overwrite unconditionally.
+  async function saveFile(content, currentPath, lineEnding, fingerprint) {
     if (!isDesktop()) return null;
 
     const path = currentPath || await invoke('show_save_dialog', {
@@ -65,10 +64,11 @@ window.Paddown.fileIO = (() => {
     if (!path) return null;
 
     const finalContent = denormalizeForSave(content, lineEnding);
-    await invoke('write_file', { path, contents: finalContent });
+    const expected = currentPath ? fingerprint || null : null;
+    const newFingerprint = await invoke('write_file', { path, contents: finalContent, expected });
     const mtime = await getMtime(path);
 
-    return { path, mtime };
+    return { path, mtime, fingerprint: newFingerprint };
   }
 
   async function saveFileAs(content, lineEnding, currentName) {
@@ -80,18 +80,22 @@ window.Paddown.fileIO = (() => {
     if (!path) return null;
 
     const finalContent = denormalizeForSave(content, lineEnding);
-    await invoke('write_file', { path, contents: finalContent });
+    const fingerprint = await invoke('write_file', { path, contents: finalContent });
     const mtime = await getMtime(path);
 
-    return { path, mtime };
+    return { path, mtime, fingerprint };
   }
 
   async function readFileContent(filePath) {
-    const rawContent = await invoke('read_file', { path: filePath });
+    const { contents: rawContent, fingerprint } = await invoke('read_file', { path: filePath });
     const lineEnding = detectLineEnding(rawContent);
     const content = normalizeForEditor(rawContent);
     const mtime = await getMtime(filePath);
-    return { content, lineEnding, mtime };
+    return { content, lineEnding, mtime, fingerprint };
+  }
+
+  async function getFingerprint(path) {
+    try { return (await invoke('read_file', { path })).fingerprint; } catch (_) { return null; }
   }
 
   return {
@@ -103,6 +107,7 @@ window.Paddown.fileIO = (() => {
     saveFile,
     saveFileAs,
     readFileContent,
-    getMtime
+    getMtime,
+    getFingerprint
   };
 })();
@@ -0,0 +1,150 @@
+/**
+ * Paddown — Version History UI
+ * Lists the saved versions of the active file; each can be opened in a new
+ * tab or restored over the file on disk.
+ */
+window.Paddown = window.Paddown || {};
+
+window.Paddown.historyUI = (() => {
+
+  function invoke(cmd, args) {
+    return window.__TAURI__.core.invoke(cmd, args);
+  }
+
+  function formatSize(bytes) {
+    if (bytes < 1024) return `${bytes} B`;
+    if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+    return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+  }
+
+  async function openVersion(tab, version) {
+    const { tabs, fileIO, editor } = window.Paddown;
+    try {
+      const raw = await invoke('read_file_version', { path: tab.filePath, versionId: version.id });
+      tabs.createTab({
+        title: `${tab.title} (${new Date(version.saved_at).toLocaleString()})`,
+        content: fileIO.normalizeForEditor(raw),
+        lineEnding: fileIO.detectLineEnding(raw)
+      });
+      editor.render();
+    } catch (err) {
+      console.error('Opening version failed:', err);
+    }
+  }
+
+  async function restoreVersion(tab, version) {
+    const { tabs, editor } = window.Paddown;
+    if (tabs.isTabDirty(tab) &&
+        !confirm(`Discard your unsaved changes to "${tab.title}" and restore this version?`)) {
+      return false;
+    }
+    const args = { path: tab.filePath, versionId: version.id, expected: tab.fingerprint };
+    try {
+      try {
+        await invoke('restore_file_version', args);
+      } catch (err) {
+        if (err?.code !== 'Conflict') throw err;
+        const ok = confirm(`"${tab.title}" was changed on disk since you opened it.\n\nRestore this version anyway?`);
+        if (!ok) return false;
+        await invoke('restore_file_version', { ...args, expected: null });
+      }
+      await editor.reloadTabFromDisk(tab);
+      tabs.refreshDirtyState();
+      return true;
+    } catch (err) {
+      console.error('Restore failed:', err);
+      return false;
+    }
+  }
+
+  async function open() {
+    if (document.getElementById('history-overlay')) return;
+
+    const tab = window.Paddown.tabs.getActiveTab();
+    if (!tab || !tab.filePath) return;
+
+    let versions = [];
+    try {
+      versions = await invoke('list_file_versions', { path: tab.filePath });
+    } catch (err) {
+      console.error('Listing versions failed:', err);
+    }
+
+    const overlay = document.createElement('div');
+    overlay.id = 'history-overlay';
+
+    const card = document.createElement('div');
+    card.id = 'history-card';
+
+    const header = document.createElement('h2');
+    header.textContent = `History \u2014 ${tab.title}`;
+
+    const closeBtn = document.createElement('button');
+    closeBtn.className = 'about-close';
+    closeBtn.textContent = '\u00D7';
+
+    const list = document.createElement('div');
+    list.className = 'history-list';
+
+    if (versions.length === 0) {
+      const empty = document.createElement('div');
+      empty.className = 'history-empty';
+      empty.textContent = 'No saved versions yet.';
+      list.appendChild(empty);
+    }
+
+    versions.forEach((version, i) => {
+      const row = document.createElement('div');
+      row.className = 'history-row';
+
+      const info = document.createElement('span');
+      info.className = 'history-info';
+      info.textContent = `${new Date(version.saved_at).toLocaleString()} \u00B7 ${formatSize(version.size)}`;
+      if (i === 0) info.textContent += ' \u00B7 current';
+
+      const openBtn = document.createElement('button');
+      openBtn.textContent = 'Open';
+      openBtn.addEventListener('click', () => { close(); openVersion(tab, version); });
+
+      row.append(info, openBtn);
+
+      if (i > 0) {
+        const restoreBtn = document.createElement('button');
+        restoreBtn.textContent = 'Restore';
+        restoreBtn.addEventListener('click', async () => {
+          if (await restoreVersion(tab, version)) close();
+        });
+        row.appendChild(restoreBtn);
+      }
+
+      list.appendChild(row);
+    });
+
+    card.append(header, closeBtn, list);
+    overlay.appendChild(card);
+    document.body.appendChild(overlay);
+
+    // Animate in
+    void overlay.offsetHeight;
+    overlay.classList.add('visible');
+
+    function close() {
+      overlay.classList.remove('visible');
+      const fallback = setTimeout(() => overlay.remove(), 300);
+      overlay.addEventListener('transitionend', (e) => {
+        if (e.target === overlay) { clearTimeout(fallback); overlay.remove(); }
+      }, { once: true });
+      document.removeEventListener('keydown', onKey);
+    }
+
+    function onKey(e) {
+      if (e.key === 'Escape') { e.preventDefault(); close(); }
+    }
+
+    closeBtn.addEventListener('click', close);
+    overlay.addEventListener('click', (e) => { if (e.target === overlay) close(); });
+    document.addEventListener('keydown', onKey);
+  }
+
+  return { open };
+})();
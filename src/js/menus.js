@@ -24,6 +24,7 @@ window.Paddown.menus = (() => {
         { label: 'Save As\u2026', shortcut: 'Ctrl+Shift+S', action: 'saveAs' },
         { label: 'Export as HTML\u2026', action: 'exportHtml' },
         { label: 'Export as PDF\u2026', action: 'exportPdf' },
+        { label: 'Version History\u2026', action: 'versionHistory' },
         { type: 'separator' },
         { label: 'Add Project Folder\u2026', action: 'addProjectFolder' },
         { type: 'separator' },
@@ -38,6 +39,7 @@ window.Paddown.menus = (() => {
       items: [
         { label: 'Undo', shortcut: 'Ctrl+Z', action: 'undo' },
         { label: 'Redo', shortcut: 'Ctrl+Y', action: 'redo' },
+        { label: 'Undo Delete', action: 'undoDelete' },
         { type: 'separator' },
         { label: 'Cut', shortcut: 'Ctrl+X', action: 'cut' },
         { label: 'Copy', shortcut: 'Ctrl+C', action: 'copy' },
@@ -45,7 +47,8 @@ window.Paddown.menus = (() => {
         { label: 'Select All', shortcut: 'Ctrl+A', action: 'selectAll' },
         { type: 'separator' },
         { label: 'Find\u2026', shortcut: 'Ctrl+F', action: 'find' },
-        { label: 'Replace\u2026', shortcut: 'Ctrl+H', action: 'replace' }
+        { label: 'Replace\u2026', shortcut: 'Ctrl+H', action: 'replace' },
+        { label: 'Find in Folders\u2026', shortcut: 'Ctrl+Shift+F', action: 'findInFolders' }
       ]
     },
     view: {
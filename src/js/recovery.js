@@ -73,7 +73,7 @@ window.Paddown.recovery = (() => {
       const items = [];
       for (const f of files) {
         try {
-          const raw = await invoke('read_file', { path: f });
+          const raw = (await invoke('read_file', { path: f })).contents;
           const data = JSON.parse(raw);
           data._recoveryFilename = window.Paddown.utils.basename(f);
           items.push(data);
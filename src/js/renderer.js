@@ -12,8 +12,10 @@ window.Paddown.renderer = (() => {
   const renderer = new marked.Renderer();
 
   // Code blocks — add language label + syntax highlighting
+  // marked passes the whole info string; the language is its first word,
+  // as in the backend's renderers.
   renderer.code = function(code, lang) {
-    const safeLang = (lang || '').toLowerCase().trim();
+    const safeLang = ((lang || '').trim().split(/\s+/)[0] || '').toLowerCase();
     const highlighted = highlight(code, safeLang);
     const label = safeLang
       ? `<div class="code-lang-label">${escapeHtml(safeLang)}</div>`
@@ -0,0 +1,358 @@
+/**
+ * Paddown — Find in Folders
+ * While typing, shows the best-ranked documents from the backend's search
+ * index; Enter runs an exact search_workspace pass over every pinned folder
+ * and lists matches as they stream in. Clicking a result opens the file at
+ * the match. With a replacement filled in, Preview Replace lists every
+ * match with its replacement; the ticked ones are applied file by file.
+ */
+window.Paddown = window.Paddown || {};
+
+window.Paddown.searchUI = (() => {
+  let taskCounter = 0;
+
+  function invoke(cmd, args) {
+    return window.__TAURI__.core.invoke(cmd, args);
+  }
+
+  function splitGlobs(value) {
+    return value.split(',').map(s => s.trim()).filter(Boolean);
+  }
+
+  // Selects the match in the active tab once the file is open. Columns and
+  // ranges are UTF-16 offsets, i.e. plain JS string indices.
+  function revealMatch(match) {
+    const ta = window.Paddown.tabs.getActiveTextarea();
+    if (!ta) return;
+    const lines = ta.value.split('\n');
+    let offset = 0;
+    for (let i = 0; i < match.line - 1 && i < lines.length; i++) offset += lines[i].length + 1;
+    const [start, end] = match.ranges?.[0] || [0, 0];
+    ta.focus();
+    ta.setSelectionRange(offset + start, offset + end);
+    const lineHeight = parseFloat(getComputedStyle(ta).lineHeight) || 20;
+    ta.scrollTop = Math.max(0, (match.line - 5) * lineHeight);
+  }
+
+  function highlighted(text, ranges) {
+    const frag = document.createDocumentFragment();
+    let pos = 0;
+    ranges.forEach(([start, end]) => {
+      frag.append(text.slice(pos, start));
+      const mark = document.createElement('mark');
+      mark.textContent = text.slice(start, end);
+      frag.append(mark);
+      pos = end;
+    });
+    frag.append(text.slice(pos));
+    return frag;
+  }
+
+  function openHit(close, path, match) {
+    return async () => {
+      close();
+      await window.Paddown.sidebar.openFile(path);
+      if (match) revealMatch(match);
+    };
+  }
+
+  function open() {
+    if (document.getElementById('search-overlay')) return;
+    const { settings, utils } = window.Paddown;
+
+    const overlay = document.createElement('div');
+    overlay.id = 'search-overlay';
+
+    const card = document.createElement('div');
+    card.id = 'search-card';
+
+    const header = document.createElement('h2');
+    header.textContent = 'Find in Folders';
+
+    const closeBtn = document.createElement('button');
+    closeBtn.className = 'about-close';
+    closeBtn.textContent = '\u00D7';
+
+    const form = document.createElement('form');
+    form.className = 'search-form';
+    form.innerHTML = `
+      <input type="text" name="pattern" placeholder="Search" autocomplete="off" spellcheck="false">
+      <div class="search-options">
+        <label><input type="checkbox" name="caseSensitive"> Match case</label>
+        <label><input type="checkbox" name="wholeWord"> Whole word</label>
+        <label><input type="checkbox" name="regex"> Regex</label>
+      </div>
+      <input type="text" name="include" placeholder="Files to include, e.g. prompts/**" spellcheck="false">
+      <input type="text" name="exclude" placeholder="Files to exclude, e.g. archive/**" spellcheck="false">
+      <div class="search-replace-row">
+        <input type="text" name="replacement" placeholder="Replace with ($1 for groups in regex mode)" spellcheck="false">
+        <button type="button" name="previewReplace">Preview Replace</button>
+      </div>
+    `;
+
+    const status = document.createElement('div');
+    status.className = 'search-status';
+
+    const ranked = document.createElement('div');
+    ranked.className = 'search-ranked';
+
+    const list = document.createElement('div');
+    list.className = 'search-results';
+
+    card.append(header, closeBtn, form, status, ranked, list);
+    overlay.appendChild(card);
+    document.body.appendChild(overlay);
+
+    void overlay.offsetHeight;
+    overlay.classList.add('visible');
+    form.elements.pattern.focus();
+
+    let currentTask = null;
+    let unlisten = null;
+    let closed = false;
+
+    window.__TAURI__.event.listen('search-results', (event) => {
+      const { task_id: taskId, path, matches } = event.payload;
+      if (taskId !== currentTask) return;
+
+      const group = document.createElement('div');
+      group.className = 'search-file';
+      const title = document.createElement('div');
+      title.className = 'search-file-name';
+      title.textContent = utils.basename(path);
+      title.title = path;
+      group.appendChild(title);
+
+      matches.forEach(match => {
+        const row = document.createElement('div');
+        row.className = 'search-match';
+        const lineNo = document.createElement('span');
+        lineNo.className = 'search-line-no';
+        lineNo.textContent = match.line;
+        const text = document.createElement('span');
+        text.className = 'search-line-text';
+        text.appendChild(highlighted(match.text, match.ranges));
+        row.title = [...match.before, match.text, ...match.after].join('\n');
+        row.append(lineNo, text);
+        row.addEventListener('click', openHit(close, path, match));
+        group.appendChild(row);
+      });
+      list.appendChild(group);
+    }).then(fn => {
+      if (closed) fn(); else unlisten = fn;
+    });
+
+    // Ranked results from the index, refreshed on every keystroke.
+    let rankedSeq = 0;
+    let rankedTimer = null;
+    async function showRanked() {
+      const seq = ++rankedSeq;
+      const text = form.elements.pattern.value;
+      if (!text.trim()) { ranked.innerHTML = ''; return; }
+      let results;
+      try {
+        results = await invoke('query_index', { query: text, limit: 8 });
+      } catch (err) {
+        console.error('Index query failed:', err);
+        return;
+      }
+      if (seq !== rankedSeq || closed) return;
+      ranked.innerHTML = '';
+      results.hits.forEach(hit => {
+        const row = document.createElement('div');
+        row.className = 'search-hit';
+        const title = document.createElement('div');
+        title.className = 'search-file-name';
+        title.textContent = hit.title;
+        title.title = hit.path;
+        const snippet = document.createElement('div');
+        snippet.className = 'search-line-text';
+        snippet.appendChild(highlighted(hit.snippet, hit.ranges));
+        row.append(title, snippet);
+        row.addEventListener('click', openHit(close, hit.path, hit.line ? { line: hit.line } : null));
+        ranked.appendChild(row);
+      });
+      if (results.indexing) {
+        const note = document.createElement('div');
+        note.className = 'search-status';
+        note.textContent = 'Still indexing some folders\u2026';
+        ranked.appendChild(note);
+      }
+    }
+
+    form.elements.pattern.addEventListener('input', () => {
+      clearTimeout(rankedTimer);
+      rankedTimer = setTimeout(showRanked, 80);
+    });
+
+    function buildQuery() {
+      return {
+        pattern: form.elements.pattern.value,
+        regex: form.elements.regex.checked,
+        case_sensitive: form.elements.caseSensitive.checked,
+        whole_word: form.elements.wholeWord.checked,
+        include: splitGlobs(form.elements.include.value),
+        exclude: splitGlobs(form.elements.exclude.value),
+        extensions: settings.get('sidebarFileExtensions') || ['md', 'markdown']
+      };
+    }
+
+    async function run() {
+      const pattern = form.elements.pattern.value;
+      if (currentTask) invoke('cancel_task', { taskId: currentTask }).catch(() => {});
+      ranked.innerHTML = '';
+      list.innerHTML = '';
+      status.textContent = '';
+      if (!pattern) { currentTask = null; return; }
+
+      const taskId = `search-${++taskCounter}`;
+      currentTask = taskId;
+      status.textContent = 'Searching\u2026';
+      try {
+        const summary = await invoke('search_workspace', { query: buildQuery(), taskId });
+        if (currentTask !== taskId) return;
+        const s = n => (n === 1 ? '' : 'es');
+        status.textContent = summary.matches === 0
+          ? `No matches in ${summary.files_searched} files`
+          : `${summary.matches} match${s(summary.matches)} in ${summary.files_matched} of ${summary.files_searched} files` +
+            (summary.truncated ? ' (stopped early)' : '');
+      } catch (err) {
+        if (currentTask !== taskId || err?.code === 'Cancelled') return;
+        status.textContent = err?.message || String(err);
+      }
+    }
+
+    // Lists each match with a checkbox; files keep their preview fingerprint
+    // so apply_replace skips any that changed in the meantime.
+    function renderReplacePreview(preview) {
+      const entries = [];
+      preview.files.forEach(file => {
+        const group = document.createElement('div');
+        group.className = 'search-file';
+        const title = document.createElement('label');
+        title.className = 'search-file-name';
+        const fileBox = document.createElement('input');
+        fileBox.type = 'checkbox';
+        fileBox.checked = true;
+        title.append(fileBox, ` ${utils.basename(file.path)}`);
+        title.title = file.path;
+        group.appendChild(title);
+
+        const boxes = file.matches.map(match => {
+          const row = document.createElement('label');
+          row.className = 'search-match';
+          const box = document.createElement('input');
+          box.type = 'checkbox';
+          box.checked = true;
+          const lineNo = document.createElement('span');
+          lineNo.className = 'search-line-no';
+          lineNo.textContent = match.line;
+          const text = document.createElement('span');
+          text.className = 'search-line-text';
+          text.appendChild(highlighted(match.line_text, [match.range]));
+          row.title = `${match.old_text} \u2192 ${match.new_text}`;
+          row.append(box, lineNo, text);
+          group.appendChild(row);
+          return box;
+        });
+        fileBox.addEventListener('change', () => boxes.forEach(b => { b.checked = fileBox.checked; }));
+        entries.push({ file, boxes });
+        list.appendChild(group);
+      });
+      return entries;
+    }
+
+    async function previewReplace() {
+      if (!form.elements.pattern.value) return;
+      if (currentTask) invoke('cancel_task', { taskId: currentTask }).catch(() => {});
+      const taskId = `replace-${++taskCounter}`;
+      currentTask = taskId;
+      ranked.innerHTML = '';
+      list.innerHTML = '';
+      status.textContent = 'Searching\u2026';
+
+      let preview;
+      try {
+        preview = await invoke('preview_replace', {
+          query: buildQuery(),
+          replacement: form.elements.replacement.value,
+          taskId
+        });
+      } catch (err) {
+        if (currentTask === taskId && err?.code !== 'Cancelled') {
+          status.textContent = err?.message || String(err);
+        }
+        return;
+      }
+      if (currentTask !== taskId) return;
+      currentTask = null;
+
+      const count = preview.files.reduce((n, f) => n + f.matches.length, 0);
+      if (count === 0) { status.textContent = 'No matches'; return; }
+      const entries = renderReplacePreview(preview);
+
+      status.textContent = `${count} match${count === 1 ? '' : 'es'} in ${preview.files.length} files` +
+        (preview.truncated ? ' (stopped early) ' : ' ');
+      const applyBtn = document.createElement('button');
+      applyBtn.type = 'button';
+      applyBtn.textContent = 'Replace Selected';
+      applyBtn.addEventListener('click', async () => {
+        const files = entries
+          .map(({ file, boxes }) => ({
+            path: file.path,
+            fingerprint: file.fingerprint,
+            edits: file.matches.filter((_, i) => boxes[i].checked)
+          }))
+          .filter(f => f.edits.length);
+        if (!files.length) return;
+        applyBtn.disabled = true;
+        try {
+          const report = await invoke('apply_replace', { files });
+          const replaced = report.changed.reduce((n, f) => n + f.replacements, 0);
+          list.innerHTML = '';
+          status.textContent = `Replaced ${replaced} in ${report.changed.length} files`;
+          report.skipped.forEach(skip => {
+            const row = document.createElement('div');
+            row.className = 'search-skipped';
+            row.textContent = skip.error?.code === 'Conflict'
+              ? `Skipped ${utils.basename(skip.path)}: changed since the preview`
+              : `Skipped ${utils.basename(skip.path)}: ${skip.error?.message || skip.error}`;
+            list.appendChild(row);
+          });
+        } catch (err) {
+          status.textContent = err?.message || String(err);
+        }
+      });
+      status.appendChild(applyBtn);
+    }
+
+    form.elements.previewReplace.addEventListener('click', previewReplace);
+    form.addEventListener('submit', (e) => { e.preventDefault(); run(); });
+    form.addEventListener('change', (e) => {
+      if (e.target.type === 'checkbox' && form.elements.pattern.value) run();
+    });
+
+    function close() {
+      closed = true;
+      if (currentTask) invoke('cancel_task', { taskId: currentTask }).catch(() => {});
+      currentTask = null;
+      if (unlisten) unlisten();
+      overlay.classList.remove('visible');
+      const fallback = setTimeout(() => overlay.remove(), 300);
+      overlay.addEventListener('transitionend', (e) => {
+        if (e.target === overlay) { clearTimeout(fallback); overlay.remove(); }
+      }, { once: true });
+      document.removeEventListener('keydown', onKey);
+    }
+
+    function onKey(e) {
+      if (e.key === 'Escape') { e.preventDefault(); close(); }
+    }
+
+    closeBtn.addEventListener('click', close);
+    overlay.addEventListener('click', (e) => { if (e.target === overlay) close(); });
+    document.addEventListener('keydown', onKey);
+  }
+
+  return { open };
+})();
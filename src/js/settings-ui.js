@@ -1,6 +1,6 @@
 /**
  * Paddown — Settings UI
- * Settings modal with startup behavior options.
+ * Settings modal with startup, theme, HTML export and PDF export options.
  */
 window.Paddown = window.Paddown || {};
 
@@ -104,7 +104,111 @@ window.Paddown.settingsUI = (() => {
       themeSection.appendChild(row);
     });
 
-    card.append(header, closeBtn, section, themeSection);
+    // Section: HTML Export
+    const htmlSection = document.createElement('div');
+    htmlSection.className = 'settings-section';
+    htmlSection.style.marginTop = '1rem';
+
+    const htmlLabel = document.createElement('div');
+    htmlLabel.className = 'settings-section-label';
+    htmlLabel.textContent = 'HTML Export';
+    htmlSection.appendChild(htmlLabel);
+
+    const currentImages = settings.get('htmlImages');
+    const imageOptions = [
+      { value: 'keep', label: 'Keep image links as they are' },
+      { value: 'inline', label: 'Embed images in the page' },
+      { value: 'assets', label: 'Copy images to an assets folder' }
+    ];
+
+    imageOptions.forEach(opt => {
+      const row = document.createElement('label');
+      row.className = 'settings-radio';
+
+      const input = document.createElement('input');
+      input.type = 'radio';
+      input.name = 'htmlImages';
+      input.value = opt.value;
+      input.checked = opt.value === currentImages;
+
+      input.addEventListener('change', () => {
+        if (input.checked) settings.set('htmlImages', opt.value);
+      });
+
+      const text = document.createTextNode(opt.label);
+      row.appendChild(input);
+      row.appendChild(text);
+      htmlSection.appendChild(row);
+    });
+
+    // Section: PDF Export
+    const pdfSection = document.createElement('div');
+    pdfSection.className = 'settings-section';
+    pdfSection.style.marginTop = '1rem';
+
+    const pdfLabel = document.createElement('div');
+    pdfLabel.className = 'settings-section-label';
+    pdfLabel.textContent = 'PDF Export';
+    pdfSection.appendChild(pdfLabel);
+
+    const pageRow = document.createElement('label');
+    pageRow.className = 'settings-field';
+    const pageSelect = document.createElement('select');
+    [
+      { value: 'a4', label: 'A4' },
+      { value: 'letter', label: 'Letter' },
+      { value: 'legal', label: 'Legal' },
+      { value: 'a5', label: 'A5' }
+    ].forEach(opt => {
+      const option = document.createElement('option');
+      option.value = opt.value;
+      option.textContent = opt.label;
+      pageSelect.appendChild(option);
+    });
+    pageSelect.value = settings.get('pdfPageSize');
+    pageSelect.addEventListener('change', () => settings.set('pdfPageSize', pageSelect.value));
+    pageRow.append(document.createTextNode('Page size'), pageSelect);
+    pdfSection.appendChild(pageRow);
+
+    const marginRow = document.createElement('label');
+    marginRow.className = 'settings-field';
+    const marginInput = document.createElement('input');
+    marginInput.type = 'number';
+    marginInput.min = '0';
+    marginInput.max = '50';
+    marginInput.step = '1';
+    marginInput.value = settings.get('pdfMarginMm');
+    marginInput.addEventListener('change', () => {
+      const mm = Math.min(50, Math.max(0, Number(marginInput.value) || 0));
+      marginInput.value = mm;
+      settings.set('pdfMarginMm', mm);
+    });
+    marginRow.append(document.createTextNode('Margins (mm)'), marginInput);
+    pdfSection.appendChild(marginRow);
+
+    const pdfToggles = [
+      { key: 'pdfLandscape', label: 'Landscape' },
+      { key: 'pdfHeader', label: 'Title and date at the top of each page' },
+      { key: 'pdfPageNumbers', label: 'Page numbers' },
+      { key: 'pdfWrapCode', label: 'Wrap long lines in code blocks' }
+    ];
+
+    pdfToggles.forEach(opt => {
+      const row = document.createElement('label');
+      row.className = 'settings-radio';
+
+      const input = document.createElement('input');
+      input.type = 'checkbox';
+      input.checked = !!settings.get(opt.key);
+      input.addEventListener('change', () => settings.set(opt.key, input.checked));
+
+      const text = document.createTextNode(opt.label);
+      row.appendChild(input);
+      row.appendChild(text);
+      pdfSection.appendChild(row);
+    });
+
+    card.append(header, closeBtn, section, themeSection, htmlSection, pdfSection);
     overlay.appendChild(card);
     document.body.appendChild(overlay);
 
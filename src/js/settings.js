@@ -17,7 +17,14 @@ window.Paddown.settings = (() => {
     sidebarVisible: false,
     sidebarProjects: [],
     sidebarExpanded: {},
-    sidebarFileExtensions: ['md', 'markdown']
+    sidebarFileExtensions: ['md', 'markdown'],
+    htmlImages: 'keep',
+    pdfPageSize: 'a4',
+    pdfLandscape: false,
+    pdfMarginMm: 20,
+    pdfHeader: false,
+    pdfPageNumbers: true,
+    pdfWrapCode: true
   };
 
   const MAX_RECENT = 10;
@@ -38,7 +45,7 @@ window.Paddown.settings = (() => {
 
       let raw;
       if (portablePath) {
-        raw = await invoke('read_file', { path: portablePath });
+        raw = (await invoke('read_file', { path: portablePath })).contents;
       } else {
         raw = await invoke('read_settings');
       }
@@ -75,30 +75,35 @@ window.Paddown.sidebar = (() => {
     try {
       const path = await invoke('show_folder_dialog');
       if (!path) return;
+      await pinProject(path);
+    } catch (err) {
+      console.error('Add project failed:', err);
+    }
+  }
 
-      // Already added?
-      if (projects.some(p => normalizePath(p.path) === normalizePath(path))) return;
+  // Adds a folder the backend has already granted, e.g. one from the
+  // dialog or from `--folder` on the command line.
+  async function pinProject(path) {
+    // Already added?
+    if (projects.some(p => normalizePath(p.path) === normalizePath(path))) return;
 
-      const project = {
-        path,
-        displayName: null,
-        order: projects.length,
-        tree: null,
-        error: false
-      };
+    const project = {
+      path,
+      displayName: null,
+      order: projects.length,
+      tree: null,
+      error: false
+    };
 
-      projects.push(project);
+    projects.push(project);
 
-      // Show sidebar if hidden
-      if (!visible) show();
+    // Show sidebar if hidden
+    if (!visible) show();
 
-      await scanProject(project);
-      renderTree();
-      startWatching(project.path);
-      saveToSettings();
-    } catch (err) {
-      console.error('Add project failed:', err);
-    }
+    await scanProject(project);
+    renderTree();
+    startWatching(project.path);
+    saveToSettings();
   }
 
   function removeProject(path) {
@@ -109,6 +114,7 @@ window.Paddown.sidebar = (() => {
     if (!confirm(`Remove "${name}" from sidebar?\n\nFiles on disk will not be affected.`)) return;
 
     stopWatching(path);
+    invoke('unpin_folder', { path }).catch(() => {});
     projects.splice(idx, 1);
 
     // Clean up expanded nodes under this project
@@ -136,6 +142,12 @@ window.Paddown.sidebar = (() => {
 
   // ─── Scanning ──────────────────────────────────────────────
 
+  // One task id per project: starting a new scan of the same folder makes
+  // the backend cancel the one still running.
+  function scanTaskId(path) {
+    return 'scan:' + path;
+  }
+
   async function scanProject(project) {
     const { settings } = window.Paddown;
     const exts = settings.get('sidebarFileExtensions') || ['md', 'markdown'];
@@ -143,11 +155,15 @@ window.Paddown.sidebar = (() => {
     try {
       const tree = await invoke('scan_directory', {
         path: project.path,
-        extensions: exts
+        extensions: exts,
+        taskId: scanTaskId(project.path)
       });
       project.tree = tree;
       project.error = false;
     } catch (err) {
+      // Superseded by a newer scan (or the project was removed) — keep
+      // whatever the newer scan produces.
+      if (err?.code === 'Cancelled') return;
       project.tree = null;
       project.error = true;
       console.warn('Scan failed for', project.path, err);
@@ -293,6 +309,13 @@ window.Paddown.sidebar = (() => {
       // Click to expand/collapse
       el.addEventListener('click', () => toggleNode(entry.path));
 
+      // Context menu
+      el.addEventListener('contextmenu', (e) => {
+        e.preventDefault();
+        e.stopPropagation();
+        showDirContextMenu(e, entry.path);
+      });
+
       treeEl.appendChild(el);
 
       // Render children if expanded
@@ -413,7 +436,7 @@ window.Paddown.sidebar = (() => {
       // Load into blank untitled tab if available
       const active = tabs.getActiveTab();
       if (tabs.isTabBlankUntitled(active)) {
-        tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime);
+        tabs.loadIntoTab(active.id, result.content, filePath, result.lineEnding, result.mtime, result.fingerprint);
         editor.render();
       } else {
         tabs.createTab({
@@ -421,7 +444,8 @@ window.Paddown.sidebar = (() => {
           filePath,
           content: result.content,
           lineEnding: result.lineEnding,
-          lastModified: result.mtime
+          lastModified: result.mtime,
+          fingerprint: result.fingerprint
         });
       }
 
@@ -550,6 +574,9 @@ window.Paddown.sidebar = (() => {
 
   function showProjectContextMenu(e, project) {
     showContextMenu(e, [
+      { label: 'New File\u2026', action: () => newFile(project.path) },
+      { label: 'New Folder\u2026', action: () => newFolder(project.path) },
+      { type: 'separator' },
       { label: 'Rename\u2026', action: () => renameProject(project.path) },
       { label: 'Refresh', action: () => refreshProject(project.path) },
       { label: 'Collapse All', action: () => collapseAllUnder(project.path) },
@@ -558,9 +585,29 @@ window.Paddown.sidebar = (() => {
     ]);
   }
 
+  function showDirContextMenu(e, dirPath) {
+    showContextMenu(e, [
+      { label: 'New File\u2026', action: () => newFile(dirPath) },
+      { label: 'New Folder\u2026', action: () => newFolder(dirPath) },
+      { type: 'separator' },
+      { label: 'Rename\u2026', action: () => renameEntry(dirPath) },
+      { label: 'Move To\u2026', action: () => moveEntry(dirPath) },
+      { label: 'Duplicate', action: () => duplicateEntry(dirPath) },
+      { label: 'Delete', action: () => deleteEntry(dirPath, true) },
+      { type: 'separator' },
+      { label: 'Reveal in File Explorer', action: () => revealInExplorer(dirPath) }
+    ]);
+  }
+
   function showFileContextMenu(e, filePath) {
     showContextMenu(e, [
       { label: 'Open', action: () => openFile(filePath) },
+      { type: 'separator' },
+      { label: 'Rename\u2026', action: () => renameEntry(filePath) },
+      { label: 'Move To\u2026', action: () => moveEntry(filePath) },
+      { label: 'Duplicate', action: () => duplicateEntry(filePath) },
+      { label: 'Delete', action: () => deleteEntry(filePath, false) },
+      { type: 'separator' },
       { label: 'Reveal in File Explorer', action: () => revealInExplorer(filePath) }
     ]);
   }
@@ -579,6 +626,157 @@ window.Paddown.sidebar = (() => {
     }
   }
 
+  // ─── File Management ──────────────────────────────────────
+  // The tree isn't touched here: the project's watcher picks up each change
+  // and rescans through the usual fs-change path.
+
+  function pathSep(p) {
+    return p.includes('\\') ? '\\' : '/';
+  }
+
+  function joinPath(dir, name) {
+    const sep = pathSep(dir);
+    return dir.endsWith(sep) ? dir + name : dir + sep + name;
+  }
+
+  function parentPath(p) {
+    const i = Math.max(p.lastIndexOf('/'), p.lastIndexOf('\\'));
+    return i > 0 ? p.slice(0, i) : p;
+  }
+
+  function reportError(action, err) {
+    console.error(`${action} failed:`, err);
+    alert(`${action} failed: ${err?.message || err}`);
+  }
+
+  // Carries tabs and expanded-folder state over to an entry's new path.
+  function followEntry(oldPath, newPath) {
+    window.Paddown.tabs.retargetPath(oldPath, newPath);
+    const sep = pathSep(oldPath);
+    Object.keys(expandedNodes).forEach(k => {
+      if (k === oldPath || k.startsWith(oldPath + sep)) {
+        expandedNodes[newPath + k.slice(oldPath.length)] = expandedNodes[k];
+        delete expandedNodes[k];
+      }
+    });
+    saveExpandedState();
+  }
+
+  // After a rename or move, offers to fix relative links in the project's
+  // Markdown files that pointed at the entry or were written inside it.
+  async function updateLinks(oldPath, newPath) {
+    try {
+      const files = await invoke('preview_link_rewrites', { oldPath, newPath });
+      if (!files.length) return;
+      const links = files.reduce((n, f) => n + f.edits.length, 0);
+      const sample = files.flatMap(f => f.edits.map(e =>
+        `${folderName(f.path)}:${e.line}  ${e.old_url} \u2192 ${e.new_url}`));
+      const shown = sample.slice(0, 8).join('\n') + (sample.length > 8 ? '\n\u2026' : '');
+      const what = `${links} link${links === 1 ? '' : 's'} in ${files.length} file${files.length === 1 ? '' : 's'}`;
+      if (!confirm(`Update ${what}?\n\n${shown}`)) return;
+      await invoke('apply_link_rewrites', { files });
+    } catch (err) {
+      reportError('Updating links', err);
+    }
+  }
+
+  async function newFile(dir) {
+    let name = prompt('New file name:', 'Untitled.md');
+    if (!name) return;
+    name = name.trim();
+    if (!name.includes('.')) name += '.md';
+    try {
+      const path = await invoke('create_file', { dir, name });
+      expandedNodes[dir] = true;
+      saveExpandedState();
+      await openFile(path);
+    } catch (err) {
+      reportError('New file', err);
+    }
+  }
+
+  async function newFolder(dir) {
+    const name = prompt('New folder name:', 'New Folder');
+    if (!name) return;
+    try {
+      await invoke('create_folder', { dir, name: name.trim() });
+      expandedNodes[dir] = true;
+      saveExpandedState();
+    } catch (err) {
+      reportError('New folder', err);
+    }
+  }
+
+  async function renameEntry(path) {
+    const current = folderName(path);
+    const name = prompt('Rename to:', current);
+    if (!name || name.trim() === current) return;
+    try {
+      const newPath = await invoke('rename_entry', { path, newName: name.trim() });
+      followEntry(path, newPath);
+      await updateLinks(path, newPath);
+    } catch (err) {
+      reportError('Rename', err);
+    }
+  }
+
+  async function moveEntry(path) {
+    const project = projects.find(p => isUnderProject(path, p.path));
+    if (!project) return;
+
+    const currentDir = parentPath(path);
+    const relative = currentDir.length > project.path.length
+      ? currentDir.slice(project.path.length).replace(/^[\\/]+/, '')
+      : '';
+    const answer = prompt(
+      `Move "${folderName(path)}" to folder (relative to ${folderName(project.path)}):`,
+      relative
+    );
+    if (answer === null) return;
+
+    const destDir = answer.split(/[\\/]/).filter(Boolean).reduce(joinPath, project.path);
+    try {
+      const newPath = await invoke('move_entry', { path, destDir });
+      followEntry(path, newPath);
+      await updateLinks(path, newPath);
+    } catch (err) {
+      reportError('Move', err);
+    }
+  }
+
+  async function duplicateEntry(path) {
+    try {
+      await invoke('duplicate_entry', { path });
+    } catch (err) {
+      reportError('Duplicate', err);
+    }
+  }
+
+  async function deleteEntry(path, isDir) {
+    const what = isDir ? `"${folderName(path)}" and everything in it` : `"${folderName(path)}"`;
+    if (!confirm(`Move ${what} to the trash?\n\nUse Edit \u203A Undo Delete to bring it back.`)) return;
+    try {
+      await invoke('delete_entry', { path });
+    } catch (err) {
+      reportError('Delete', err);
+    }
+  }
+
+  // Puts the most recently deleted entries back where they were.
+  async function undoDelete() {
+    const { fileIO } = window.Paddown;
+    if (!fileIO.isDesktop()) return;
+    try {
+      const report = await invoke('undo_delete');
+      if (report.missing.length > 0) {
+        const lines = report.missing.map(m => `${m.path}: ${m.error.message}`);
+        alert(`Some items could not be restored:\n\n${lines.join('\n')}`);
+      }
+    } catch (err) {
+      reportError('Undo delete', err);
+    }
+  }
+
   // ─── Drag-to-Reorder Projects ─────────────────────────────
 
   let dragState = null;
@@ -684,6 +882,7 @@ window.Paddown.sidebar = (() => {
       clearTimeout(rescanTimers[path]);
       delete rescanTimers[path];
     }
+    invoke('cancel_task', { taskId: scanTaskId(path) }).catch(() => {});
     try {
       await invoke('stop_watching', { path });
     } catch (err) {
@@ -802,6 +1001,9 @@ window.Paddown.sidebar = (() => {
     hide,
     toggle,
     addProject,
+    pinProject,
+    openFile,
+    undoDelete,
     loadFromSettings,
     updateActiveHighlight,
     stopAllWatching,
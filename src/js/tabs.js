@@ -22,11 +22,14 @@ window.Paddown.tabs = (() => {
       savedContent: opts.content || '',
       lineEnding: opts.lineEnding || '\r\n',
       lastModified: opts.lastModified || null,
+      fingerprint: opts.fingerprint || null,
       scrollTop: 0,
       previewScrollTop: 0,
       cursorStart: 0,
       cursorEnd: 0,
-      isNew: !opts.filePath
+      isNew: !opts.filePath,
+      readonly: false,
+      editId: opts.editId || null
     };
   }
 
@@ -60,13 +63,15 @@ window.Paddown.tabs = (() => {
 
     tabs.forEach(tab => {
       const el = document.createElement('div');
-      el.className = 'tab-item' + (tab.id === activeTabId ? ' active' : '');
+      el.className = 'tab-item' + (tab.id === activeTabId ? ' active' : '') +
+        (tab.readonly ? ' readonly' : '');
       el.dataset.tabId = tab.id;
       const isDirty = isTabDirty(tab);
       const titleSpan = document.createElement('span');
       titleSpan.className = 'tab-title';
       titleSpan.textContent = (isDirty ? '\u25CF ' : '') + tab.title;
       el.appendChild(titleSpan);
+      if (tab.readonly) el.title = 'Read-only';
 
       const closeBtn = document.createElement('button');
       closeBtn.className = 'tab-close';
@@ -96,8 +101,21 @@ window.Paddown.tabs = (() => {
     return ta.value !== tab.savedContent;
   }
 
+  // Edit tabs are left out: their text goes back to the command that
+  // opened them rather than to a file.
   function getDirtyTabs() {
-    return tabs.filter(tab => isTabDirty(tab));
+    return tabs.filter(tab => !tab.editId && isTabDirty(tab));
+  }
+
+  // Sends an --edit-stdin tab's text to the process waiting for it.
+  function finishEdit(tab) {
+    const ta = getTextarea(tab.id);
+    const id = tab.editId;
+    tab.editId = null;
+    return window.__TAURI__.core.invoke('finish_edit', {
+      id,
+      contents: ta ? ta.value : tab.savedContent
+    }).catch(err => console.error('Finishing edit failed:', err));
   }
 
   function isTabBlankUntitled(tab) {
@@ -168,7 +186,8 @@ window.Paddown.tabs = (() => {
     const tab = tabs.find(t => t.id === tabId);
     if (!tab) return;
 
-    if (isTabDirty(tab)) {
+    // Closing an --edit-stdin tab is how its text gets handed back.
+    if (isTabDirty(tab) && !tab.editId) {
       const result = confirm(`"${tab.title}" has unsaved changes that will be lost.\n\nClose anyway?`);
       if (!result) return;
     }
@@ -180,6 +199,8 @@ window.Paddown.tabs = (() => {
     const idx = tabs.findIndex(t => t.id === tabId);
     if (idx === -1) return;
 
+    if (tabs[idx].editId) finishEdit(tabs[idx]);
+
     // Remove textarea
     const ta = getTextarea(tabId);
     if (ta) ta.remove();
@@ -212,7 +233,7 @@ window.Paddown.tabs = (() => {
 
   // ─── File State Helpers ─────────────────────────────────────
 
-  function markTabSaved(tabId, filePath, mtime) {
+  function markTabSaved(tabId, filePath, mtime, fingerprint) {
     const tab = tabs.find(t => t.id === tabId);
     const ta = getTextarea(tabId);
     if (!tab || !ta) return;
@@ -222,12 +243,13 @@ window.Paddown.tabs = (() => {
     tab.savedContent = ta.value;
     tab.isNew = false;
     if (mtime != null) tab.lastModified = mtime;
+    if (fingerprint) tab.fingerprint = fingerprint;
 
     renderTabBar();
     updateWindowTitle();
   }
 
-  function loadIntoTab(tabId, content, filePath, lineEnding, mtime) {
+  function loadIntoTab(tabId, content, filePath, lineEnding, mtime, fingerprint) {
     const tab = tabs.find(t => t.id === tabId);
     const ta = getTextarea(tabId);
     if (!tab || !ta) return;
@@ -238,6 +260,7 @@ window.Paddown.tabs = (() => {
     tab.lineEnding = lineEnding || '\r\n';
     tab.isNew = !filePath;
     tab.lastModified = mtime || null;
+    tab.fingerprint = fingerprint || null;
 
     ta.value = content;
     ta.setSelectionRange(0, 0);
@@ -247,6 +270,41 @@ window.Paddown.tabs = (() => {
     updateWindowTitle();
   }
 
+  // Read-only tabs keep their textarea from being edited; execCommand, which
+  // the toolbar and find/replace go through, honours that too.
+  function setReadOnly(tabId, readonly) {
+    const tab = tabs.find(t => t.id === tabId);
+    const ta = getTextarea(tabId);
+    if (!tab || !ta) return;
+    tab.readonly = readonly;
+    ta.readOnly = readonly;
+    renderTabBar();
+  }
+
+  // Follows a file or folder that was renamed or moved on disk, so tabs
+  // open on it (or on anything inside it) keep pointing at the right file.
+  function retargetPath(oldPath, newPath) {
+    let changed = false;
+    tabs.forEach(tab => {
+      if (!tab.filePath) return;
+      let next = null;
+      if (tab.filePath === oldPath) {
+        next = newPath;
+      } else if (tab.filePath.startsWith(oldPath + '/') || tab.filePath.startsWith(oldPath + '\\')) {
+        next = newPath + tab.filePath.slice(oldPath.length);
+      }
+      if (next) {
+        tab.filePath = next;
+        tab.title = window.Paddown.utils.basename(next);
+        changed = true;
+      }
+    });
+    if (changed) {
+      renderTabBar();
+      updateWindowTitle();
+    }
+  }
+
   // ─── Window Title ───────────────────────────────────────────
 
   function updateWindowTitle() {
@@ -421,6 +479,9 @@ window.Paddown.tabs = (() => {
     getActiveTextarea,
     markTabSaved,
     loadIntoTab,
+    setReadOnly,
+    finishEdit,
+    retargetPath,
     isTabDirty,
     getDirtyTabs,
     refreshDirtyState,
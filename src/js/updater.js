@@ -7,6 +7,8 @@ window.Paddown = window.Paddown || {};
 
 window.Paddown.updater = (() => {
   const CHECK_DELAY_MS = 5000; // wait 5s after launch before checking
+  const CHECK_TIMEOUT_MS = 20000; // give up on a slow network after 20s
+  const TASK_ID = 'update-check';
 
   function invoke(cmd, args) {
     return window.__TAURI__.core.invoke(cmd, args);
@@ -37,22 +39,29 @@ window.Paddown.updater = (() => {
   }
 
   async function check() {
+    const timeout = setTimeout(cancel, CHECK_TIMEOUT_MS);
     try {
       const currentVersion = await invoke('get_app_version');
-      const result = await invoke('check_for_updates', { currentVersion });
+      const result = await invoke('check_for_updates', { currentVersion, taskId: TASK_ID });
       if (result) {
         showUpdateBar(result.version, result.url);
       }
     } catch (err) {
       // Silently fail — update check is non-critical
       console.debug('Update check failed:', err);
+    } finally {
+      clearTimeout(timeout);
     }
   }
 
+  function cancel() {
+    invoke('cancel_task', { taskId: TASK_ID }).catch(() => {});
+  }
+
   function init() {
     if (!window.Paddown.fileIO.isDesktop()) return;
     setTimeout(check, CHECK_DELAY_MS);
   }
 
-  return { init, check };
+  return { init, check, cancel };
 })();